@@ -0,0 +1,18 @@
+use std::process::Command;
+
+/// Exposes the short git commit hash as `GIT_HASH` for `--version`, when built from a checkout.
+fn main() {
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+
+    let hash = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok());
+
+    if let Some(hash) = hash {
+        println!("cargo:rustc-env=GIT_HASH={}", hash.trim());
+    }
+}
@@ -1,30 +1,797 @@
-use config::{Config, File};
-use std::path::Path;
+use crate::ubx::decode_ubx_frame;
+use config::{Config, Environment, File, Map};
+use log::{info, warn, LevelFilter};
+use serde::{Deserialize, Serialize, Serializer};
+use serialport::{DataBits, FlowControl, Parity, StopBits};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Where GPS data is read from, selected with `source_type`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceType {
+    /// The receiver's serial port (`serial`)
+    Serial,
+    /// NMEA sentences relayed by a gpsd daemon (`gpsd`)
+    Gpsd,
+    /// NMEA datagrams broadcast over UDP, e.g. by marine gateways (`udp`)
+    Udp,
+}
+
+/// What happens when no valid fix arrives for `fix_timeout_secs`, selected with
+/// `fix_timeout_action`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FixTimeoutAction {
+    /// Publish `STATUS/FIX` = `lost` and keep running (`alert`)
+    Alert,
+    /// Publish `STATUS/FIX` = `lost` and exit with a nonzero code (`exit`)
+    Exit,
+}
+
+/// Which sentence the `SPD`, `SPD_MS` and `SPD_MPH` topics are taken from, selected with
+/// `speed_source`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpeedSource {
+    /// RMC speed over ground, nothing is published while the field is empty (`rmc`)
+    Rmc,
+    /// VTG speed over ground, nothing is published while the field is empty (`vtg`)
+    Vtg,
+    /// RMC while its speed field is filled, VTG otherwise (`auto`)
+    Auto,
+}
+
+/// How `LAT`, `LNG` and the other position topics are written, selected with
+/// `coordinate_format`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CoordinateFormat {
+    /// Signed decimal degrees, e.g. `49.274167` (`decimal`)
+    Decimal,
+    /// Degrees and decimal minutes with the hemisphere, e.g. `49°16.450'N` (`ddm`)
+    Ddm,
+    /// Degrees, minutes and seconds with the hemisphere, e.g. `49°16'27.0"N` (`dms`)
+    Dms,
+}
+
+/// Filter applied to the position published to `LAT_FILTERED` and `LNG_FILTERED`, selected with
+/// `position_filter`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PositionFilter {
+    /// No filtered position is published (`none`)
+    None,
+    /// Constant-velocity Kalman filter driven by the RMC speed and course (`kalman`)
+    Kalman,
+}
+
+/// How the MQTT broker is reached, selected with `mqtt_transport`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MqttTransport {
+    /// Plain MQTT over TCP, `mqtt://` (`tcp`)
+    Tcp,
+    /// MQTT over WebSocket, `ws://` (`ws`)
+    Ws,
+    /// MQTT over TLS-secured WebSocket, `wss://` (`wss`)
+    Wss,
+}
 
 /// Struct to hold the application configuration.
+///
+/// Serializes to the keys and value formats of the settings file, for `--print-config`.
+#[derive(Clone, PartialEq, Serialize)]
 pub struct AppConfig {
+    /// Where GPS data is read from (defaults to `Serial`).
+    pub source_type: SourceType,
+
+    /// Host of the gpsd daemon used with `SourceType::Gpsd`.
+    pub gpsd_host: String,
+
+    /// Port of the gpsd daemon used with `SourceType::Gpsd`.
+    pub gpsd_port: u16,
+
+    /// Local port NMEA datagrams are received on with `SourceType::Udp`.
+    pub udp_port: u16,
+
     /// The name of the serial port.
     pub port_name: String,
 
     /// The baud rate for the serial port.
     pub baud_rate: i64,
 
+    /// Probe common baud rates at startup instead of trusting `baud_rate`.
+    pub auto_baud: bool,
+
+    /// Serial data bits (defaults to 8).
+    #[serde(serialize_with = "serialize_as_u8")]
+    pub data_bits: DataBits,
+
+    /// Serial parity (defaults to none).
+    #[serde(serialize_with = "serialize_lowercase")]
+    pub parity: Parity,
+
+    /// Serial stop bits (defaults to 1).
+    #[serde(serialize_with = "serialize_as_u8")]
+    pub stop_bits: StopBits,
+
+    /// Serial flow control (defaults to none).
+    #[serde(serialize_with = "serialize_lowercase")]
+    pub flow_control: FlowControl,
+
     // Should the GPS sample rate be increased to 10Hz
     pub set_gps_to_10hz: bool,
 
+    /// Optional GPS measurement rate in Hz, sent to u-blox devices at startup.
+    /// Ignored when `set_gps_to_10hz` is true.
+    pub gps_rate_hz: Option<u16>,
+
+    /// Hex-encoded UBX frames written to the device after opening the port, e.g. UBX-CFG-MSG
+    /// commands enabling or disabling sentences.
+    pub ubx_init_commands: Vec<String>,
+
+    /// Milliseconds to wait for the UBX-ACK-ACK or UBX-ACK-NAK after each UBX command sent at
+    /// startup (defaults to 1000). 0 sends the commands without waiting.
+    pub ubx_ack_timeout_ms: u64,
+
+    /// Delay in milliseconds before reopening the serial port after a read error. It doubles
+    /// with each consecutive failure, up to `reconnect_max_ms`, and a random part of it is
+    /// skipped so receivers sharing a USB hub don't all reconnect at once.
+    pub reconnect_base_ms: u64,
+
+    /// Upper limit in milliseconds of the serial reopen delay.
+    pub reconnect_max_ms: u64,
+
+    /// Consecutive serial port failures after which reading stops, 0 retries forever.
+    pub max_consecutive_failures: u32,
+
     /// The MQTT broker host address.
     pub mqtt_host: String,
 
     /// The MQTT broker port number.
     pub mqtt_port: i64,
 
-    // The base topic of MQTT where data is pushed
+    /// Protocol the broker is connected with (defaults to `Tcp`).
+    pub mqtt_transport: MqttTransport,
+
+    /// Optional HTTP path of the broker's WebSocket endpoint, e.g. `/mqtt`. Only used with the
+    /// `Ws` and `Wss` transports.
+    pub mqtt_ws_path: Option<String>,
+
+    /// The base topic of MQTT where data is pushed, e.g. `/GOLF86/GPS/`. Topic suffixes are
+    /// appended directly, so a trailing `/` is added on load when missing.
     pub mqtt_base_topic: String,
+
+    /// Layout of every data topic, with the `{base}`, `{device}`, `{vehicle}` and `{field}`
+    /// placeholders. Defaults to `{base}{field}`.
+    pub topic_template: String,
+
+    /// Replacement names for `{field}`, keyed by the upper case default name, e.g. `ALT`.
+    pub topic_names: HashMap<String, String>,
+
+    /// Value of the `{vehicle}` placeholder in `topic_template`.
+    pub vehicle_id: Option<String>,
+
+    /// Identifies this instance: the `{device}` placeholder of `topic_template` and
+    /// `mqtt_lwt_topic`, and the `device_id` field of the JSON position. Defaults to the hostname.
+    pub device_id: String,
+
+    /// The MQTT Quality of Service level (0-2) used for data messages.
+    pub mqtt_qos: i32,
+
+    /// Whether data messages are published with the retained flag (defaults to true).
+    pub mqtt_retain: bool,
+
+    /// Optional topic for the MQTT Last Will and Testament message.
+    pub mqtt_lwt_topic: Option<String>,
+
+    /// Payload published on the LWT topic when the connection drops (defaults to `offline`).
+    pub mqtt_lwt_payload: Option<String>,
+
+    /// Optional fixed MQTT client ID. When absent, a stable ID is derived from the hostname and base topic.
+    pub mqtt_client_id: Option<String>,
+
+    /// MQTT protocol version, 3 for 3.1.1 (the default) or 5.
+    pub mqtt_version: u32,
+
+    /// MQTT 5 user properties attached to every published message, sorted by name, with
+    /// `{device}` and `{vehicle}` replaced by `device_id` and `vehicle_id`.
+    #[serde(serialize_with = "serialize_pairs")]
+    pub mqtt_user_properties: Vec<(String, String)>,
+
+    /// Publish each complete RMC+GGA fix as a single JSON document to `{base}POSITION`.
+    pub mqtt_json_mode: bool,
+
+    /// Advertise the feed as a `_gps-mqtt._tcp` mDNS service carrying the broker and base topic.
+    /// Requires the `mdns` Cargo feature (defaults to false).
+    pub mdns_advertise: bool,
+
+    /// Optional topic each complete fix is published to as a retained GeoJSON `Feature`.
+    /// `{device}` is replaced by `device_id`.
+    pub geojson_topic: Option<String>,
+
+    /// Optional topic a maps link to each valid fix is published to, retained. `{device}` is
+    /// replaced by `device_id`.
+    pub maps_url_topic: Option<String>,
+
+    /// URL published to `maps_url_topic`, with `{lat}` and `{lon}` replaced by the position in
+    /// decimal degrees (defaults to an OpenStreetMap marker link).
+    pub maps_url_template: String,
+
+    /// Optional debug topic every sentence with a valid checksum is published to unparsed, at
+    /// QoS 0 and not retained. `{device}` is replaced by `device_id`.
+    pub raw_nmea_topic: Option<String>,
+
+    /// Maximum number of topics remembered for change detection, e.g. of the time and date.
+    pub change_cache_size: usize,
+
+    /// Interval in seconds of the `STATUS/HEARTBEAT` liveness message, 0 disables it.
+    pub heartbeat_secs: u64,
+
+    /// Seconds without a valid position fix before `STATUS/FIX` turns `lost`, 0 disables it.
+    pub fix_timeout_secs: u64,
+
+    /// What to do when `fix_timeout_secs` is exceeded (defaults to `Alert`).
+    pub fix_timeout_action: FixTimeoutAction,
+
+    /// Publish the position of RMC and GLL sentences whose status is `V` (void), and of GNS
+    /// sentences without a fix. These carry empty coordinates that would be published as 0.0
+    /// (defaults to false).
+    pub publish_invalid_fixes: bool,
+
+    /// Publish the talker ID of the fix sentences (GGA, RMC, GLL and GNS), e.g. `GN` for a
+    /// combined fix or `GP` for GPS only, to `SAT/GLOBAL/TALKER` when it changes (defaults to
+    /// false).
+    pub publish_talker: bool,
+
+    /// Sentence the speed topics are taken from, so RMC and VTG don't publish conflicting
+    /// values (defaults to `Auto`).
+    pub speed_source: SpeedSource,
+
+    /// Format of the published latitudes and longitudes (defaults to `Decimal`). The JSON,
+    /// GeoJSON and file outputs always use decimal degrees.
+    pub coordinate_format: CoordinateFormat,
+
+    /// Decimal places of the published decimal-degree coordinates (defaults to 6, about 0.1m).
+    pub coordinate_decimals: usize,
+
+    /// Decimal places of the published speeds, course and vertical speed (defaults to 2).
+    pub speed_decimals: usize,
+
+    /// Decimal places of the published altitudes and geoidal separation (defaults to 1).
+    pub altitude_decimals: usize,
+
+    /// Minimum interval in milliseconds between position updates (`LAT`, `LNG`, `ALT`, `GLL_LAT`
+    /// and `GLL_LNG`), faster ones are dropped. 0 publishes every update.
+    pub position_min_interval_ms: u64,
+
+    /// Minimum interval in milliseconds between speed and course updates (`SPD*` and `CRS`),
+    /// faster ones are dropped. 0 publishes every update.
+    pub speed_min_interval_ms: u64,
+
+    /// Two-digit RMC years below this are in the 2000s, the others in the 1900s (defaults to 80,
+    /// covering 1980-2079).
+    pub year_pivot: u32,
+
+    /// Minimum level of log messages (`off`, `error`, `warn`, `info`, `debug` or `trace`).
+    /// The `RUST_LOG` environment variable takes precedence.
+    #[serde(serialize_with = "serialize_lowercase")]
+    pub log_level: LevelFilter,
+
+    /// Line entered on stdin to quit the application (defaults to `q`).
+    pub quit_keyword: String,
+
+    /// Optional path of a `.gpx` file the track is logged to. An existing file is replaced. With
+    /// several `[[sources]]`, each source is logged to its own file with its `device_id` added to
+    /// the file name.
+    pub gpx_output_path: Option<String>,
+
+    /// Optional path of a CSV file every RMC fix is logged to. An existing file is replaced.
+    pub csv_output_path: Option<String>,
+
+    /// Optional path of a SQLite database every valid fix is inserted into, created on first
+    /// run. Requires the `sqlite` Cargo feature.
+    pub sqlite_path: Option<String>,
+
+    /// Minimum movement in meters between fixes counted towards `TRIP/DISTANCE_M`, filtering
+    /// GPS jitter while stationary.
+    pub trip_min_distance_m: f64,
+
+    /// RMC speed in knots below which the vehicle is considered stationary, 0 disables the filter.
+    pub stationary_speed_threshold: f64,
+
+    /// Minimum movement in meters from the last published position before `LAT`/`LNG` are
+    /// published again while stationary.
+    pub stationary_min_distance_m: f64,
+
+    /// Number of samples the published `SPD` and `CRS` values are averaged over, 1 disables it.
+    pub smoothing_window: usize,
+
+    /// Filter smoothing the position published to `LAT_FILTERED`/`LNG_FILTERED`.
+    pub position_filter: PositionFilter,
+
+    /// Standard deviation in m/s of the movement not explained by the RMC speed and course, how
+    /// quickly the Kalman filter follows the measured positions.
+    pub position_filter_process_noise: f64,
+
+    /// Standard deviation in meters of the measured positions, how strongly the Kalman filter
+    /// smooths them.
+    pub position_filter_measurement_noise: f64,
+
+    /// Optional InfluxDB sink every complete fix is written to.
+    pub influxdb: Option<InfluxConfig>,
+
+    /// Circular zones whose enter/exit transitions are published to `GEOFENCE/{name}/STATE`.
+    pub geofences: Vec<Geofence>,
+
+    /// Receivers read at the same time, each in its own thread. Empty to read only the source
+    /// configured by the top-level keys.
+    pub sources: Vec<SourceConfig>,
+}
+
+/// Settings of the `[influxdb]` section.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct InfluxConfig {
+    /// Base URL of the InfluxDB server, e.g. `http://localhost:8086`.
+    pub url: String,
+
+    /// Bucket (or `database/retention-policy` on InfluxDB 1.8) the points are written to.
+    pub bucket: String,
+
+    /// Organization, required by InfluxDB 2 unless the token implies it.
+    pub org: Option<String>,
+
+    /// API token sent in the `Authorization` header. Redacted when serialized.
+    #[serde(serialize_with = "serialize_redacted")]
+    pub token: Option<String>,
+
+    /// Measurement name of the points.
+    #[serde(default = "default_influx_measurement")]
+    pub measurement: String,
+
+    /// Number of points written per request.
+    #[serde(default = "default_influx_batch_size")]
+    pub batch_size: usize,
+
+    /// Seconds after which a partial batch is written.
+    #[serde(default = "default_influx_flush_interval_secs")]
+    pub flush_interval_secs: u64,
+}
+
+fn default_influx_measurement() -> String {
+    "gps".to_string()
+}
+
+fn default_influx_batch_size() -> usize {
+    100
 }
 
+fn default_influx_flush_interval_secs() -> u64 {
+    10
+}
+
+/// Serializes a value by its lowercased `Display` form, e.g. `Parity::None` as `"none"`.
+fn serialize_lowercase<T: std::fmt::Display, S: Serializer>(
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&value.to_string().to_lowercase())
+}
+
+/// Serializes a value by its number, e.g. `DataBits::Eight` as `8`.
+fn serialize_as_u8<T: Copy + Into<u8>, S: Serializer>(
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u8((*value).into())
+}
+
+/// Serializes key-value pairs as a table, the way they are written in the settings file.
+fn serialize_pairs<S: Serializer>(
+    pairs: &[(String, String)],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(pairs.iter().map(|(key, value)| (key, value)))
+}
+
+/// Serializes a secret as `"<redacted>"`, so printed configurations don't leak it.
+fn serialize_redacted<S: Serializer>(
+    secret: &Option<String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match secret {
+        Some(_) => serializer.serialize_str("<redacted>"),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// A circular zone from a `[[geofences]]` table.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Geofence {
+    /// Name used in the `GEOFENCE/{name}/STATE` topic, a single topic level without `/`, `+`
+    /// or `#`.
+    pub name: String,
+
+    /// Latitude of the center in decimal degrees.
+    pub lat: f64,
+
+    /// Longitude of the center in decimal degrees.
+    pub lon: f64,
+
+    /// Radius in meters.
+    pub radius_m: f64,
+}
+
+/// A receiver from a `[[sources]]` table. Keys left out fall back to the top-level settings.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SourceConfig {
+    /// Identifies the source in its topics, must be unique.
+    pub device_id: String,
+
+    /// Where this source is read from.
+    pub source_type: Option<SourceType>,
+
+    /// Serial port of this source.
+    pub port_name: Option<String>,
+
+    /// Baud rate of this source's serial port.
+    pub baud_rate: Option<i64>,
+
+    /// Host of the gpsd daemon of this source.
+    pub gpsd_host: Option<String>,
+
+    /// Port of the gpsd daemon of this source.
+    pub gpsd_port: Option<u16>,
+
+    /// UDP port this source receives NMEA datagrams on.
+    pub udp_port: Option<u16>,
+}
+
+/// The settings file as written, before `parse_settings` converts and checks the values.
+///
+/// Keys left out take the defaults of the `Default` impl. Unknown keys are rejected, so a
+/// misspelled key is reported instead of silently falling back to its default.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawConfig {
+    source_type: String,
+    gpsd_host: String,
+    gpsd_port: i64,
+    udp_port: i64,
+    port_name: String,
+    baud_rate: i64,
+    auto_baud: bool,
+    data_bits: i64,
+    parity: String,
+    stop_bits: i64,
+    flow_control: String,
+    set_gps_to_10hz: bool,
+    gps_rate_hz: Option<i64>,
+    ubx_init_commands: Vec<String>,
+    ubx_ack_timeout_ms: i64,
+    reconnect_base_ms: i64,
+    reconnect_max_ms: i64,
+    max_consecutive_failures: i64,
+    mqtt_host: String,
+    mqtt_port: i64,
+    mqtt_transport: String,
+    mqtt_ws_path: Option<String>,
+    mqtt_base_topic: String,
+    topic_template: String,
+    topic_names: HashMap<String, String>,
+    vehicle_id: Option<String>,
+    device_id: Option<String>,
+    mqtt_qos: i64,
+    mqtt_retain: bool,
+    mqtt_lwt_topic: Option<String>,
+    mqtt_lwt_payload: Option<String>,
+    mqtt_client_id: Option<String>,
+    mqtt_version: i64,
+    mqtt_user_properties: BTreeMap<String, String>,
+    mqtt_json_mode: bool,
+    mdns_advertise: bool,
+    geojson_topic: Option<String>,
+    maps_url_topic: Option<String>,
+    maps_url_template: String,
+    raw_nmea_topic: Option<String>,
+    change_cache_size: i64,
+    heartbeat_secs: i64,
+    fix_timeout_secs: i64,
+    fix_timeout_action: String,
+    publish_invalid_fixes: bool,
+    publish_talker: bool,
+    speed_source: String,
+    coordinate_format: String,
+    coordinate_decimals: i64,
+    speed_decimals: i64,
+    altitude_decimals: i64,
+    position_min_interval_ms: i64,
+    speed_min_interval_ms: i64,
+    year_pivot: i64,
+    log_level: String,
+    quit_keyword: String,
+    gpx_output_path: Option<String>,
+    csv_output_path: Option<String>,
+    sqlite_path: Option<String>,
+    trip_min_distance_m: f64,
+    stationary_speed_threshold: f64,
+    stationary_min_distance_m: f64,
+    smoothing_window: i64,
+    position_filter: String,
+    position_filter_process_noise: f64,
+    position_filter_measurement_noise: f64,
+    influxdb: Option<InfluxConfig>,
+    geofences: Vec<Geofence>,
+    sources: Vec<SourceConfig>,
+}
+
+impl Default for RawConfig {
+    fn default() -> Self {
+        RawConfig {
+            source_type: "serial".to_string(),
+            gpsd_host: "localhost".to_string(),
+            gpsd_port: 2947,
+            udp_port: 10110,
+            port_name: "default_port".to_string(),
+            baud_rate: 9600,
+            auto_baud: false,
+            data_bits: 8,
+            parity: "none".to_string(),
+            stop_bits: 1,
+            flow_control: "none".to_string(),
+            set_gps_to_10hz: false,
+            gps_rate_hz: None,
+            ubx_init_commands: Vec::new(),
+            ubx_ack_timeout_ms: 1000,
+            reconnect_base_ms: 1000,
+            reconnect_max_ms: 10000,
+            max_consecutive_failures: 0,
+            mqtt_host: "default_host".to_string(),
+            mqtt_port: 1883,
+            mqtt_transport: "tcp".to_string(),
+            mqtt_ws_path: None,
+            mqtt_base_topic: "default_topic".to_string(),
+            topic_template: DEFAULT_TOPIC_TEMPLATE.to_string(),
+            topic_names: HashMap::new(),
+            vehicle_id: None,
+            device_id: None,
+            mqtt_qos: 0,
+            mqtt_retain: true,
+            mqtt_lwt_topic: None,
+            mqtt_lwt_payload: None,
+            mqtt_client_id: None,
+            mqtt_version: 3,
+            mqtt_user_properties: BTreeMap::new(),
+            mqtt_json_mode: false,
+            mdns_advertise: false,
+            geojson_topic: None,
+            maps_url_topic: None,
+            maps_url_template: DEFAULT_MAPS_URL_TEMPLATE.to_string(),
+            raw_nmea_topic: None,
+            change_cache_size: 4096,
+            heartbeat_secs: 10,
+            fix_timeout_secs: 0,
+            fix_timeout_action: "alert".to_string(),
+            publish_invalid_fixes: false,
+            publish_talker: false,
+            speed_source: "auto".to_string(),
+            coordinate_format: "decimal".to_string(),
+            coordinate_decimals: 6,
+            speed_decimals: 2,
+            altitude_decimals: 1,
+            position_min_interval_ms: 0,
+            speed_min_interval_ms: 0,
+            year_pivot: 80,
+            log_level: "warn".to_string(),
+            quit_keyword: "q".to_string(),
+            gpx_output_path: None,
+            csv_output_path: None,
+            sqlite_path: None,
+            trip_min_distance_m: 5.0,
+            stationary_speed_threshold: 0.0,
+            stationary_min_distance_m: 10.0,
+            smoothing_window: 1,
+            position_filter: "none".to_string(),
+            position_filter_process_noise: 1.0,
+            position_filter_measurement_noise: 5.0,
+            influxdb: None,
+            geofences: Vec::new(),
+            sources: Vec::new(),
+        }
+    }
+}
+
+/// Prefix of the environment variables that override configuration file values.
+const ENV_PREFIX: &str = "GPS2MQTT";
+
+/// Environment variable naming a directory searched first for `gps-to-mqtt.toml`. It shares the
+/// override prefix but is not a configuration key.
+const CONFIG_DIR_ENV: &str = "GPS2MQTT_CONFIG_DIR";
+
+/// Commented `settings.toml` template listing every known key with its default value.
+///
+/// Optional keys without a default are commented out.
+const CONFIG_TEMPLATE: &str = r#"# gps-to-mqtt configuration
+# Every key can also be set with a GPS2MQTT_-prefixed environment variable, e.g. GPS2MQTT_MQTT_HOST.
+
+# Where GPS data is read from: "serial", "gpsd" or "udp"
+source_type = "serial"
+# gpsd daemon used when source_type is "gpsd"
+gpsd_host = "localhost"
+gpsd_port = 2947
+# Port NMEA datagrams are received on when source_type is "udp"
+udp_port = 10110
+
+# Serial port the GPS receiver is connected to (see --list-ports)
+port_name = "/dev/ttyACM0"
+# Serial port baud rate
+baud_rate = 9600
+# Try 4800, 9600, 38400 and 115200 at startup and use the first that yields valid NMEA
+auto_baud = false
+# Serial framing: data bits (5-8), parity ("none", "odd" or "even") and stop bits (1 or 2)
+data_bits = 8
+parity = "none"
+stop_bits = 1
+# Serial flow control: "none", "software" (XON/XOFF) or "hardware" (RTS/CTS)
+flow_control = "none"
+# Switch u-blox receivers to 10Hz output at startup
+set_gps_to_10hz = false
+# Measurement rate in Hz sent to u-blox receivers (ignored when set_gps_to_10hz is true)
+# gps_rate_hz = 5
+# Hex-encoded UBX frames sent after opening the port, e.g. UBX-CFG-MSG disabling GxGSV
+# ubx_init_commands = ["B5 62 06 01 03 00 F0 03 00 FD 15"]
+# Milliseconds to wait for the receiver to acknowledge each UBX command (0 doesn't wait)
+ubx_ack_timeout_ms = 1000
+# Delay before reopening the serial port after a read error, doubled per consecutive failure
+reconnect_base_ms = 1000
+# Upper limit of the reopen delay
+reconnect_max_ms = 10000
+# Stop reading after this many consecutive serial port failures (0 retries forever)
+max_consecutive_failures = 0
+
+# MQTT broker host name or address
+mqtt_host = "localhost"
+# MQTT broker port
+mqtt_port = 1883
+# Connection protocol: tcp, ws (WebSocket) or wss (WebSocket over TLS)
+mqtt_transport = "tcp"
+# HTTP path of the broker's WebSocket endpoint, used with ws and wss
+# mqtt_ws_path = "/mqtt"
+# Prefix of every published topic
+mqtt_base_topic = "/GOLF86/GPS/"
+# Layout of every data topic: {base} is mqtt_base_topic, {vehicle} is vehicle_id and {field}
+# the data field (e.g. ALT), which can be renamed in [topic_names]
+topic_template = "{base}{field}"
+# vehicle_id = "golf86"
+# Identifies this instance in {device} and the JSON position (defaults to the hostname)
+# device_id = "golf86-pi"
+# Quality of Service level (0-2) for data messages
+mqtt_qos = 0
+# Publish data messages with the retained flag
+mqtt_retain = true
+# Topic for the retained online/offline status (Last Will and Testament)
+# mqtt_lwt_topic = "/GOLF86/GPS/STATUS" ({device} is replaced by device_id)
+# Payload published on the LWT topic when the connection drops
+# mqtt_lwt_payload = "offline"
+# Fixed MQTT client ID (derived from the hostname and base topic when unset)
+# mqtt_client_id = "gps-to-mqtt-golf86"
+# MQTT protocol version: 3 (3.1.1) or 5, needed for [mqtt_user_properties]
+mqtt_version = 3
+# Also publish each complete fix as one JSON document to {base}POSITION
+mqtt_json_mode = false
+# Advertise the feed via mDNS as _gps-mqtt._tcp (needs a build with the mdns feature)
+mdns_advertise = false
+# Publish each complete fix as a retained GeoJSON Feature, e.g. for Leaflet
+# geojson_topic = "/GOLF86/GPS/GEOJSON" ({device} is replaced by device_id)
+# Publish a retained maps link to each valid fix, e.g. for phone notifications
+# maps_url_topic = "/GOLF86/GPS/MAPS_URL" ({device} is replaced by device_id)
+# Link published to maps_url_topic, {lat} and {lon} are replaced by the position
+maps_url_template = "https://www.openstreetmap.org/?mlat={lat}&mlon={lon}"
+# Publish every raw sentence with a valid checksum for troubleshooting (QoS 0, not retained)
+# raw_nmea_topic = "/GOLF86/GPS/RAW" ({device} is replaced by device_id)
+# Number of topics remembered to skip unchanged values (least recently updated are evicted)
+change_cache_size = 4096
+# Seconds between STATUS/HEARTBEAT liveness messages (0 disables them)
+heartbeat_secs = 10
+# Seconds without a valid fix before STATUS/FIX turns "lost" (0 disables the watchdog)
+fix_timeout_secs = 0
+# On fix timeout: "alert" only publishes STATUS/FIX, "exit" also exits with a nonzero code
+fix_timeout_action = "alert"
+# Publish position and speed from RMC/GLL sentences with a void (V) status and from GNS
+# sentences with an N (no fix) mode for every constellation
+publish_invalid_fixes = false
+# Publish the talker ID of the fix sentences (GN, GP, GL, GA, GB) to SAT/GLOBAL/TALKER
+publish_talker = false
+# Sentence SPD is taken from: "rmc", "vtg" or "auto" (RMC when its speed field is filled, else VTG)
+speed_source = "auto"
+# Format of LAT, LNG and the other position topics: "decimal" (49.274167), "ddm" (49°16.450'N)
+# or "dms" (49°16'27.0"N)
+coordinate_format = "decimal"
+# Decimal places of published coordinates (6 is about 0.1m), speeds and course, and altitudes.
+# Trailing zeros are left out, e.g. 22.4 rather than 22.40
+coordinate_decimals = 6
+speed_decimals = 2
+altitude_decimals = 1
+# Minimum milliseconds between position updates (LAT, LNG, ALT, GLL_LAT, GLL_LNG), 0 sends all
+position_min_interval_ms = 0
+# Minimum milliseconds between speed and course updates (SPD*, CRS), 0 sends all
+speed_min_interval_ms = 0
+# Two-digit RMC years below this are 20xx, the others 19xx (80 covers 1980-2079)
+year_pivot = 80
+
+# Minimum log level: off, error, warn, info, debug or trace (RUST_LOG takes precedence)
+log_level = "warn"
+# Line entered on stdin to quit; closing a piped stdin quits as well
+quit_keyword = "q"
+
+# Log the track to a GPX file (replaced on startup)
+# gpx_output_path = "/var/log/gps-to-mqtt/track.gpx"
+# Log every fix to a CSV file (replaced on startup)
+# csv_output_path = "/var/log/gps-to-mqtt/fixes.csv"
+# Insert every valid fix into a SQLite database (needs a build with the sqlite feature)
+# sqlite_path = "/var/lib/gps-to-mqtt/track.db"
+
+# Minimum movement in meters between fixes counted towards TRIP/DISTANCE_M (filters jitter)
+trip_min_distance_m = 5.0
+# Speed in knots below which the vehicle is stationary: speed is published as 0 and position
+# updates closer than stationary_min_distance_m meters are suppressed (0 disables the filter)
+stationary_speed_threshold = 0.0
+stationary_min_distance_m = 10.0
+# Average SPD and CRS over this many samples to smooth high-rate jitter (1 disables it)
+smoothing_window = 1
+# Smooth the position published to LAT_FILTERED/LNG_FILTERED: "none" or "kalman"
+position_filter = "none"
+# Kalman filter tuning: standard deviation of the movement not explained by speed and course in
+# m/s (higher follows faster) and of the measured positions in meters (higher smooths more)
+position_filter_process_noise = 1.0
+position_filter_measurement_noise = 5.0
+
+# Names used for {field} in topic_template instead of the default ones
+# [topic_names]
+# ALT = "altitude"
+# "SAT/GLOBAL/NUM" = "satellites"
+
+# MQTT 5 user properties attached to every message ({device} and {vehicle} are replaced)
+# [mqtt_user_properties]
+# device_id = "{device}"
+# source = "gps-to-mqtt"
+
+# Write every complete fix to InfluxDB as line protocol
+# [influxdb]
+# url = "http://localhost:8086"
+# bucket = "telemetry"
+# org = "home"
+# token = "secret"
+# measurement = "gps"
+# batch_size = 100
+# flush_interval_secs = 10
+
+# Circular zones; GEOFENCE/{name}/STATE is published as inside/outside when a fix crosses one
+# [[geofences]]
+# name = "home"
+# lat = 56.9496
+# lon = 24.1052
+# radius_m = 100
+
+# Read several receivers at once, each published under its device_id. Keys left out of a
+# source use the top-level values, and topic_template gets a {device}/ level if it has none.
+# [[sources]]
+# device_id = "position"
+# port_name = "/dev/ttyACM0"
+# [[sources]]
+# device_id = "timing"
+# port_name = "/dev/ttyUSB0"
+# baud_rate = 38400
+"#;
+
 /// Load application configuration from a TOML file.
 ///
-/// This function reads the configuration settings from a TOML file.
+/// This function reads the configuration settings from a TOML file. Every key can be overridden
+/// by an environment variable named after it with a `GPS2MQTT_` prefix, e.g. `GPS2MQTT_MQTT_HOST`
+/// for `mqtt_host`. Precedence is environment > file > built-in default.
 ///
 /// # Arguments
 /// - `config_path`: An optional path to the configuration file.
@@ -32,28 +799,808 @@ pub struct AppConfig {
 /// # Returns
 /// Returns a `Result` containing either the `AppConfig` struct with the loaded configuration or an error message.
 pub fn load_configuration(config_path: Option<&str>) -> Result<AppConfig, String> {
-    let settings = if let Some(path) = config_path {
-        load_from_path(path)?
+    load_configuration_with_path(config_path).map(|(config, _)| config)
+}
+
+/// Loads the configuration like `load_configuration`, also returning the path of the file it
+/// was read from.
+///
+/// # Returns
+/// Returns the `AppConfig` and the file path, which is `None` when no default file was found and
+/// only the built-in defaults and environment overrides apply.
+pub fn load_configuration_with_path(
+    config_path: Option<&str>,
+) -> Result<(AppConfig, Option<PathBuf>), String> {
+    let (file_settings, path) = if let Some(path) = config_path {
+        (load_from_path(path)?, Some(PathBuf::from(path)))
     } else {
         load_default_paths()?
     };
 
+    let settings = Config::builder()
+        .add_source(file_settings)
+        .add_source(environment_source())
+        .build()
+        .map_err(|err| format!("{}", err))?;
+
+    parse_settings(&settings).map(|config| (config, path))
+}
+
+/// Builds an `AppConfig` from merged settings, applying defaults for missing keys.
+///
+/// # Arguments
+/// - `settings`: The merged file and environment settings.
+///
+/// # Returns
+/// Returns the `AppConfig`, or an error message for unknown keys, values of the wrong type and
+/// values that cannot be represented.
+fn parse_settings(settings: &Config) -> Result<AppConfig, String> {
+    let raw = settings
+        .clone()
+        .try_deserialize::<RawConfig>()
+        .map_err(|err| format!("{}", err))?;
+    from_raw(raw)
+}
+
+/// Checks and converts the deserialized settings into an `AppConfig`.
+fn from_raw(raw: RawConfig) -> Result<AppConfig, String> {
+    let source_type = match raw.source_type.as_str() {
+        "serial" => SourceType::Serial,
+        "gpsd" => SourceType::Gpsd,
+        "udp" => SourceType::Udp,
+        other => {
+            return Err(format!(
+                "Invalid source_type: {} (expected serial, gpsd or udp)",
+                other
+            ))
+        }
+    };
+
+    let gpsd_port = u16::try_from(raw.gpsd_port)
+        .map_err(|_| format!("Invalid gpsd_port: {}", raw.gpsd_port))?;
+
+    let udp_port =
+        u16::try_from(raw.udp_port).map_err(|_| format!("Invalid udp_port: {}", raw.udp_port))?;
+
+    let device_id = raw.device_id.unwrap_or_else(system_hostname);
+
+    let mqtt_lwt_topic = raw
+        .mqtt_lwt_topic
+        .map(|topic| topic.replace("{device}", &device_id));
+
+    let mqtt_version = match raw.mqtt_version {
+        3 | 5 => raw.mqtt_version as u32,
+        other => return Err(format!("Invalid mqtt_version: {} (expected 3 or 5)", other)),
+    };
+
+    let mqtt_user_properties = raw
+        .mqtt_user_properties
+        .into_iter()
+        .map(|(name, value)| {
+            let value = value
+                .replace("{device}", &device_id)
+                .replace("{vehicle}", raw.vehicle_id.as_deref().unwrap_or(""));
+            (name, value)
+        })
+        .collect();
+
+    let topic_names = raw
+        .topic_names
+        .into_iter()
+        .map(|(field, name)| (field.to_uppercase(), name))
+        .collect();
+
+    let data_bits = match raw.data_bits {
+        5 => DataBits::Five,
+        6 => DataBits::Six,
+        7 => DataBits::Seven,
+        8 => DataBits::Eight,
+        other => return Err(format!("Invalid data_bits: {} (expected 5-8)", other)),
+    };
+
+    let parity = match raw.parity.as_str() {
+        "none" => Parity::None,
+        "odd" => Parity::Odd,
+        "even" => Parity::Even,
+        other => {
+            return Err(format!(
+                "Invalid parity: {} (expected none, odd or even)",
+                other
+            ))
+        }
+    };
+
+    let stop_bits = match raw.stop_bits {
+        1 => StopBits::One,
+        2 => StopBits::Two,
+        other => return Err(format!("Invalid stop_bits: {} (expected 1 or 2)", other)),
+    };
+
+    let flow_control = match raw.flow_control.as_str() {
+        "none" => FlowControl::None,
+        "software" => FlowControl::Software,
+        "hardware" => FlowControl::Hardware,
+        other => {
+            return Err(format!(
+                "Invalid flow_control: {} (expected none, software or hardware)",
+                other
+            ))
+        }
+    };
+
+    let gps_rate_hz = match raw.gps_rate_hz {
+        Some(rate) => match u16::try_from(rate) {
+            Ok(rate) if rate > 0 => Some(rate),
+            _ => return Err(format!("Invalid gps_rate_hz: {}", rate)),
+        },
+        None => None,
+    };
+
+    let ubx_ack_timeout_ms = u64::try_from(raw.ubx_ack_timeout_ms)
+        .map_err(|_| format!("Invalid ubx_ack_timeout_ms: {}", raw.ubx_ack_timeout_ms))?;
+
+    let reconnect_base_ms = u64::try_from(raw.reconnect_base_ms)
+        .ok()
+        .filter(|ms| *ms > 0)
+        .ok_or_else(|| format!("Invalid reconnect_base_ms: {}", raw.reconnect_base_ms))?;
+
+    let reconnect_max_ms = u64::try_from(raw.reconnect_max_ms)
+        .ok()
+        .filter(|ms| *ms >= reconnect_base_ms)
+        .ok_or_else(|| {
+            format!(
+                "Invalid reconnect_max_ms: {} (must be at least reconnect_base_ms)",
+                raw.reconnect_max_ms
+            )
+        })?;
+
+    let max_consecutive_failures = u32::try_from(raw.max_consecutive_failures).map_err(|_| {
+        format!(
+            "Invalid max_consecutive_failures: {}",
+            raw.max_consecutive_failures
+        )
+    })?;
+
+    let mqtt_transport = match raw.mqtt_transport.as_str() {
+        "tcp" => MqttTransport::Tcp,
+        "ws" => MqttTransport::Ws,
+        "wss" => MqttTransport::Wss,
+        other => {
+            return Err(format!(
+                "Invalid mqtt_transport: {} (expected tcp, ws or wss)",
+                other
+            ))
+        }
+    };
+
+    let mqtt_ws_path = raw.mqtt_ws_path.map(|path| {
+        if path.starts_with('/') {
+            path
+        } else {
+            format!("/{}", path)
+        }
+    });
+
+    if !(0..=2).contains(&raw.mqtt_qos) {
+        return Err(format!(
+            "Invalid mqtt_qos: {} (expected 0, 1 or 2)",
+            raw.mqtt_qos
+        ));
+    }
+
+    let change_cache_size = usize::try_from(raw.change_cache_size)
+        .ok()
+        .filter(|size| *size > 0)
+        .ok_or_else(|| format!("Invalid change_cache_size: {}", raw.change_cache_size))?;
+
+    let heartbeat_secs = u64::try_from(raw.heartbeat_secs)
+        .map_err(|_| format!("Invalid heartbeat_secs: {}", raw.heartbeat_secs))?;
+
+    let fix_timeout_secs = u64::try_from(raw.fix_timeout_secs)
+        .map_err(|_| format!("Invalid fix_timeout_secs: {}", raw.fix_timeout_secs))?;
+
+    let fix_timeout_action = match raw.fix_timeout_action.as_str() {
+        "alert" => FixTimeoutAction::Alert,
+        "exit" => FixTimeoutAction::Exit,
+        other => {
+            return Err(format!(
+                "Invalid fix_timeout_action: {} (expected alert or exit)",
+                other
+            ))
+        }
+    };
+
+    let position_min_interval_ms = u64::try_from(raw.position_min_interval_ms).map_err(|_| {
+        format!(
+            "Invalid position_min_interval_ms: {}",
+            raw.position_min_interval_ms
+        )
+    })?;
+
+    let speed_min_interval_ms = u64::try_from(raw.speed_min_interval_ms).map_err(|_| {
+        format!(
+            "Invalid speed_min_interval_ms: {}",
+            raw.speed_min_interval_ms
+        )
+    })?;
+
+    let year_pivot = u32::try_from(raw.year_pivot)
+        .ok()
+        .filter(|pivot| *pivot <= 99)
+        .ok_or_else(|| format!("Invalid year_pivot: {} (expected 0-99)", raw.year_pivot))?;
+
+    let speed_source = match raw.speed_source.as_str() {
+        "rmc" => SpeedSource::Rmc,
+        "vtg" => SpeedSource::Vtg,
+        "auto" => SpeedSource::Auto,
+        other => {
+            return Err(format!(
+                "Invalid speed_source: {} (expected rmc, vtg or auto)",
+                other
+            ))
+        }
+    };
+
+    let coordinate_decimals = parse_decimals("coordinate_decimals", raw.coordinate_decimals)?;
+    let speed_decimals = parse_decimals("speed_decimals", raw.speed_decimals)?;
+    let altitude_decimals = parse_decimals("altitude_decimals", raw.altitude_decimals)?;
+
+    let coordinate_format = match raw.coordinate_format.as_str() {
+        "decimal" => CoordinateFormat::Decimal,
+        "ddm" => CoordinateFormat::Ddm,
+        "dms" => CoordinateFormat::Dms,
+        other => {
+            return Err(format!(
+                "Invalid coordinate_format: {} (expected decimal, ddm or dms)",
+                other
+            ))
+        }
+    };
+
+    let log_level = raw
+        .log_level
+        .parse::<LevelFilter>()
+        .map_err(|_| format!("Invalid log_level: {}", raw.log_level))?;
+
+    let quit_keyword = raw.quit_keyword.trim().to_string();
+    if quit_keyword.is_empty() {
+        return Err("Invalid quit_keyword: must not be empty".to_string());
+    }
+
+    if raw.trip_min_distance_m.is_nan() || raw.trip_min_distance_m < 0.0 {
+        return Err(format!(
+            "Invalid trip_min_distance_m: {}",
+            raw.trip_min_distance_m
+        ));
+    }
+
+    if raw.stationary_speed_threshold.is_nan() || raw.stationary_speed_threshold < 0.0 {
+        return Err(format!(
+            "Invalid stationary_speed_threshold: {}",
+            raw.stationary_speed_threshold
+        ));
+    }
+
+    if raw.stationary_min_distance_m.is_nan() || raw.stationary_min_distance_m < 0.0 {
+        return Err(format!(
+            "Invalid stationary_min_distance_m: {}",
+            raw.stationary_min_distance_m
+        ));
+    }
+
+    let smoothing_window = usize::try_from(raw.smoothing_window)
+        .ok()
+        .filter(|window| *window > 0)
+        .ok_or_else(|| format!("Invalid smoothing_window: {}", raw.smoothing_window))?;
+
+    let position_filter = match raw.position_filter.as_str() {
+        "none" => PositionFilter::None,
+        "kalman" => PositionFilter::Kalman,
+        other => {
+            return Err(format!(
+                "Invalid position_filter: {} (expected none or kalman)",
+                other
+            ))
+        }
+    };
+
+    for (key, value) in [
+        (
+            "position_filter_process_noise",
+            raw.position_filter_process_noise,
+        ),
+        (
+            "position_filter_measurement_noise",
+            raw.position_filter_measurement_noise,
+        ),
+    ] {
+        if !value.is_finite() || value <= 0.0 {
+            return Err(format!("Invalid {}: {} (expected above 0)", key, value));
+        }
+    }
+
     Ok(AppConfig {
-        port_name: settings
-            .get_string("port_name")
-            .unwrap_or_else(|_| "default_port".to_string()),
-        baud_rate: settings.get_int("baud_rate").unwrap_or(9600),
-        set_gps_to_10hz: settings.get_bool("set_gps_to_10hz").unwrap_or(false),
-        mqtt_host: settings
-            .get_string("mqtt_host")
-            .unwrap_or_else(|_| "default_host".to_string()),
-        mqtt_port: settings.get_int("mqtt_port").unwrap_or(1883),
-        mqtt_base_topic: settings
-            .get_string("mqtt_base_topic")
-            .unwrap_or_else(|_| "default_topic".to_string()),
+        source_type,
+        gpsd_host: raw.gpsd_host,
+        gpsd_port,
+        udp_port,
+        port_name: raw.port_name,
+        baud_rate: raw.baud_rate,
+        auto_baud: raw.auto_baud,
+        data_bits,
+        parity,
+        stop_bits,
+        flow_control,
+        set_gps_to_10hz: raw.set_gps_to_10hz,
+        gps_rate_hz,
+        ubx_init_commands: raw.ubx_init_commands,
+        ubx_ack_timeout_ms,
+        reconnect_base_ms,
+        reconnect_max_ms,
+        max_consecutive_failures,
+        mqtt_host: raw.mqtt_host,
+        mqtt_port: raw.mqtt_port,
+        mqtt_transport,
+        mqtt_ws_path,
+        mqtt_base_topic: normalize_base_topic(raw.mqtt_base_topic),
+        topic_template: raw.topic_template,
+        topic_names,
+        vehicle_id: raw.vehicle_id,
+        device_id,
+        mqtt_qos: raw.mqtt_qos as i32,
+        mqtt_retain: raw.mqtt_retain,
+        mqtt_lwt_topic,
+        mqtt_lwt_payload: raw.mqtt_lwt_payload,
+        mqtt_client_id: raw.mqtt_client_id,
+        mqtt_version,
+        mqtt_user_properties,
+        mqtt_json_mode: raw.mqtt_json_mode,
+        mdns_advertise: raw.mdns_advertise,
+        geojson_topic: raw.geojson_topic,
+        maps_url_topic: raw.maps_url_topic,
+        maps_url_template: raw.maps_url_template,
+        raw_nmea_topic: raw.raw_nmea_topic,
+        change_cache_size,
+        heartbeat_secs,
+        fix_timeout_secs,
+        fix_timeout_action,
+        publish_invalid_fixes: raw.publish_invalid_fixes,
+        publish_talker: raw.publish_talker,
+        speed_source,
+        coordinate_format,
+        coordinate_decimals,
+        speed_decimals,
+        altitude_decimals,
+        position_min_interval_ms,
+        speed_min_interval_ms,
+        year_pivot,
+        log_level,
+        quit_keyword,
+        gpx_output_path: raw.gpx_output_path,
+        csv_output_path: raw.csv_output_path,
+        sqlite_path: raw.sqlite_path,
+        trip_min_distance_m: raw.trip_min_distance_m,
+        stationary_speed_threshold: raw.stationary_speed_threshold,
+        stationary_min_distance_m: raw.stationary_min_distance_m,
+        smoothing_window,
+        position_filter,
+        position_filter_process_noise: raw.position_filter_process_noise,
+        position_filter_measurement_noise: raw.position_filter_measurement_noise,
+        influxdb: raw.influxdb,
+        geofences: raw.geofences,
+        sources: raw.sources,
     })
 }
 
+impl Default for AppConfig {
+    /// The configuration of an empty settings file, with the defaults listed in
+    /// `example.settings.toml`. `device_id` defaults to the hostname, and `port_name`, `mqtt_host`
+    /// and `mqtt_base_topic` are placeholders; `validate` fails until the first two are set.
+    fn default() -> Self {
+        from_raw(RawConfig::default()).expect("the built-in defaults are valid")
+    }
+}
+
+/// Builds an `AppConfig` in code, starting from `AppConfig::default()`.
+///
+/// Each setter takes the already converted value of the field it's named after, e.g.
+/// `SpeedSource::Vtg` for `speed_source`. Unlike in the settings file, `{device}` placeholders are
+/// kept as given. Nothing is validated, call `AppConfig::validate` on the result if needed.
+///
+/// ```
+/// use gps_to_mqtt::AppConfig;
+///
+/// let config = AppConfig::builder()
+///     .port_name("/dev/ttyACM0")
+///     .mqtt_host("localhost")
+///     .mqtt_base_topic("/GOLF86/GPS")
+///     .build();
+/// assert_eq!(config.topic("ALT"), "/GOLF86/GPS/ALT");
+/// ```
+#[derive(Clone, Default)]
+pub struct AppConfigBuilder {
+    config: AppConfig,
+}
+
+/// Generates a setter on `AppConfigBuilder` for each listed `AppConfig` field.
+macro_rules! builder_setters {
+    ($($field:ident: $ty:ty,)*) => {
+        impl AppConfigBuilder {
+            $(
+                #[doc = concat!("Sets `", stringify!($field), "`.")]
+                pub fn $field(mut self, $field: impl Into<$ty>) -> Self {
+                    self.config.$field = $field.into();
+                    self
+                }
+            )*
+        }
+    };
+}
+
+builder_setters! {
+        source_type: SourceType,
+        gpsd_host: String,
+        gpsd_port: u16,
+        udp_port: u16,
+        port_name: String,
+        baud_rate: i64,
+        auto_baud: bool,
+        data_bits: DataBits,
+        parity: Parity,
+        stop_bits: StopBits,
+        flow_control: FlowControl,
+        set_gps_to_10hz: bool,
+        gps_rate_hz: Option<u16>,
+        ubx_init_commands: Vec<String>,
+        ubx_ack_timeout_ms: u64,
+        reconnect_base_ms: u64,
+        reconnect_max_ms: u64,
+        max_consecutive_failures: u32,
+        mqtt_host: String,
+        mqtt_port: i64,
+        mqtt_transport: MqttTransport,
+        mqtt_ws_path: Option<String>,
+        mqtt_base_topic: String,
+        topic_template: String,
+        topic_names: HashMap<String, String>,
+        vehicle_id: Option<String>,
+        device_id: String,
+        mqtt_qos: i32,
+        mqtt_retain: bool,
+        mqtt_lwt_topic: Option<String>,
+        mqtt_lwt_payload: Option<String>,
+        mqtt_client_id: Option<String>,
+        mqtt_version: u32,
+        mqtt_user_properties: Vec<(String, String)>,
+        mqtt_json_mode: bool,
+        mdns_advertise: bool,
+        geojson_topic: Option<String>,
+        maps_url_topic: Option<String>,
+        maps_url_template: String,
+        raw_nmea_topic: Option<String>,
+        change_cache_size: usize,
+        heartbeat_secs: u64,
+        fix_timeout_secs: u64,
+        fix_timeout_action: FixTimeoutAction,
+        publish_invalid_fixes: bool,
+        publish_talker: bool,
+        speed_source: SpeedSource,
+        coordinate_format: CoordinateFormat,
+        coordinate_decimals: usize,
+        speed_decimals: usize,
+        altitude_decimals: usize,
+        position_min_interval_ms: u64,
+        speed_min_interval_ms: u64,
+        year_pivot: u32,
+        log_level: LevelFilter,
+        quit_keyword: String,
+        gpx_output_path: Option<String>,
+        csv_output_path: Option<String>,
+        sqlite_path: Option<String>,
+        trip_min_distance_m: f64,
+        stationary_speed_threshold: f64,
+        stationary_min_distance_m: f64,
+        smoothing_window: usize,
+        position_filter: PositionFilter,
+        position_filter_process_noise: f64,
+        position_filter_measurement_noise: f64,
+        influxdb: Option<InfluxConfig>,
+        geofences: Vec<Geofence>,
+        sources: Vec<SourceConfig>,
+}
+
+impl AppConfigBuilder {
+    /// Returns the built configuration, with the `/` separator appended to the base topic the
+    /// way the settings file does.
+    pub fn build(mut self) -> AppConfig {
+        self.config.mqtt_base_topic = normalize_base_topic(self.config.mqtt_base_topic);
+        self.config
+    }
+}
+
+/// Parses the defaults from `CONFIG_TEMPLATE`, for tests that need a complete `AppConfig`.
+#[cfg(test)]
+pub fn template_config() -> AppConfig {
+    let settings = Config::builder()
+        .add_source(File::from_str(CONFIG_TEMPLATE, config::FileFormat::Toml))
+        .build()
+        .unwrap();
+    parse_settings(&settings).unwrap()
+}
+
+/// Appends the `/` separator to a non-empty base topic that lacks it.
+fn normalize_base_topic(mut topic: String) -> String {
+    if !topic.is_empty() && !topic.ends_with('/') {
+        topic.push('/');
+    }
+    topic
+}
+
+/// Topic layout of earlier versions, with the field appended to the base topic.
+const DEFAULT_TOPIC_TEMPLATE: &str = "{base}{field}";
+
+/// Most decimal places of `coordinate_decimals`, `speed_decimals` and `altitude_decimals`, beyond
+/// which an f64 has no meaningful digits left.
+const MAX_DECIMALS: usize = 15;
+
+/// Checks a number of decimal places of the `*_decimals` keys.
+fn parse_decimals(key: &str, value: i64) -> Result<usize, String> {
+    usize::try_from(value)
+        .ok()
+        .filter(|decimals| *decimals <= MAX_DECIMALS)
+        .ok_or_else(|| format!("Invalid {}: {} (expected 0-{})", key, value, MAX_DECIMALS))
+}
+
+/// Default `maps_url_template`, an OpenStreetMap link with a marker at the position.
+const DEFAULT_MAPS_URL_TEMPLATE: &str = "https://www.openstreetmap.org/?mlat={lat}&mlon={lon}";
+
+/// Standard serial baud rates accepted for `baud_rate`.
+const STANDARD_BAUD_RATES: [i64; 10] = [
+    4800, 9600, 14400, 19200, 38400, 57600, 115200, 230400, 460800, 921600,
+];
+
+impl AppConfig {
+    /// Returns a builder starting from the defaults, see `AppConfigBuilder`.
+    pub fn builder() -> AppConfigBuilder {
+        AppConfigBuilder::default()
+    }
+
+    /// Builds the topic a data field is published to from `topic_template`.
+    ///
+    /// # Arguments
+    /// - `field`: The default field name, e.g. `ALT` or `SAT/GLOBAL/NUM`.
+    pub fn topic(&self, field: &str) -> String {
+        let name = self
+            .topic_names
+            .get(&field.to_uppercase())
+            .map(String::as_str)
+            .unwrap_or(field);
+
+        self.topic_template
+            .replace("{base}", &self.mqtt_base_topic)
+            .replace("{device}", &self.device_id)
+            .replace("{vehicle}", self.vehicle_id.as_deref().unwrap_or(""))
+            .replace("{field}", name)
+    }
+
+    /// Returns the configuration of every source to read.
+    ///
+    /// Without `[[sources]]` this is just a copy of this configuration. Otherwise each source gets
+    /// a copy with its own `device_id` and source settings, and a `{device}` level is added in
+    /// front of `{field}` when `topic_template` has none, so the sources' topics stay apart.
+    pub fn source_configs(&self) -> Vec<AppConfig> {
+        if self.sources.is_empty() {
+            return vec![self.clone()];
+        }
+
+        self.sources
+            .iter()
+            .map(|source| {
+                let mut config = self.clone();
+                config.sources = Vec::new();
+                config.device_id = source.device_id.clone();
+                if !config.topic_template.contains("{device}") {
+                    config.topic_template =
+                        config.topic_template.replace("{field}", "{device}/{field}");
+                }
+                if let Some(source_type) = source.source_type {
+                    config.source_type = source_type;
+                }
+                if let Some(port_name) = &source.port_name {
+                    config.port_name = port_name.clone();
+                }
+                if let Some(baud_rate) = source.baud_rate {
+                    config.baud_rate = baud_rate;
+                }
+                if let Some(gpsd_host) = &source.gpsd_host {
+                    config.gpsd_host = gpsd_host.clone();
+                }
+                if let Some(gpsd_port) = source.gpsd_port {
+                    config.gpsd_port = gpsd_port;
+                }
+                if let Some(udp_port) = source.udp_port {
+                    config.udp_port = udp_port;
+                }
+                config
+            })
+            .collect()
+    }
+
+    /// Checks the loaded values for mistakes that would otherwise only show up as connection
+    /// failures at runtime.
+    ///
+    /// Missing `port_name` and `mqtt_host` keys fall back to placeholder values, which are
+    /// rejected here so a typo in the TOML file is reported instead of silently ignored. The
+    /// serial settings are only checked when reading from the serial port. With `[[sources]]`,
+    /// each source is checked instead of the top-level source settings.
+    ///
+    /// # Returns
+    /// Returns `Ok(())` or an error message naming the invalid field and why.
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.sources.is_empty() {
+            return self.validate_sources();
+        }
+
+        if self.source_type == SourceType::Serial {
+            if self.port_name.is_empty() || self.port_name == "default_port" {
+                return Err("port_name is not set".to_string());
+            }
+
+            if !STANDARD_BAUD_RATES.contains(&self.baud_rate) {
+                return Err(format!(
+                    "baud_rate {} is not a standard rate (expected one of {:?})",
+                    self.baud_rate, STANDARD_BAUD_RATES
+                ));
+            }
+
+            // NMEA is 7-bit ASCII, UBX frames need all 8 bits
+            if matches!(self.data_bits, DataBits::Five | DataBits::Six) {
+                return Err(format!(
+                    "data_bits {} is too small for NMEA sentences (expected 7 or 8)",
+                    u8::from(self.data_bits)
+                ));
+            }
+            let sends_ubx = self.set_gps_to_10hz
+                || self.gps_rate_hz.is_some()
+                || !self.ubx_init_commands.is_empty();
+            if self.data_bits == DataBits::Seven && sends_ubx {
+                return Err(
+                    "data_bits 7 cannot carry the UBX frames sent for set_gps_to_10hz, gps_rate_hz or ubx_init_commands"
+                        .to_string(),
+                );
+            }
+            if self.flow_control == FlowControl::Software && self.data_bits != DataBits::Eight {
+                return Err("flow_control software requires data_bits 8".to_string());
+            }
+        }
+
+        for command in &self.ubx_init_commands {
+            decode_ubx_frame(command)
+                .map_err(|e| format!("invalid ubx_init_commands entry: {}", e))?;
+        }
+
+        if self.mqtt_host.is_empty() || self.mqtt_host == "default_host" {
+            return Err("mqtt_host is not set".to_string());
+        }
+
+        if !(1..=65535).contains(&self.mqtt_port) {
+            return Err(format!(
+                "mqtt_port {} is out of range (expected 1-65535)",
+                self.mqtt_port
+            ));
+        }
+
+        if self.mqtt_ws_path.is_some() && self.mqtt_transport == MqttTransport::Tcp {
+            return Err("mqtt_ws_path requires mqtt_transport ws or wss".to_string());
+        }
+
+        if !self.mqtt_user_properties.is_empty() && self.mqtt_version != 5 {
+            return Err("mqtt_user_properties requires mqtt_version 5".to_string());
+        }
+
+        if self.mqtt_base_topic.is_empty() && self.topic_template.contains("{base}") {
+            return Err("mqtt_base_topic must not be empty".to_string());
+        }
+
+        if !self.topic_template.contains("{field}") {
+            return Err(format!(
+                "topic_template {:?} must contain the {{field}} placeholder",
+                self.topic_template
+            ));
+        }
+
+        if self.maps_url_topic.is_some()
+            && !(self.maps_url_template.contains("{lat}")
+                && self.maps_url_template.contains("{lon}"))
+        {
+            return Err(format!(
+                "maps_url_template {:?} must contain the {{lat}} and {{lon}} placeholders",
+                self.maps_url_template
+            ));
+        }
+
+        if self.device_id.is_empty() || self.device_id.contains(['/', '+', '#']) {
+            return Err(format!(
+                "device_id {:?} must be a non-empty topic level without '/', '+' or '#'",
+                self.device_id
+            ));
+        }
+
+        if self.topic_template.contains("{vehicle}")
+            && self.vehicle_id.as_deref().is_none_or(str::is_empty)
+        {
+            return Err("topic_template uses {vehicle} but vehicle_id is not set".to_string());
+        }
+
+        for geofence in &self.geofences {
+            if geofence.name.is_empty() || geofence.name.contains(['/', '+', '#']) {
+                return Err(format!(
+                    "geofence name {:?} must be a non-empty topic level without '/', '+' or '#'",
+                    geofence.name
+                ));
+            }
+            if !(-90.0..=90.0).contains(&geofence.lat) || !(-180.0..=180.0).contains(&geofence.lon)
+            {
+                return Err(format!(
+                    "geofence {} center ({}, {}) is out of range",
+                    geofence.name, geofence.lat, geofence.lon
+                ));
+            }
+            if geofence.radius_m <= 0.0 {
+                return Err(format!(
+                    "geofence {} radius_m must be positive",
+                    geofence.name
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the `[[sources]]` device IDs are unique and that every source configuration
+    /// is valid.
+    fn validate_sources(&self) -> Result<(), String> {
+        for (i, source) in self.sources.iter().enumerate() {
+            if self.sources[..i]
+                .iter()
+                .any(|other| other.device_id == source.device_id)
+            {
+                return Err(format!(
+                    "device_id {:?} is used by more than one source",
+                    source.device_id
+                ));
+            }
+        }
+
+        for config in self.source_configs() {
+            config
+                .validate()
+                .map_err(|err| format!("source {}: {}", config.device_id, err))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Environment source for `GPS2MQTT_*` overrides.
+///
+/// Only the prefix separator is set: keys themselves contain underscores, so splitting on `_`
+/// would turn `GPS2MQTT_MQTT_HOST` into a nested `mqtt.host` key instead of `mqtt_host`.
+/// Values stay strings and are converted when `RawConfig` is deserialized, so
+/// `GPS2MQTT_MQTT_PORT=1884` is read as a number. Like misspelled file keys, variables with the
+/// prefix that name no known key are rejected, except `GPS2MQTT_CONFIG_DIR`, which selects the
+/// file instead.
+fn environment_source() -> Environment {
+    let variables: Map<String, String> = std::env::vars_os()
+        .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)))
+        .filter(|(key, _)| key != CONFIG_DIR_ENV)
+        .collect();
+
+    Environment::with_prefix(ENV_PREFIX)
+        .prefix_separator("_")
+        .source(Some(variables))
+}
+
 /// Loads the configuration from the specified path.
 ///
 /// This function attempts to load the configuration from the given file path.
@@ -75,48 +1622,612 @@ fn load_from_path(path: &str) -> Result<Config, String> {
         .map_err(|err| format!("{}", err))
 }
 
-/// Attempts to load the configuration from default paths.
+/// Returns the default configuration file locations in the order they are tried:
+/// 1. A `gps-to-mqtt.toml` file in the directory named by `GPS2MQTT_CONFIG_DIR`, when set.
+/// 2. `gps-to-mqtt/config.toml` in the XDG config directory, `$XDG_CONFIG_HOME` or
+///    `~/.config`.
+/// 3. A `settings.toml` file located in the same directory as the executable.
+/// 4. A `gps-to-mqtt.toml` file located at `/usr/etc/g86-car-telemetry/`.
+/// 5. A `gps-to-mqtt.toml` file located at `/etc/g86-car-telemetry/`.
+fn default_config_paths() -> Vec<PathBuf> {
+    let non_empty_var = |name| std::env::var_os(name).filter(|value| !value.is_empty());
+
+    let mut paths = Vec::new();
+    if let Some(dir) = non_empty_var(CONFIG_DIR_ENV) {
+        paths.push(Path::new(&dir).join("gps-to-mqtt.toml"));
+    }
+    let xdg_config_home = non_empty_var("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| non_empty_var("HOME").map(|home| Path::new(&home).join(".config")));
+    if let Some(config_home) = xdg_config_home {
+        paths.push(config_home.join("gps-to-mqtt").join("config.toml"));
+    }
+    if let Ok(exe_dir) = std::env::current_exe() {
+        let exe_dir = exe_dir.parent().unwrap_or_else(|| Path::new("."));
+        paths.push(exe_dir.join("settings.toml"));
+    }
+    paths.push(PathBuf::from("/usr/etc/g86-car-telemetry/gps-to-mqtt.toml"));
+    paths.push(PathBuf::from("/etc/g86-car-telemetry/gps-to-mqtt.toml"));
+    paths
+}
+
+/// Logs which configuration file the settings were read from.
 ///
-/// This function tries to load the configuration from the following locations in order:
-/// 1. A `settings.toml` file located in the same directory as the executable.
-/// 2. A `gps-to-mqtt.toml` file located at `/usr/etc/g86-car-telemetry/`.
-/// 3. A `gps-to-mqtt.toml` file located at `/etc/g86-car-telemetry/`.
+/// When no path was given on the command line, every default location holding a file is logged
+/// as well, with a warning when there are several: only the first one that loads is read, so
+/// edits to the others have no effect.
 ///
-/// If a configuration file is successfully loaded from any of these locations, it will be used.
-/// If none of the files are found or successfully loaded, the default configuration will be returned.
+/// # Arguments
+///
+/// * `config_path` - The configuration file given on the command line.
+/// * `used` - The file the configuration was loaded from, see `load_configuration_with_path`.
+pub fn log_config_files(config_path: Option<&str>, used: Option<&Path>) {
+    if config_path.is_none() {
+        let found: Vec<_> = default_config_paths()
+            .into_iter()
+            .filter(|path| path.is_file())
+            .collect();
+        for path in &found {
+            info!("Found default configuration file {}", path.display());
+        }
+        if let (true, Some(used)) = (found.len() > 1, used) {
+            warn!(
+                "{} default configuration files exist, only {} is read",
+                found.len(),
+                used.display()
+            );
+        }
+    }
+
+    match used {
+        Some(path) => info!("Using configuration file {}", path.display()),
+        None => info!("No configuration file found, using the built-in defaults"),
+    }
+}
+
+/// Attempts to load the configuration from the default paths.
+///
+/// The locations of `default_config_paths` are tried in order. If a configuration file is
+/// successfully loaded from any of these locations, it will be used and the later ones are
+/// ignored. If none of the files are found or successfully loaded, the default configuration
+/// will be returned.
 ///
 /// # Returns
 ///
-/// * `Ok(Config)` - If a configuration file is successfully loaded from any of the default paths.
+/// * `Ok((Config, Option<PathBuf>))` - The configuration and the path of the file it was loaded
+///   from, or the default configuration and `None` when no file was found.
 /// * `Err(String)` - If there is an error loading the configuration from all default paths.
-fn load_default_paths() -> Result<Config, String> {
-    if let Ok(exe_dir) = std::env::current_exe() {
-        let exe_dir = exe_dir.parent().unwrap_or_else(|| Path::new("."));
-        let default_path = exe_dir.join("settings.toml");
-
+fn load_default_paths() -> Result<(Config, Option<PathBuf>), String> {
+    for path in default_config_paths() {
         if let Ok(config) = Config::builder()
-            .add_source(File::with_name(default_path.to_str().unwrap()))
+            .add_source(File::from(path.as_path()))
             .build()
         {
-            return Ok(config);
+            return Ok((config, Some(path)));
         }
     }
 
-    if let Ok(config) = Config::builder()
-        .add_source(File::with_name(
-            "/usr/etc/g86-car-telemetry/gps-to-mqtt.toml",
-        ))
-        .build()
-    {
-        return Ok(config);
+    Ok((Config::default(), None))
+}
+
+/// Writes the commented configuration template to `path`.
+///
+/// An existing file is only replaced when `force` is set.
+///
+/// # Arguments
+///
+/// * `path` - Destination of the template.
+/// * `force` - Overwrite an existing file.
+///
+/// # Returns
+///
+/// * `io::Result<()>` - Success, or an `AlreadyExists` error when the file exists and `force` is not set
+pub fn write_config_template(path: &Path, force: bool) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .create_new(!force)
+        .truncate(true)
+        .open(path)?;
+
+    file.write_all(CONFIG_TEMPLATE.as_bytes())
+}
+
+/// Returns the hostname of the machine, falling back to `localhost` when it cannot be determined.
+pub fn system_hostname() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "localhost".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Serializes tests that load configuration, since environment overrides are process-wide.
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    #[test]
+    fn test_normalize_base_topic() {
+        assert_eq!(
+            normalize_base_topic("/GOLF86/GPS/".to_string()),
+            "/GOLF86/GPS/"
+        );
+        assert_eq!(
+            normalize_base_topic("/GOLF86/GPS".to_string()),
+            "/GOLF86/GPS/"
+        );
+        assert_eq!(normalize_base_topic(String::new()), "");
     }
 
-    if let Ok(config) = Config::builder()
-        .add_source(File::with_name("/etc/g86-car-telemetry/gps-to-mqtt.toml"))
-        .build()
-    {
-        return Ok(config);
+    #[test]
+    fn test_validate() {
+        assert_eq!(template_config().validate(), Ok(()));
+
+        let mut config = template_config();
+        config.port_name = "default_port".to_string();
+        assert_eq!(config.validate(), Err("port_name is not set".to_string()));
+
+        // 7E1 framing is fine for plain NMEA, but can't carry binary UBX frames
+        let mut config = template_config();
+        config.data_bits = DataBits::Seven;
+        config.parity = Parity::Even;
+        assert_eq!(config.validate(), Ok(()));
+        config.gps_rate_hz = Some(5);
+        assert!(config.validate().unwrap_err().starts_with("data_bits 7"));
+
+        let mut config = template_config();
+        config.data_bits = DataBits::Six;
+        assert!(config.validate().unwrap_err().starts_with("data_bits 6"));
+
+        let mut config = template_config();
+        config.baud_rate = 9601;
+        assert!(config.validate().unwrap_err().starts_with("baud_rate 9601"));
+
+        // The serial settings don't matter when reading from gpsd
+        config.port_name = "default_port".to_string();
+        config.source_type = SourceType::Gpsd;
+        assert_eq!(config.validate(), Ok(()));
+
+        let mut config = template_config();
+        config.mqtt_host = "default_host".to_string();
+        assert_eq!(config.validate(), Err("mqtt_host is not set".to_string()));
+
+        let mut config = template_config();
+        config.mqtt_port = 70000;
+        assert!(config
+            .validate()
+            .unwrap_err()
+            .starts_with("mqtt_port 70000"));
+
+        let mut config = template_config();
+        config.mqtt_ws_path = Some("/mqtt".to_string());
+        assert_eq!(
+            config.validate(),
+            Err("mqtt_ws_path requires mqtt_transport ws or wss".to_string())
+        );
+        config.mqtt_transport = MqttTransport::Wss;
+        assert_eq!(config.validate(), Ok(()));
+
+        let mut config = template_config();
+        config.maps_url_template = "https://maps.example.com/?q={lat}".to_string();
+        assert_eq!(config.validate(), Ok(()));
+        config.maps_url_topic = Some("/GOLF86/GPS/MAPS_URL".to_string());
+        assert!(config
+            .validate()
+            .unwrap_err()
+            .contains("must contain the {lat} and {lon} placeholders"));
+
+        let mut config = template_config();
+        config.mqtt_base_topic = String::new();
+        assert!(config
+            .validate()
+            .unwrap_err()
+            .starts_with("mqtt_base_topic"));
+
+        let mut config = template_config();
+        config.ubx_init_commands = vec!["B5 62 06 01 03 00 F0 03 00 FD 16".to_string()];
+        assert!(config
+            .validate()
+            .unwrap_err()
+            .starts_with("invalid ubx_init_commands entry: checksum mismatch"));
+
+        let mut config = template_config();
+        config.geofences = vec![Geofence {
+            name: "home".to_string(),
+            lat: 56.9496,
+            lon: 24.1052,
+            radius_m: 0.0,
+        }];
+        assert_eq!(
+            config.validate(),
+            Err("geofence home radius_m must be positive".to_string())
+        );
+
+        config.geofences[0].radius_m = 100.0;
+        config.geofences[0].name = "home/garage".to_string();
+        assert!(config.validate().unwrap_err().starts_with("geofence name"));
+        config.geofences[0].name = "home+".to_string();
+        assert!(config.validate().unwrap_err().starts_with("geofence name"));
     }
 
-    Ok(Config::default())
+    #[test]
+    fn test_topic_template() {
+        let config = template_config();
+        assert_eq!(config.topic("ALT"), "/GOLF86/GPS/ALT");
+        assert_eq!(config.topic("SAT/GLOBAL/NUM"), "/GOLF86/GPS/SAT/GLOBAL/NUM");
+
+        let settings = Config::builder()
+            .add_source(File::from_str(CONFIG_TEMPLATE, config::FileFormat::Toml))
+            .set_override("topic_template", "telemetry/{vehicle}/gps/{field}")
+            .unwrap()
+            .set_override("vehicle_id", "golf86")
+            .unwrap()
+            .set_override("topic_names.ALT", "altitude")
+            .unwrap()
+            .build()
+            .unwrap();
+        let config = parse_settings(&settings).unwrap();
+        assert_eq!(config.validate(), Ok(()));
+        assert_eq!(config.topic("ALT"), "telemetry/golf86/gps/altitude");
+        assert_eq!(config.topic("SPD"), "telemetry/golf86/gps/SPD");
+
+        let mut config = template_config();
+        config.device_id = "car-2".to_string();
+        config.topic_template = "{base}{device}/{field}".to_string();
+        assert_eq!(config.topic("LAT"), "/GOLF86/GPS/car-2/LAT");
+        assert_eq!(
+            config.topic("STATUS/HEARTBEAT"),
+            "/GOLF86/GPS/car-2/STATUS/HEARTBEAT"
+        );
+
+        config.device_id = "car/2".to_string();
+        assert!(config.validate().unwrap_err().starts_with("device_id"));
+
+        let mut config = template_config();
+        config.topic_template = "{base}gps".to_string();
+        assert!(config
+            .validate()
+            .unwrap_err()
+            .contains("must contain the {field} placeholder"));
+
+        config.topic_template = "{vehicle}/{field}".to_string();
+        assert_eq!(
+            config.validate(),
+            Err("topic_template uses {vehicle} but vehicle_id is not set".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_influxdb() {
+        assert_eq!(template_config().influxdb, None);
+
+        let toml = "[influxdb]\nurl = \"http://localhost:8086\"\nbucket = \"telemetry\"\n";
+        let settings = Config::builder()
+            .add_source(File::from_str(toml, config::FileFormat::Toml))
+            .build()
+            .unwrap();
+        let influxdb = parse_settings(&settings).unwrap().influxdb.unwrap();
+        assert_eq!(influxdb.url, "http://localhost:8086");
+        assert_eq!(influxdb.token, None);
+        assert_eq!(influxdb.measurement, "gps");
+        assert_eq!(influxdb.batch_size, 100);
+    }
+
+    #[test]
+    fn test_parse_mqtt_user_properties() {
+        let toml = format!(
+            "{}\nvehicle_id = \"golf86\"\ndevice_id = \"pi\"\n\n[mqtt_user_properties]\nsource = \"gps-to-mqtt\"\nDeviceId = \"{{vehicle}}-{{device}}\"\n",
+            CONFIG_TEMPLATE
+        );
+        let settings = Config::builder()
+            .add_source(File::from_str(&toml, config::FileFormat::Toml))
+            .build()
+            .unwrap();
+        let config = parse_settings(&settings).unwrap();
+        assert_eq!(
+            config.mqtt_user_properties,
+            [
+                ("DeviceId".to_string(), "golf86-pi".to_string()),
+                ("source".to_string(), "gps-to-mqtt".to_string()),
+            ]
+        );
+        assert_eq!(
+            config.validate(),
+            Err("mqtt_user_properties requires mqtt_version 5".to_string())
+        );
+
+        let mut config = config;
+        config.mqtt_version = 5;
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_parse_geofences() {
+        assert!(template_config().geofences.is_empty());
+
+        let toml = "[[geofences]]\nname = \"home\"\nlat = 56.9496\nlon = 24.1052\nradius_m = 100\n";
+        let settings = Config::builder()
+            .add_source(File::from_str(toml, config::FileFormat::Toml))
+            .build()
+            .unwrap();
+        let config = parse_settings(&settings).unwrap();
+        assert_eq!(
+            config.geofences,
+            vec![Geofence {
+                name: "home".to_string(),
+                lat: 56.9496,
+                lon: 24.1052,
+                radius_m: 100.0,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_source_configs() {
+        let config = template_config();
+        assert!(config.sources.is_empty());
+        let sources = config.source_configs();
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].topic("ALT"), "/GOLF86/GPS/ALT");
+
+        let toml = format!(
+            "{}\n[[sources]]\ndevice_id = \"position\"\n\n[[sources]]\ndevice_id = \"timing\"\nsource_type = \"gpsd\"\ngpsd_port = 2948\n",
+            CONFIG_TEMPLATE
+        );
+        let settings = Config::builder()
+            .add_source(File::from_str(&toml, config::FileFormat::Toml))
+            .build()
+            .unwrap();
+        let config = parse_settings(&settings).unwrap();
+        assert_eq!(config.validate(), Ok(()));
+
+        let sources = config.source_configs();
+        assert_eq!(sources.len(), 2);
+        assert_eq!(sources[0].source_type, SourceType::Serial);
+        assert_eq!(sources[0].port_name, "/dev/ttyACM0");
+        assert_eq!(sources[0].topic("ALT"), "/GOLF86/GPS/position/ALT");
+        assert_eq!(sources[1].source_type, SourceType::Gpsd);
+        assert_eq!(sources[1].gpsd_port, 2948);
+        assert_eq!(sources[1].topic("ALT"), "/GOLF86/GPS/timing/ALT");
+
+        let mut config = config;
+        config.sources[1].device_id = "position".to_string();
+        assert_eq!(
+            config.validate(),
+            Err("device_id \"position\" is used by more than one source".to_string())
+        );
+        config.sources[1].device_id = "timing".to_string();
+        config.sources[1].source_type = Some(SourceType::Serial);
+        config.sources[1].baud_rate = Some(1234);
+        assert!(config
+            .validate()
+            .unwrap_err()
+            .starts_with("source timing: baud_rate 1234"));
+    }
+
+    #[test]
+    fn test_default_config_paths() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let dir = std::env::temp_dir().join(format!("gps-to-mqtt-dir-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("gps-to-mqtt.toml"),
+            "port_name = \"/dev/ttyUSB0\"\n",
+        )
+        .unwrap();
+
+        std::env::set_var("GPS2MQTT_CONFIG_DIR", &dir);
+        std::env::set_var("XDG_CONFIG_HOME", "/home/pi/.config");
+        let paths = default_config_paths();
+        // Not taken for an unknown configuration key
+        let loaded = load_configuration_with_path(None);
+        std::env::remove_var("GPS2MQTT_CONFIG_DIR");
+        std::env::remove_var("XDG_CONFIG_HOME");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(paths[0], dir.join("gps-to-mqtt.toml"));
+        assert_eq!(
+            paths[1],
+            PathBuf::from("/home/pi/.config/gps-to-mqtt/config.toml")
+        );
+        assert!(paths[2].ends_with("settings.toml"));
+        assert_eq!(
+            paths[3..],
+            [
+                PathBuf::from("/usr/etc/g86-car-telemetry/gps-to-mqtt.toml"),
+                PathBuf::from("/etc/g86-car-telemetry/gps-to-mqtt.toml"),
+            ]
+        );
+        let (config, path) = loaded.unwrap();
+        assert_eq!(config.port_name, "/dev/ttyUSB0");
+        assert_eq!(path, Some(dir.join("gps-to-mqtt.toml")));
+    }
+
+    #[test]
+    fn test_serialize_config() {
+        let mut config = template_config();
+        config.influxdb = Some(InfluxConfig {
+            url: "http://localhost:8086".to_string(),
+            bucket: "gps".to_string(),
+            org: None,
+            token: Some("secret".to_string()),
+            measurement: default_influx_measurement(),
+            batch_size: default_influx_batch_size(),
+            flush_interval_secs: default_influx_flush_interval_secs(),
+        });
+        config.mqtt_user_properties = vec![("source".to_string(), "gps-to-mqtt".to_string())];
+
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(json["source_type"], "serial");
+        assert_eq!(json["data_bits"], 8);
+        assert_eq!(json["parity"], "none");
+        assert_eq!(json["stop_bits"], 1);
+        assert_eq!(json["log_level"], "warn");
+        assert_eq!(json["speed_source"], "auto");
+        assert_eq!(json["mqtt_user_properties"]["source"], "gps-to-mqtt");
+        assert_eq!(json["influxdb"]["token"], "<redacted>");
+        assert!(!json.to_string().contains("secret"));
+    }
+
+    #[test]
+    fn test_unknown_keys_rejected() {
+        let settings = Config::builder()
+            .add_source(File::from_str(
+                "mqtt_host = \"localhost\"\nmqtt_hots = \"broker\"\n",
+                config::FileFormat::Toml,
+            ))
+            .build()
+            .unwrap();
+        let err = parse_settings(&settings).err().unwrap();
+        assert!(err.contains("unknown field `mqtt_hots`"), "{}", err);
+
+        let settings = Config::builder()
+            .add_source(File::from_str(
+                "baud_rate = \"fast\"",
+                config::FileFormat::Toml,
+            ))
+            .build()
+            .unwrap();
+        let err = parse_settings(&settings).err().unwrap();
+        assert!(err.contains("baud_rate"), "{}", err);
+    }
+
+    #[test]
+    fn test_environment_overrides_file_values() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let path =
+            std::env::temp_dir().join(format!("gps-to-mqtt-env-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            "port_name = \"/dev/ttyACM0\"\nbaud_rate = 9600\nmqtt_host = \"localhost\"\nmqtt_port = 1883\n",
+        )
+        .unwrap();
+
+        std::env::set_var("GPS2MQTT_MQTT_HOST", "broker.example.com");
+        std::env::set_var("GPS2MQTT_BAUD_RATE", "115200");
+        std::env::set_var("GPS2MQTT_MQTT_RETAIN", "false");
+
+        let config = load_configuration(path.to_str());
+
+        std::env::remove_var("GPS2MQTT_MQTT_HOST");
+        std::env::remove_var("GPS2MQTT_BAUD_RATE");
+        std::env::remove_var("GPS2MQTT_MQTT_RETAIN");
+        std::fs::remove_file(&path).unwrap();
+
+        let config = config.unwrap();
+        assert_eq!(config.mqtt_host, "broker.example.com");
+        assert_eq!(config.baud_rate, 115200);
+        assert!(!config.mqtt_retain);
+        // Values without an override still come from the file
+        assert_eq!(config.port_name, "/dev/ttyACM0");
+        assert_eq!(config.mqtt_port, 1883);
+    }
+
+    #[test]
+    fn test_default_and_builder() {
+        // The template lists the same defaults as an empty settings file, apart from the
+        // placeholders of the keys that must be set
+        let config = AppConfig::default();
+        assert_eq!(config.validate(), Err("port_name is not set".to_string()));
+        assert!(
+            config
+                == AppConfig {
+                    port_name: config.port_name.clone(),
+                    mqtt_host: config.mqtt_host.clone(),
+                    mqtt_base_topic: config.mqtt_base_topic.clone(),
+                    ..template_config()
+                }
+        );
+
+        let config = AppConfig::builder()
+            .source_type(SourceType::Gpsd)
+            .mqtt_base_topic("/GOLF86/GPS")
+            .gps_rate_hz(5)
+            .speed_source(SpeedSource::Vtg)
+            .build();
+        assert_eq!(config.source_type, SourceType::Gpsd);
+        assert_eq!(config.mqtt_base_topic, "/GOLF86/GPS/");
+        assert_eq!(config.gps_rate_hz, Some(5));
+        assert_eq!(config.speed_source, SpeedSource::Vtg);
+        assert_eq!(config.mqtt_port, 1883);
+    }
+
+    #[test]
+    fn test_config_template_round_trip() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let path =
+            std::env::temp_dir().join(format!("gps-to-mqtt-init-{}.toml", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        write_config_template(&path, false).unwrap();
+        let err = write_config_template(&path, false).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        write_config_template(&path, true).unwrap();
+
+        let config = load_configuration(path.to_str());
+        std::fs::remove_file(&path).unwrap();
+
+        let config = config.unwrap();
+        assert_eq!(config.port_name, "/dev/ttyACM0");
+        assert_eq!(config.baud_rate, 9600);
+        assert!(!config.auto_baud);
+        assert_eq!(config.data_bits, DataBits::Eight);
+        assert_eq!(config.parity, Parity::None);
+        assert_eq!(config.stop_bits, StopBits::One);
+        assert_eq!(config.flow_control, FlowControl::None);
+        assert_eq!(config.mqtt_base_topic, "/GOLF86/GPS/");
+        assert_eq!(config.mqtt_qos, 0);
+        assert_eq!(config.mqtt_transport, MqttTransport::Tcp);
+        assert_eq!(config.mqtt_ws_path, None);
+        assert_eq!(config.mqtt_version, 3);
+        assert!(config.mqtt_user_properties.is_empty());
+        assert!(config.mqtt_retain);
+        assert_eq!(config.gps_rate_hz, None);
+        assert_eq!(config.heartbeat_secs, 10);
+        assert_eq!(config.ubx_ack_timeout_ms, 1000);
+        assert_eq!(config.reconnect_base_ms, 1000);
+        assert_eq!(config.reconnect_max_ms, 10000);
+        assert_eq!(config.max_consecutive_failures, 0);
+        assert_eq!(config.geojson_topic, None);
+        assert_eq!(config.maps_url_topic, None);
+        assert_eq!(config.maps_url_template, DEFAULT_MAPS_URL_TEMPLATE);
+        assert_eq!(config.raw_nmea_topic, None);
+        assert!(!config.mdns_advertise);
+        assert_eq!(config.device_id, system_hostname());
+        assert_eq!(config.fix_timeout_secs, 0);
+        assert_eq!(config.fix_timeout_action, FixTimeoutAction::Alert);
+        assert!(!config.publish_invalid_fixes);
+        assert!(!config.publish_talker);
+        assert_eq!(config.speed_source, SpeedSource::Auto);
+        assert_eq!(config.coordinate_format, CoordinateFormat::Decimal);
+        assert_eq!(
+            (
+                config.coordinate_decimals,
+                config.speed_decimals,
+                config.altitude_decimals
+            ),
+            (6, 2, 1)
+        );
+        assert_eq!(config.position_min_interval_ms, 0);
+        assert_eq!(config.speed_min_interval_ms, 0);
+        assert_eq!(config.year_pivot, 80);
+        assert_eq!(config.change_cache_size, 4096);
+        assert_eq!(config.log_level, LevelFilter::Warn);
+        assert_eq!(config.quit_keyword, "q");
+        assert_eq!(config.source_type, SourceType::Serial);
+        assert_eq!(config.gpsd_port, 2947);
+        assert_eq!(config.udp_port, 10110);
+        assert_eq!(config.trip_min_distance_m, 5.0);
+        assert_eq!(config.stationary_speed_threshold, 0.0);
+        assert_eq!(config.smoothing_window, 1);
+        assert_eq!(config.position_filter, PositionFilter::None);
+        assert_eq!(config.position_filter_process_noise, 1.0);
+        assert_eq!(config.position_filter_measurement_noise, 5.0);
+    }
 }
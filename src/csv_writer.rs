@@ -0,0 +1,109 @@
+use crate::gps_data_parser::PositionFix;
+use crate::speed_conversions::knots_to_kph;
+use log::{error, info};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::sync::Mutex;
+
+const CSV_HEADER: &str = "utc,date,lat,lon,alt,speed_kph,course,fix_quality,sats_used,device_id";
+
+lazy_static::lazy_static! {
+    static ref CSV_WRITER: Mutex<Option<CsvWriter<BufWriter<File>>>> = Mutex::new(None);
+}
+
+/// Writes position fixes as CSV rows.
+///
+/// Rows are buffered and only guaranteed to be on disk once the writer is flushed on quit.
+pub struct CsvWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> CsvWriter<W> {
+    /// Creates a CSV writer and writes the header row.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writeln!(writer, "{}", CSV_HEADER)?;
+        Ok(CsvWriter { writer })
+    }
+
+    /// Appends one row for the fix of the source `device_id`.
+    ///
+    /// The `utc` column carries the ISO-8601 timestamp and `date` its `YYYY-MM-DD` date part.
+    pub fn write_row(&mut self, fix: &PositionFix, device_id: &str) -> io::Result<()> {
+        let date = fix.timestamp.split('T').next().unwrap_or_default();
+        writeln!(
+            self.writer,
+            "{},{},{:.7},{:.7},{:.1},{:.2},{:.1},{},{},{}",
+            fix.timestamp,
+            date,
+            fix.lat,
+            fix.lon,
+            fix.alt,
+            knots_to_kph(fix.speed),
+            fix.course,
+            fix.fix_quality,
+            fix.sats_used,
+            device_id
+        )
+    }
+
+    /// Flushes buffered rows and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Opens the CSV file, replacing any existing file at `path`.
+///
+/// # Arguments
+///
+/// * `path` - Path of the `.csv` file to write
+pub fn init(path: &str) -> io::Result<()> {
+    let writer = CsvWriter::new(BufWriter::new(File::create(path)?))?;
+    *CSV_WRITER.lock().unwrap() = Some(writer);
+    info!("Logging fixes as CSV to {}", path);
+    Ok(())
+}
+
+/// Appends a fix of the source `device_id` to the CSV file if CSV logging is enabled.
+pub fn write_fix(fix: &PositionFix, device_id: &str) {
+    if let Some(writer) = CSV_WRITER.lock().unwrap().as_mut() {
+        if let Err(e) = writer.write_row(fix, device_id) {
+            error!("Error writing CSV row: {}", e);
+        }
+    }
+}
+
+/// Flushes and closes the CSV file if CSV logging is enabled.
+pub fn close() {
+    if let Some(writer) = CSV_WRITER.lock().unwrap().take() {
+        if let Err(e) = writer.finish() {
+            error!("Error closing CSV file: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gps_data_parser::test_fix;
+
+    #[test]
+    fn test_csv_rows() {
+        let fix = PositionFix {
+            speed: 10.0,
+            ..test_fix()
+        };
+
+        let mut writer = CsvWriter::new(Vec::new()).unwrap();
+        writer.write_row(&fix, "golf86-pi").unwrap();
+        let output = String::from_utf8(writer.finish().unwrap()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(
+            lines[1],
+            "2024-03-23T12:35:19Z,2024-03-23,48.1173000,11.5166667,545.4,18.52,84.4,1,8,golf86-pi"
+        );
+    }
+}
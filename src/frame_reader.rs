@@ -0,0 +1,232 @@
+use crate::ubx::{ubx_checksum, UbxMessage, UBX_OVERHEAD, UBX_SYNC};
+
+/// Longest NMEA sentence kept while waiting for its line ending. The standard allows 82
+/// characters; anything far longer is binary data that happened to contain a `$`.
+const MAX_NMEA_LEN: usize = 256;
+/// Largest UBX payload accepted. Larger length fields are treated as a false sync match.
+const MAX_UBX_PAYLOAD: usize = 4096;
+
+/// A complete frame extracted from the device's byte stream.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Frame {
+    /// An NMEA sentence from `$` up to, but excluding, the line ending
+    Nmea(String),
+    /// A UBX message whose checksum matched
+    Ubx(UbxMessage),
+}
+
+/// Splits a mixed stream of NMEA text and binary UBX frames into complete frames.
+///
+/// Bytes are buffered across reads, so a sentence or frame split over two reads is still
+/// delivered whole. NMEA sentences run from `$` to `\r` or `\n`, and UBX frames from the
+/// `0xB5 0x62` sync through the length field to the checksum, so `0x0A` bytes inside a UBX
+/// payload don't break the framing. Bytes outside either kind of frame are dropped.
+#[derive(Debug, Default)]
+pub struct FrameReader {
+    buffer: Vec<u8>,
+}
+
+impl FrameReader {
+    /// Adds received bytes and returns the frames completed by them, in stream order.
+    pub fn push(&mut self, data: &[u8]) -> Vec<Frame> {
+        self.buffer.extend_from_slice(data);
+
+        let mut frames = Vec::new();
+        loop {
+            let Some(start) = self.frame_start() else {
+                // Keep a trailing first sync byte, its second byte may be in the next read
+                let keep = usize::from(self.buffer.last() == Some(&UBX_SYNC[0]));
+                self.buffer.drain(..self.buffer.len() - keep);
+                break;
+            };
+            self.buffer.drain(..start);
+
+            let next = if self.buffer[0] == b'$' {
+                self.next_nmea()
+            } else {
+                self.next_ubx()
+            };
+
+            match next {
+                Some(Some(frame)) => frames.push(frame),
+                Some(None) => (),
+                None => break,
+            }
+        }
+
+        frames
+    }
+
+    /// Returns the offset of the first `$` or UBX sync in the buffer.
+    fn frame_start(&self) -> Option<usize> {
+        let nmea = self.buffer.iter().position(|byte| *byte == b'$');
+        let ubx = self
+            .buffer
+            .windows(UBX_SYNC.len())
+            .position(|window| window == UBX_SYNC);
+
+        match (nmea, ubx) {
+            (Some(nmea), Some(ubx)) => Some(nmea.min(ubx)),
+            (nmea, ubx) => nmea.or(ubx),
+        }
+    }
+
+    /// Takes the NMEA sentence at the start of the buffer.
+    ///
+    /// Returns `None` while the line ending hasn't arrived yet, and `Some(None)` when the
+    /// candidate was discarded because it was cut short by another frame or ran too long.
+    fn next_nmea(&mut self) -> Option<Option<Frame>> {
+        let end = self
+            .buffer
+            .iter()
+            .position(|byte| *byte == b'\r' || *byte == b'\n');
+        let interrupted = (1..self.buffer.len())
+            .find(|&i| self.buffer[i] == b'$' || self.buffer[i..].starts_with(&UBX_SYNC));
+
+        match (end, interrupted) {
+            (_, Some(next)) if end.is_none_or(|end| next < end) => {
+                self.buffer.drain(..next);
+                Some(None)
+            }
+            (Some(end), _) if end <= MAX_NMEA_LEN => {
+                let sentence = String::from_utf8_lossy(&self.buffer[..end]).into_owned();
+                self.buffer.drain(..end);
+                Some(Some(Frame::Nmea(sentence)))
+            }
+            (Some(end), _) => {
+                self.buffer.drain(..end);
+                Some(None)
+            }
+            (None, _) if self.buffer.len() > MAX_NMEA_LEN => {
+                self.buffer.drain(..1);
+                Some(None)
+            }
+            (None, _) => None,
+        }
+    }
+
+    /// Takes the UBX frame at the start of the buffer.
+    ///
+    /// Returns `None` while the frame is incomplete, and `Some(None)` when the sync bytes turned
+    /// out not to start a valid frame.
+    fn next_ubx(&mut self) -> Option<Option<Frame>> {
+        if self.buffer.len() < UBX_OVERHEAD {
+            return None;
+        }
+
+        let payload_len = u16::from_le_bytes([self.buffer[4], self.buffer[5]]) as usize;
+        if payload_len > MAX_UBX_PAYLOAD {
+            self.buffer.drain(..UBX_SYNC.len());
+            return Some(None);
+        }
+
+        let frame_len = payload_len + UBX_OVERHEAD;
+        if self.buffer.len() < frame_len {
+            return None;
+        }
+
+        let frame = &self.buffer[..frame_len];
+        if ubx_checksum(&frame[2..frame_len - 2]) != (frame[frame_len - 2], frame[frame_len - 1]) {
+            self.buffer.drain(..UBX_SYNC.len());
+            return Some(None);
+        }
+
+        let message = UbxMessage {
+            class: frame[2],
+            id: frame[3],
+            payload: frame[6..frame_len - 2].to_vec(),
+        };
+        self.buffer.drain(..frame_len);
+        Some(Some(Frame::Ubx(message)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ubx::build_ubx_frame;
+
+    const VTG: &str = "$GNVTG,054.7,T,034.4,M,005.5,N,010.2,K*56";
+
+    #[test]
+    fn test_frames_split_across_reads() {
+        let mut reader = FrameReader::default();
+        assert!(reader.push(b"garbage\r\n$GNVTG,054.7,T,").is_empty());
+        assert_eq!(
+            reader.push(b"034.4,M,005.5,N,010.2,K*56\r\n$GN"),
+            [Frame::Nmea(VTG.to_string())]
+        );
+        assert!(reader.push(b"GGA,123519").is_empty());
+    }
+
+    #[test]
+    fn test_cr_or_lf_only_line_endings() {
+        let gga = "$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47";
+        let expected = [Frame::Nmea(VTG.to_string()), Frame::Nmea(gga.to_string())];
+
+        for ending in ["\r", "\n", "\r\n"] {
+            let mut reader = FrameReader::default();
+            let data = format!("{}{}{}{}", VTG, ending, gga, ending);
+            assert_eq!(reader.push(data.as_bytes()), expected, "{:?}", ending);
+        }
+    }
+
+    #[test]
+    fn test_ubx_frame_containing_line_feeds() {
+        // A payload full of 0x0A and '$' bytes must not be taken for NMEA
+        let payload = [0x0A, b'$', 0x0D, 0x0A, 0x24, 0x0A];
+        let frame = build_ubx_frame(0x0A, 0x09, &payload);
+
+        let mut data = format!("{}\r\n", VTG).into_bytes();
+        data.extend_from_slice(&frame);
+        data.extend_from_slice(format!("{}\r\n", VTG).as_bytes());
+
+        let mut reader = FrameReader::default();
+        let mut frames = Vec::new();
+        // Feed byte by byte to exercise every partial state
+        for byte in &data {
+            frames.extend(reader.push(&[*byte]));
+        }
+
+        assert_eq!(
+            frames,
+            [
+                Frame::Nmea(VTG.to_string()),
+                Frame::Ubx(UbxMessage {
+                    class: 0x0A,
+                    id: 0x09,
+                    payload: payload.to_vec(),
+                }),
+                Frame::Nmea(VTG.to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_corrupt_frames_are_dropped() {
+        let mut frame = build_ubx_frame(0x06, 0x08, &[0x64, 0x00, 0x01, 0x00, 0x01, 0x00]);
+        frame[7] ^= 0xFF;
+
+        // A sentence cut short by a UBX frame and a frame with a bad checksum
+        let mut data = b"$GNGGA,1235".to_vec();
+        data.extend_from_slice(&frame);
+        data.extend_from_slice(format!("{}\n", VTG).as_bytes());
+
+        let mut reader = FrameReader::default();
+        assert_eq!(reader.push(&data), [Frame::Nmea(VTG.to_string())]);
+
+        // Overlong lines are dropped, and an unterminated one doesn't stall the reader
+        let mut data = vec![b'$'];
+        data.extend_from_slice(&[b'A'; MAX_NMEA_LEN]);
+        data.extend_from_slice(format!("\r\n{}\n", VTG).as_bytes());
+        assert_eq!(reader.push(&data), [Frame::Nmea(VTG.to_string())]);
+
+        let mut data = vec![b'$'];
+        data.extend_from_slice(&[b'A'; MAX_NMEA_LEN]);
+        assert!(reader.push(&data).is_empty());
+        assert_eq!(
+            reader.push(format!("{}\n", VTG).as_bytes()),
+            [Frame::Nmea(VTG.to_string())]
+        );
+    }
+}
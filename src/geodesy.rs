@@ -0,0 +1,90 @@
+/// Mean Earth radius in meters, as used by the haversine formula.
+pub const EARTH_RADIUS_M: f64 = 6_371_000.0;
+
+/// Great-circle distance in meters between two points given in decimal degrees.
+///
+/// Uses the haversine formula on a spherical Earth, which is accurate to about 0.5%.
+pub fn haversine_distance_m(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let d_lat = (lat2 - lat1).to_radians();
+    let d_lon = (lon2 - lon1).to_radians();
+
+    let a = (d_lat / 2.0).sin().powi(2)
+        + lat1.to_radians().cos() * lat2.to_radians().cos() * (d_lon / 2.0).sin().powi(2);
+
+    2.0 * EARTH_RADIUS_M * a.sqrt().asin()
+}
+
+/// Initial bearing in degrees (0-360, clockwise from north) on the great circle from the first
+/// point to the second, both given in decimal degrees.
+pub fn initial_bearing_deg(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let d_lon = (lon2 - lon1).to_radians();
+
+    let y = d_lon.sin() * lat2.cos();
+    let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * d_lon.cos();
+
+    y.atan2(x).to_degrees().rem_euclid(360.0)
+}
+
+/// Moves a point given in decimal degrees by `north_m` and `east_m` meters.
+///
+/// Uses a flat-Earth approximation around the point, which is accurate for the few meters
+/// between consecutive fixes but not for long distances or close to the poles.
+pub fn offset_position(lat: f64, lon: f64, north_m: f64, east_m: f64) -> (f64, f64) {
+    let meters_per_degree = EARTH_RADIUS_M.to_radians();
+    (
+        lat + north_m / meters_per_degree,
+        lon + east_m / (meters_per_degree * lat.to_radians().cos()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_haversine_distance_known_pairs() {
+        assert_eq!(
+            haversine_distance_m(48.1173, 11.5167, 48.1173, 11.5167),
+            0.0
+        );
+
+        // One degree of latitude along a meridian
+        let one_degree = EARTH_RADIUS_M * std::f64::consts::PI / 180.0;
+        assert!((haversine_distance_m(0.0, 0.0, 1.0, 0.0) - one_degree).abs() < 1e-6);
+
+        // Paris to London, about 343.5 km
+        let distance = haversine_distance_m(48.8566, 2.3522, 51.5074, -0.1278);
+        assert!((distance - 343_500.0).abs() < 1_000.0, "{}", distance);
+
+        // Riga to Munich, about 1291 km
+        let distance = haversine_distance_m(56.9496, 24.1052, 48.1351, 11.5820);
+        assert!((distance - 1_291_300.0).abs() < 1_000.0, "{}", distance);
+
+        // Symmetric
+        let there = haversine_distance_m(48.8566, 2.3522, 51.5074, -0.1278);
+        let back = haversine_distance_m(51.5074, -0.1278, 48.8566, 2.3522);
+        assert!((there - back).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_offset_position() {
+        let (lat, lon) = offset_position(56.9496, 24.1052, 30.0, -40.0);
+        let distance = haversine_distance_m(56.9496, 24.1052, lat, lon);
+        assert!((distance - 50.0).abs() < 0.01, "{}", distance);
+        let bearing = initial_bearing_deg(56.9496, 24.1052, lat, lon);
+        assert!((bearing - 306.87).abs() < 0.01, "{}", bearing);
+    }
+
+    #[test]
+    fn test_initial_bearing() {
+        assert!((initial_bearing_deg(0.0, 0.0, 1.0, 0.0) - 0.0).abs() < 1e-9);
+        assert!((initial_bearing_deg(0.0, 0.0, 0.0, 1.0) - 90.0).abs() < 1e-9);
+        assert!((initial_bearing_deg(1.0, 0.0, 0.0, 0.0) - 180.0).abs() < 1e-9);
+        assert!((initial_bearing_deg(0.0, 1.0, 0.0, 0.0) - 270.0).abs() < 1e-9);
+
+        // Paris to London, about 330 degrees
+        let bearing = initial_bearing_deg(48.8566, 2.3522, 51.5074, -0.1278);
+        assert!((bearing - 330.0).abs() < 1.0, "{}", bearing);
+    }
+}
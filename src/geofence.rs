@@ -0,0 +1,114 @@
+use crate::config::{AppConfig, Geofence};
+use crate::geodesy::haversine_distance_m;
+use crate::mqtt_handler::{publish_message, Publisher};
+use log::{error, info};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+lazy_static::lazy_static! {
+    /// Whether the last fix was inside each geofence, keyed by `device_id` and geofence name.
+    static ref GEOFENCE_STATES: Mutex<HashMap<String, HashMap<String, bool>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Returns the geofences whose inside/outside state changed with this position.
+///
+/// The first position seen for a geofence always counts as a transition, so its initial state
+/// gets published.
+///
+/// # Arguments
+///
+/// * `states` - The last known state of each geofence, updated in place.
+/// * `geofences` - The configured geofences.
+/// * `lat`, `lon` - The current position in decimal degrees.
+///
+/// # Returns
+///
+/// * `Vec<(&Geofence, bool)>` - The changed geofences and whether the position is now inside
+fn update_states<'a>(
+    states: &mut HashMap<String, bool>,
+    geofences: &'a [Geofence],
+    lat: f64,
+    lon: f64,
+) -> Vec<(&'a Geofence, bool)> {
+    geofences
+        .iter()
+        .filter_map(|geofence| {
+            let inside =
+                haversine_distance_m(lat, lon, geofence.lat, geofence.lon) <= geofence.radius_m;
+            let previous = states.insert(geofence.name.clone(), inside);
+            (previous != Some(inside)).then_some((geofence, inside))
+        })
+        .collect()
+}
+
+/// Checks a position fix against the configured geofences.
+///
+/// Publishes `GEOFENCE/{name}/STATE` as `inside` or `outside` when the state of a geofence
+/// changes.
+///
+/// # Arguments
+///
+/// * `lat`, `lon` - The current position in decimal degrees.
+/// * `mqtt` - An MQTT client to publish the transitions.
+/// * `config` - Configuration settings for the application.
+pub fn check_position(lat: f64, lon: f64, mqtt: &dyn Publisher, config: &AppConfig) {
+    if config.geofences.is_empty() {
+        return;
+    }
+
+    let mut devices = GEOFENCE_STATES.lock().unwrap();
+    let states = devices.entry(config.device_id.clone()).or_default();
+    for (geofence, inside) in update_states(states, &config.geofences, lat, lon) {
+        let state = if inside { "inside" } else { "outside" };
+        info!("Geofence {}: {}", geofence.name, state);
+
+        if let Err(e) = publish_message(
+            mqtt,
+            &config.topic(&format!("GEOFENCE/{}/STATE", geofence.name)),
+            state,
+            config.mqtt_qos,
+            config.mqtt_retain,
+        ) {
+            error!("Error pushing geofence state to MQTT: {:?}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn geofence(name: &str, lat: f64, lon: f64, radius_m: f64) -> Geofence {
+        Geofence {
+            name: name.to_string(),
+            lat,
+            lon,
+            radius_m,
+        }
+    }
+
+    #[test]
+    fn test_update_states_reports_transitions_only() {
+        let geofences = vec![
+            geofence("home", 56.9496, 24.1052, 100.0),
+            geofence("office", 56.9600, 24.1200, 50.0),
+        ];
+        let mut states = HashMap::new();
+
+        // First fix reports the initial state of every geofence
+        let changes = update_states(&mut states, &geofences, 56.9496, 24.1052);
+        let changes: Vec<(&str, bool)> =
+            changes.iter().map(|(g, i)| (g.name.as_str(), *i)).collect();
+        assert_eq!(changes, vec![("home", true), ("office", false)]);
+
+        // About 50m north, still inside home
+        assert!(update_states(&mut states, &geofences, 56.9500, 24.1052).is_empty());
+
+        // About 220m north, leaving home
+        let changes = update_states(&mut states, &geofences, 56.9516, 24.1052);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].0.name, "home");
+        assert!(!changes[0].1);
+    }
+}
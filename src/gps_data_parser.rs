@@ -1,8 +1,32 @@
-use crate::config::AppConfig;
-use crate::mqtt_handler::publish_message;
-use paho_mqtt as mqtt;
+use crate::config::{AppConfig, CoordinateFormat, FixTimeoutAction, PositionFilter, SpeedSource};
+use crate::csv_writer;
+use crate::frame_reader::Frame;
+use crate::geodesy::{haversine_distance_m, initial_bearing_deg, offset_position};
+use crate::geofence;
+use crate::gpx_writer;
+use crate::influx_writer;
+use crate::mqtt_handler::{
+    clear_retained, publish_if_changed, publish_message, publish_rate_limited, Publisher,
+};
+use crate::serial_port_handler::broadcast_quit;
+use crate::speed_conversions::{knots_to_mph, knots_to_ms};
+#[cfg(feature = "sqlite")]
+use crate::sqlite_writer;
+use crate::stdout_writer;
+use crate::ubx::{parse_mon_hw, MonHw, UbxMessage, UBX_MON_HW};
+use log::{debug, error, warn};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, VecDeque};
 use std::error::Error;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+/// Interval at which the sentence statistics are published to `STATS/`.
+const STATS_PUBLISH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Interval at which `STATUS/FIX_AGE_MS` is published between fixes.
+const FIX_AGE_PUBLISH_INTERVAL: Duration = Duration::from_secs(1);
 
 #[derive(Debug)]
 pub enum NmeaSentence {
@@ -13,6 +37,11 @@ pub enum NmeaSentence {
     GSA, // Overall satellite data
     GLL, // Geographic position
     TXT, // Text transmission
+    ZDA, // Time and date
+    GST, // Position error statistics
+    GNS, // Multi-constellation fix data
+    DTM, // Datum reference
+    GBS, // Satellite fault detection
     Unknown,
 }
 
@@ -26,17 +55,25 @@ impl NmeaSentence {
             s if s.contains("GSA") => NmeaSentence::GSA,
             s if s.contains("GLL") => NmeaSentence::GLL,
             s if s.contains("TXT") => NmeaSentence::TXT,
+            s if s.contains("ZDA") => NmeaSentence::ZDA,
+            s if s.contains("GST") => NmeaSentence::GST,
+            s if s.contains("GNS") => NmeaSentence::GNS,
+            s if s.contains("DTM") => NmeaSentence::DTM,
+            s if s.contains("GBS") => NmeaSentence::GBS,
             _ => NmeaSentence::Unknown,
         }
     }
 }
 
-#[derive(Debug)]
+/// Satellite constellation, labelled by `as_str` the same way in the GSV, GSA and GNS topics.
+#[derive(Debug, PartialEq)]
 enum SatelliteType {
     GPS,
     GLONASS,
     Galileo,
     BeiDou,
+    Qzss,
+    NavIC,
     Unknown,
 }
 
@@ -47,673 +84,4394 @@ impl SatelliteType {
             SatelliteType::GLONASS => "GLONASS",
             SatelliteType::Galileo => "Galileo",
             SatelliteType::BeiDou => "BeiDou",
+            SatelliteType::Qzss => "QZSS",
+            SatelliteType::NavIC => "NavIC",
             SatelliteType::Unknown => "Unknown",
         }
     }
 }
 
-lazy_static::lazy_static! {
-    static ref LAST_PUBLISHED_TIME: Mutex<Option<String>> = Mutex::new(None);
-    static ref LAST_PUBLISHED_DATE: Mutex<Option<String>> = Mutex::new(None);
+/// A complete position fix combined from the RMC and GGA sentences of the same epoch.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PositionFix {
+    /// UTC timestamp in `YYYY-MM-DDTHH:MM:SSZ` format, with `.sss` milliseconds before the `Z`
+    /// when the receiver reports them
+    pub timestamp: String,
+    /// Latitude in decimal degrees
+    pub lat: f64,
+    /// Longitude in decimal degrees
+    pub lon: f64,
+    /// Altitude above mean sea level in meters
+    pub alt: f64,
+    /// Speed over ground in knots
+    pub speed: f64,
+    /// Course over ground in degrees
+    pub course: f64,
+    /// GGA fix quality
+    pub fix_quality: usize,
+    /// Number of satellites used in the fix
+    pub sats_used: usize,
 }
 
-/// Process and print the received GPS data from NMEA-0183 messages.
-///
-/// This function takes a slice of bytes representing received data, converts it to a string,
-/// extracts relevant sentences starting with '$' and containing '*', and dispatches them
-/// to specialized parsing functions based on sentence type.
-///
-/// # Arguments
-///
-/// * `data` - A slice of bytes representing received data.
-pub fn process_gps_data(
-    data: &[u8],
-    config: &AppConfig,
-    mqtt: mqtt::Client,
-) -> Result<(), Box<dyn Error>> {
-    let data_str = String::from_utf8_lossy(data);
-
-    // Early return if invalid format
-    if !data_str.starts_with('$') || !data_str.contains('*') {
-        return Ok(());
+impl PositionFix {
+    /// Returns whether the fix is a satellite fix, like `is_valid_fix` does for GGA: fixes
+    /// without quality (0) and dead-reckoning estimates (6) aren't.
+    pub fn is_valid(&self) -> bool {
+        !matches!(self.fix_quality, 0 | 6)
     }
+}
 
-    // Extract sentence using more efficient string operations
-    let sentence = match data_str.split('*').next() {
-        Some(s) => &s[1..], // Skip the '$' character
-        None => return Ok(()),
-    };
-
-    // Parse sentence type and dispatch to appropriate handler
-    match NmeaSentence::from_str(sentence) {
-        NmeaSentence::GSV => parse_and_display_gsv(sentence, mqtt.clone(), config),
-        NmeaSentence::GGA => parse_and_display_gga(sentence, mqtt.clone(), config),
-        NmeaSentence::RMC => parse_and_display_rmc(sentence, mqtt.clone(), config),
-        NmeaSentence::VTG => parse_and_display_vtg(sentence, mqtt.clone(), config),
-        NmeaSentence::GSA => parse_and_display_gsa(sentence, mqtt.clone(), config),
-        NmeaSentence::GLL => parse_and_display_gll(sentence, mqtt.clone(), config),
-        NmeaSentence::TXT => parse_and_display_gntxt(sentence, mqtt.clone(), config),
-        NmeaSentence::Unknown => {
-            println!("Unknown Sentence Type: {}", sentence);
-        }
+/// The valid fix shared by the tests of the fix outputs, at the position of the NMEA examples.
+#[cfg(test)]
+pub(crate) fn test_fix() -> PositionFix {
+    PositionFix {
+        timestamp: "2024-03-23T12:35:19Z".to_string(),
+        lat: 48.1173,
+        lon: 11.5166667,
+        alt: 545.4,
+        speed: 22.4,
+        course: 84.4,
+        fix_quality: 1,
+        sats_used: 8,
     }
-
-    Ok(())
 }
 
-/// Parses and displays GSV (Satellites in View) sentence data and publishes it to MQTT.
-///
-/// # Arguments
-///
-/// * `data` - A string slice that holds the GSV sentence data.
-/// * `mqtt` - An MQTT client to publish the parsed data.
-/// * `config` - Configuration settings for the application.
-///
-/// The function splits the GSV sentence into its components and prints the total number of sentences,
-/// the sentence number, and the total number of satellites. It also prints the details of each satellite
-/// including PRN, elevation, azimuth, and SNR, and publishes this information to MQTT.
-fn parse_and_display_gsv(data: &str, mqtt: mqtt::Client, config: &AppConfig) {
-    // Extract message type prefix (e.g., "GP" from "$GPGSV")
-    let msg_type = data.get(0..2).unwrap_or("--");
-    let sat_type = match msg_type {
-        "GP" => SatelliteType::GPS,
-        "GL" => SatelliteType::GLONASS,
-        "GA" => SatelliteType::Galileo,
-        "BD" => SatelliteType::BeiDou,
-        _ => {
-            println!("Unknown satellite type prefix: {}", msg_type);
-            SatelliteType::Unknown
-        }
-    };
+/// Latest values from RMC and GGA, buffered until both sentences for the same timestamp are parsed.
+#[derive(Debug, Default)]
+struct FixBuffer {
+    rmc_time: Option<String>,
+    gga_time: Option<String>,
+    flushed_time: Option<String>,
+    timestamp: String,
+    lat: f64,
+    lon: f64,
+    alt: f64,
+    speed: f64,
+    course: f64,
+    fix_quality: usize,
+    sats_used: usize,
+}
 
-    let parts: Vec<&str> = data.split(',').collect();
-    if parts.len() >= 8 {
-        let num_satellites = parts[3].parse::<usize>().unwrap_or(0);
-        println!("Total Satellites: {}", num_satellites);
+impl FixBuffer {
+    /// Stores the GGA-derived values and returns the combined fix if RMC for the same time was seen.
+    fn update_from_gga(
+        &mut self,
+        utc_time: &str,
+        alt: f64,
+        fix_quality: usize,
+        sats_used: usize,
+    ) -> Option<PositionFix> {
+        self.gga_time = Some(utc_time.to_string());
+        self.alt = alt;
+        self.fix_quality = fix_quality;
+        self.sats_used = sats_used;
+        self.take_complete_fix()
+    }
 
-        // Publish total satellites count
-        if let Err(e) = publish_message(
-            &mqtt,
-            &format!("{}SAT/GLOBAL/NUM", config.mqtt_base_topic),
-            &format!("{}", num_satellites).as_str(),
-            0,
-        ) {
-            println!("Error pushing total number of satellites to MQTT: {:?}", e);
+    /// Stores the RMC-derived values and returns the combined fix if GGA for the same time was seen.
+    fn update_from_rmc(
+        &mut self,
+        utc_time: &str,
+        timestamp: String,
+        lat: f64,
+        lon: f64,
+        speed: f64,
+        course: Option<f64>,
+    ) -> Option<PositionFix> {
+        self.rmc_time = Some(utc_time.to_string());
+        self.timestamp = timestamp;
+        self.lat = lat;
+        self.lon = lon;
+        self.speed = speed;
+        // An empty course field keeps the last known course instead of pointing north
+        if let Some(course) = course {
+            self.course = course;
         }
+        self.take_complete_fix()
+    }
 
-        // Process each satellite
-        for i in 0..((parts.len() - 4) / 4) {
-            let sat_index = 4 + i * 4;
-            let sat_prn = parts[sat_index].parse::<usize>().unwrap_or(0);
-            let sat_elevation = parts[sat_index + 1].parse::<usize>().unwrap_or(0);
-            let sat_azimuth = parts[sat_index + 2].parse::<usize>().unwrap_or(0);
-            let sat_snr = parts[sat_index + 3].parse::<usize>().unwrap_or(0);
-            let in_view = sat_snr > 0;
-
-            println!(
-                "Satellite PRN: {}, Type: {}, Elevation: {}, Azimuth: {}, SNR: {}, In View: {}",
-                sat_prn,
-                sat_type.as_str(),
-                sat_elevation,
-                sat_azimuth,
-                sat_snr,
-                in_view
-            );
+    /// Returns the combined fix once per timestamp, when RMC and GGA carry the same UTC time.
+    fn take_complete_fix(&mut self) -> Option<PositionFix> {
+        if self.rmc_time.is_none()
+            || self.rmc_time != self.gga_time
+            || self.flushed_time == self.rmc_time
+        {
+            return None;
+        }
 
-            // Keep original MQTT topic structure
-            let sat_topic = format!("{}SAT/VEHICLES/{}", config.mqtt_base_topic, sat_prn);
-            let sat_info = format!(
-                "PRN: {}, Type: {}, Elevation: {}, Azimuth: {}, SNR: {}, In View: {}",
-                sat_prn,
-                sat_type.as_str(),
-                sat_elevation,
-                sat_azimuth,
-                sat_snr,
-                in_view
-            );
+        self.flushed_time = self.rmc_time.clone();
+        Some(self.latest())
+    }
 
-            if let Err(e) = publish_message(&mqtt, &sat_topic, &sat_info, 0) {
-                println!("Error pushing satellite info to MQTT: {:?}", e);
-            }
+    /// Returns the latest RMC values merged with the most recent GGA values, paired or not.
+    fn latest(&self) -> PositionFix {
+        PositionFix {
+            timestamp: self.timestamp.clone(),
+            lat: self.lat,
+            lon: self.lon,
+            alt: self.alt,
+            speed: self.speed,
+            course: self.course,
+            fix_quality: self.fix_quality,
+            sats_used: self.sats_used,
         }
-    } else {
-        println!("Invalid GSV Sentence: {}", data);
     }
 }
 
-/// Parses and displays GGA (Global Positioning System Fix Data) sentence data.
-///
-/// # Arguments
-///
-/// * `data` - A string slice that holds the GGA sentence data.
-/// * `mqtt` - An MQTT client to publish the parsed data.
-/// * `config` - Configuration settings for the application.
-///
-/// The function splits the GGA sentence into its components and publishes the altitude and fix quality to MQTT.
-fn parse_and_display_gga(data: &str, mqtt: mqtt::Client, config: &AppConfig) {
-    let parts: Vec<&str> = data.split(',').collect();
+/// Parser state kept separately for each `device_id`, so the sentences of several sources
+/// running at the same time don't mix.
+type PerDevice<T> = Mutex<HashMap<String, T>>;
 
-    if parts.len() >= 10 {
-        let latitude = parts[2].parse::<f64>().unwrap_or(0.0);
-        let longitude = parts[4].parse::<f64>().unwrap_or(0.0);
-        let altitude = parts[9].parse::<f64>().unwrap_or(0.0);
-        let fix_quality = parts[6].parse::<usize>().unwrap_or(0);
+lazy_static::lazy_static! {
+    static ref GSV_BURSTS: PerDevice<HashMap<String, GsvBurst>> = Mutex::new(HashMap::new());
+    /// PRNs of the last complete GSV burst of each talker, to clear satellites leaving the view.
+    static ref SATELLITES_IN_VIEW: PerDevice<HashMap<String, BTreeSet<usize>>> =
+        Mutex::new(HashMap::new());
+    /// Satellites in view reported by the last complete GSV burst of each talker.
+    static ref SATELLITE_COUNTS: PerDevice<HashMap<String, usize>> = Mutex::new(HashMap::new());
+    static ref FIX_BUFFER: PerDevice<FixBuffer> = Mutex::new(HashMap::new());
+    static ref STATS: PerDevice<SentenceStats> = Mutex::new(HashMap::new());
+    static ref LAST_STATS_PUBLISH: PerDevice<Option<Instant>> = Mutex::new(HashMap::new());
+    static ref TRIP: PerDevice<Trip> = Mutex::new(HashMap::new());
+    static ref VERTICAL_SPEED: PerDevice<VerticalSpeed> = Mutex::new(HashMap::new());
+    static ref MOTION: PerDevice<MotionFilter> = Mutex::new(HashMap::new());
+    static ref SMOOTHER: PerDevice<Smoother> = Mutex::new(HashMap::new());
+    static ref KALMAN: PerDevice<PositionKalman> = Mutex::new(HashMap::new());
+    static ref ACTIVE_DATUM: PerDevice<Option<String>> = Mutex::new(HashMap::new());
+    static ref FIX_WATCHDOG: PerDevice<FixWatchdog> = Mutex::new(HashMap::new());
+    /// Time of the last valid RMC fix, see `fix_age_ms`.
+    static ref LAST_RMC_FIX: PerDevice<Option<Instant>> = Mutex::new(HashMap::new());
+    static ref LAST_FIX_AGE_PUBLISH: PerDevice<Option<Instant>> = Mutex::new(HashMap::new());
+    /// Whether the last RMC sentence had a speed field, see `SpeedSource::Auto`.
+    static ref RMC_HAS_SPEED: PerDevice<bool> = Mutex::new(HashMap::new());
+}
 
-        println!("Latitude: {}", latitude);
-        println!("Longitude: {}", longitude);
-        println!("Altitude: {}", altitude);
+/// Runs `f` with the state of the configured `device_id`, created on first use.
+fn with_device<T: Default, R>(
+    states: &PerDevice<T>,
+    config: &AppConfig,
+    f: impl FnOnce(&mut T) -> R,
+) -> R {
+    let mut states = states.lock().unwrap();
+    f(states.entry(config.device_id.clone()).or_default())
+}
 
-        // Push altitude to MQTT
-        if let Err(e) = publish_message(
-            &mqtt,
-            &format!("{}ALT", config.mqtt_base_topic),
-            &format!("{}", altitude).as_str(),
-            0,
-        ) {
-            println!("Error pushing altitude to MQTT: {:?}", e);
-        }
+/// State of the position fix tracked by `FixWatchdog`, published to `STATUS/FIX`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FixState {
+    Ok,
+    /// Dead reckoning without a satellite fix, see `is_estimated_fix`
+    Estimated,
+    Lost,
+}
 
-        // Push fix quality to MQTT
-        if let Err(e) = publish_message(
-            &mqtt,
-            &format!("{}QTY", config.mqtt_base_topic),
-            &format!("{}", fix_quality).as_str(),
-            0,
-        ) {
-            println!("Error pushing fix quality to MQTT: {:?}", e);
+impl FixState {
+    fn as_str(&self) -> &'static str {
+        match self {
+            FixState::Ok => "ok",
+            FixState::Estimated => "estimated",
+            FixState::Lost => "lost",
         }
-    } else {
-        println!("Invalid GGA Sentence: {}", data);
     }
 }
 
-/// Parses and displays RMC (Recommended Minimum Specific GNSS Data) sentence data and publishes it to MQTT.
-///
-/// # Arguments
-///
-/// * `data` - A string slice that holds the RMC sentence data.
-/// * `mqtt` - An MQTT client to publish the parsed data.
-/// * `config` - Configuration settings for the application.
-///
-/// The function splits the RMC sentence into its components, prints the latitude, longitude, UTC time, and data status,
-/// and publishes the RMC time, latitude, longitude, and speed to MQTT.
-fn parse_and_display_rmc(data: &str, mqtt: mqtt::Client, config: &AppConfig) {
-    let parts: Vec<&str> = data.split(',').collect();
-    if parts.len() >= 10 {
-        let utc_time = parts[1];
-        let latitude = parse_latitude(parts[3], parts[4]);
-        let longitude = parse_longitude(parts[5], parts[6]);
-        let speed = parts[7].parse::<f64>().unwrap_or(0.0);
-        let date = parts[9];
-
-        // Parse UTC time and date
-        let (hour, minute, second) = parse_utc_time(utc_time);
-        let (day, month, year) = parse_date(date);
-
-        // Push time to MQTT
-        let current_time = format!("{:02}:{:02}:{:02}", hour, minute, second);
-
-        let mut last_published_time = LAST_PUBLISHED_TIME.lock().unwrap();
-        if last_published_time.as_deref() != Some(&current_time) {
-            if let Err(e) = publish_message(
-                &mqtt,
-                &format!("{}TME", config.mqtt_base_topic),
-                &current_time,
-                0,
-            ) {
-                println!("Error pushing time to MQTT: {:?}", e);
-            }
-            *last_published_time = Some(current_time);
-        }
+/// Tracks the time of the last valid position fix, see `fix_timeout_secs`.
+#[derive(Debug, Clone, PartialEq)]
+struct FixWatchdog {
+    /// Time of the last valid fix, or of startup before the first one
+    last_fix: Instant,
+    state: Option<FixState>,
+}
 
-        // Push date to MQTT
-        let current_date = format!("{:02}.{:02}.20{:02}", day, month, year);
+impl Default for FixWatchdog {
+    /// Starts the timeout at the time the watchdog is created.
+    fn default() -> Self {
+        FixWatchdog::new(Instant::now())
+    }
+}
 
-        let mut last_published_date = LAST_PUBLISHED_DATE.lock().unwrap();
-        if last_published_date.as_deref() != Some(&current_date) {
-            if let Err(e) = publish_message(&mqtt, "/GOLF86/GPS/DTE", &current_date, 0) {
-                println!("Error pushing date to MQTT: {:?}", e);
-            }
-            *last_published_date = Some(current_date);
+impl FixWatchdog {
+    fn new(now: Instant) -> Self {
+        FixWatchdog {
+            last_fix: now,
+            state: None,
         }
+    }
 
-        // Push latitude to MQTT
-        if let Err(e) = publish_message(
-            &mqtt,
-            &format!("{}LAT", config.mqtt_base_topic),
-            &format!("{}", latitude).as_str(),
-            0,
-        ) {
-            println!("Error pushing latitude to MQTT: {:?}", e);
+    /// Records a valid fix and returns `FixState::Ok` if the state changed.
+    fn record_fix(&mut self, now: Instant) -> Option<FixState> {
+        self.last_fix = now;
+        self.transition(FixState::Ok)
+    }
+
+    /// Records an estimated position and returns `FixState::Estimated` if the state changed.
+    ///
+    /// An estimate doesn't restart the timeout, and once the fix is lost it stays lost until a
+    /// real fix arrives.
+    fn record_estimate(&mut self) -> Option<FixState> {
+        if self.state == Some(FixState::Lost) {
+            return None;
         }
+        self.transition(FixState::Estimated)
+    }
 
-        // Push longitude to MQTT
-        if let Err(e) = publish_message(
-            &mqtt,
-            &format!("{}LNG", config.mqtt_base_topic),
-            &format!("{}", longitude).as_str(),
-            0,
-        ) {
-            println!("Error pushing longitude to MQTT: {:?}", e);
+    /// Returns `FixState::Lost` once when no fix has been recorded for `timeout`.
+    fn check(&mut self, now: Instant, timeout: Duration) -> Option<FixState> {
+        if now.duration_since(self.last_fix) < timeout {
+            return None;
         }
+        self.transition(FixState::Lost)
+    }
 
-        // Push speed to MQTT
-        if let Err(e) = publish_message(
-            &mqtt,
-            &format!("{}SPD", config.mqtt_base_topic),
-            &format!("{}", speed).as_str(),
-            0,
-        ) {
-            println!("Error pushing speed to MQTT: {:?}", e);
+    fn transition(&mut self, state: FixState) -> Option<FixState> {
+        if self.state == Some(state) {
+            return None;
         }
-    } else {
-        println!("Invalid RMC Sentence: {}", data);
+        self.state = Some(state);
+        Some(state)
     }
 }
 
-/// Parses and displays VTG (Course Over Ground and Ground Speed) sentence data.
-///
-/// # Arguments
-///
-/// * `data` - A string slice that holds the VTG sentence data.
-/// * `mqtt` - An MQTT client to publish the parsed data.
-/// * `config` - Configuration settings for the application.
-///
-/// The function splits the VTG sentence into its components and publishes the course, speed in knots, and speed in kph to MQTT.
-fn parse_and_display_vtg(data: &str, mqtt: mqtt::Client, config: &AppConfig) {
-    let parts: Vec<&str> = data.split(',').collect();
-    if parts.len() >= 9 {
-        let course = parts[1].parse::<f64>().unwrap_or(0.0);
-        let speed_knots = parts[5].parse::<f64>().unwrap_or(0.0);
-        let speed_kph = parts[7].parse::<f64>().unwrap_or(0.0);
-
-        let messages = [
-            (course, "CRS"),
-            (speed_knots, "SPD_KTS"),
-            (speed_kph, "SPD_KPH"),
-        ];
+/// Moving averages of the recent speed and course values, see `smoothing_window`.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Smoother {
+    speeds: VecDeque<f64>,
+    courses: VecDeque<f64>,
+}
 
-        for (value, suffix) in &messages {
-            if let Err(e) = publish_message(
-                &mqtt,
-                &format!("{}{}", config.mqtt_base_topic, suffix),
-                &format!("{}", value).as_str(),
-                0,
-            ) {
-                println!("Error pushing {} to MQTT: {:?}", suffix, e);
-            }
-        }
-    } else {
-        println!("Invalid VTG Sentence: {}", data);
+impl Smoother {
+    /// Adds a speed sample and returns the mean of the last `window` samples.
+    fn smooth_speed(&mut self, speed: f64, window: usize) -> f64 {
+        push_sample(&mut self.speeds, speed, window);
+        self.speeds.iter().sum::<f64>() / self.speeds.len() as f64
+    }
+
+    /// Adds a course sample and returns the circular mean of the last `window` samples.
+    fn smooth_course(&mut self, course: f64, window: usize) -> f64 {
+        push_sample(&mut self.courses, course, window);
+        circular_mean_deg(self.courses.iter().copied())
     }
 }
 
-/// Parses and displays GSA (GNSS DOP and Active Satellites) sentence data.
-///
-/// # Arguments
-///
-/// * `data` - A string slice that holds the GSA sentence data.
-/// * `mqtt` - An MQTT client to publish the parsed data.
-/// * `config` - Configuration settings for the application.
+/// Constant-velocity Kalman filter over the RMC positions, see `position_filter`.
 ///
-/// The function splits the GSA sentence into its components and prints the message ID, fix type, and PRN.
-fn parse_and_display_gsa(data: &str, mqtt: mqtt::Client, config: &AppConfig) {
-    let parts: Vec<&str> = data.split(',').collect();
-    if parts.len() >= 17 {
-        let message_id = parts[0];
-        let fix_type = match parts[2] {
-            "1" => "Not Available",
-            "2" => "2D",
-            "3" => "3D",
-            _ => "Unknown",
+/// The RMC speed and course are the process input moving the estimate between fixes, so only
+/// the unexplained movement adds uncertainty. Latitude and longitude share one variance in
+/// meters², since both are measured and predicted with the same noise.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct PositionKalman {
+    /// Seconds since midnight UTC, latitude and longitude of the estimate
+    estimate: Option<(f64, f64, f64)>,
+    /// Variance of the estimate in meters²
+    variance: f64,
+}
+
+impl PositionKalman {
+    /// Adds a measured latitude and longitude and returns the filtered ones.
+    ///
+    /// The filter is seeded from the first position. `speed` is in knots and `course` in degrees;
+    /// without a course the estimate isn't moved between fixes.
+    fn update(
+        &mut self,
+        time: f64,
+        (lat, lon): (f64, f64),
+        speed: f64,
+        course: Option<f64>,
+        process_noise: f64,
+        measurement_noise: f64,
+    ) -> (f64, f64) {
+        let measurement_variance = measurement_noise.powi(2);
+        let Some((last_time, last_lat, last_lon)) = self.estimate else {
+            self.estimate = Some((time, lat, lon));
+            self.variance = measurement_variance;
+            return (lat, lon);
         };
-        let prn = parts[3].parse::<usize>().unwrap_or(0);
 
-        println!(
-            "GSA Sentence - Message ID: {}, Fix Type: {}, PRN: {}",
-            message_id, fix_type, prn
-        );
+        // Predict, with fixes on both sides of midnight
+        let elapsed = (time - last_time).rem_euclid(86400.0);
+        let distance = knots_to_ms(speed) * elapsed;
+        let (predicted_lat, predicted_lon) = match course {
+            Some(course) => offset_position(
+                last_lat,
+                last_lon,
+                distance * course.to_radians().cos(),
+                distance * course.to_radians().sin(),
+            ),
+            None => (last_lat, last_lon),
+        };
+        let predicted_variance = self.variance + (process_noise * elapsed).powi(2);
 
-        // Publish fix type to MQTT
-        let sat_topic = format!("{}SAT/VEHICLES/{}/FIX_TYPE", config.mqtt_base_topic, prn);
-        if let Err(e) = publish_message(&mqtt, &sat_topic, fix_type, 0) {
-            println!("Error pushing fix type to MQTT: {:?}", e);
-        }
-    } else {
-        println!("Invalid GSA Sentence: {}", data);
+        // Correct, taking the shorter way around the antimeridian
+        let gain = predicted_variance / (predicted_variance + measurement_variance);
+        let filtered_lat = predicted_lat + gain * (lat - predicted_lat);
+        let lon_innovation = (lon - predicted_lon + 180.0).rem_euclid(360.0) - 180.0;
+        let filtered_lon =
+            (predicted_lon + gain * lon_innovation + 180.0).rem_euclid(360.0) - 180.0;
+
+        self.estimate = Some((time, filtered_lat, filtered_lon));
+        self.variance = (1.0 - gain) * predicted_variance;
+        (filtered_lat, filtered_lon)
     }
 }
 
-/// Parses and displays GNTXT (Text Transmission) sentence data.
-///
-/// # Arguments
-///
-/// * `data` - A string slice that holds the GNTXT sentence data.
-/// * `mqtt` - An MQTT client used to publish messages.
-/// * `config` - Configuration settings for the application.
+/// Appends `value` to `samples`, dropping the oldest samples beyond `window`.
+fn push_sample(samples: &mut VecDeque<f64>, value: f64, window: usize) {
+    samples.push_back(value);
+    while samples.len() > window.max(1) {
+        samples.pop_front();
+    }
+}
+
+/// Mean of angles in degrees, returned in the range 0-360.
 ///
-/// The function splits the GNTXT sentence into its components and prints the message ID, message number, total messages, and text.
-/// If the message contains "ANTSTATUS=", it publishes the value after "=" to the MQTT topic.
-/// If the message contains "PF=", it publishes the value after "=" to the MQTT topic.
-/// If the message contains "GNSS OTP=", it prints the value after "=".
+/// The angles are averaged as unit vectors, so 359° and 1° average to 0° rather than 180°.
+fn circular_mean_deg(angles: impl Iterator<Item = f64>) -> f64 {
+    let (sin, cos) = angles.fold((0.0, 0.0), |(sin, cos), angle: f64| {
+        let angle = angle.to_radians();
+        (sin + angle.sin(), cos + angle.cos())
+    });
+    sin.atan2(cos).to_degrees().rem_euclid(360.0)
+}
 
-fn parse_and_display_gntxt(data: &str, mqtt: mqtt::Client, config: &AppConfig) {
-    let mut parts = data.splitn(4, ',');
-    if let (Some(_msg_id), Some(_msg_num), Some(_msg_total), Some(text)) =
-        (parts.next(), parts.next(), parts.next(), parts.next())
-    {
-        let message = text.splitn(2, ',').nth(1).unwrap_or(text);
+/// Cleans up RMC speed and position noise while the vehicle is parked.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct MotionFilter {
+    /// Whether the last fix was stationary, `None` before the first fix
+    stationary: Option<bool>,
+    /// Last position published to `LAT`/`LNG`
+    last_position: Option<(f64, f64)>,
+}
 
-        if message.contains("txbuf alloc") {
-            return;
+/// Result of `MotionFilter::update` for one RMC fix.
+#[derive(Debug, Clone, PartialEq)]
+struct MotionUpdate {
+    /// Speed in knots to publish, 0 while stationary
+    speed: f64,
+    /// Whether `LAT`/`LNG` should be published for this fix
+    publish_position: bool,
+    /// The new stationary state when it changed with this fix
+    changed_to: Option<bool>,
+}
+
+impl MotionFilter {
+    /// Classifies a fix as stationary when `speed` is below `speed_threshold` knots.
+    ///
+    /// While stationary, the speed is clamped to 0 and positions less than `min_distance_m` from
+    /// the last published one are suppressed. A threshold of 0 disables the filter.
+    fn update(
+        &mut self,
+        speed: f64,
+        lat: f64,
+        lon: f64,
+        speed_threshold: f64,
+        min_distance_m: f64,
+    ) -> MotionUpdate {
+        if speed_threshold <= 0.0 {
+            return MotionUpdate {
+                speed,
+                publish_position: true,
+                changed_to: None,
+            };
         }
 
-        println!("GNTXT Text: {}", message);
+        let stationary = speed < speed_threshold;
+        let changed_to = (self.stationary != Some(stationary)).then_some(stationary);
+        self.stationary = Some(stationary);
 
-        let topics = [
-            ("ANTSTATUS=", "SAT/GLOBAL/ANTSTATUS"),
-            ("PF=", "SAT/GLOBAL/PF"),
-            ("GNSS OTP=", "SAT/GLOBAL/GNSS_OTP"),
-        ];
+        let publish_position = !stationary
+            || self.last_position.is_none_or(|(last_lat, last_lon)| {
+                haversine_distance_m(last_lat, last_lon, lat, lon) >= min_distance_m
+            });
+        if publish_position {
+            self.last_position = Some((lat, lon));
+        }
 
-        for (prefix, topic_suffix) in &topics {
-            if let Some(value) = message.strip_prefix(prefix) {
-                if let Err(e) = publish_message(
-                    &mqtt,
-                    &format!("{}{}", config.mqtt_base_topic, topic_suffix),
-                    value,
-                    0,
-                ) {
-                    println!(
-                        "Error pushing {} to MQTT: {:?}",
-                        prefix.trim_end_matches('='),
-                        e
-                    );
-                }
-                break;
-            }
+        MotionUpdate {
+            speed: if stationary { 0.0 } else { speed },
+            publish_position,
+            changed_to,
         }
-    } else {
-        println!("Invalid GNTXT Sentence: {}", data);
     }
 }
 
-/// Parses and displays GLL (Geographic Position - Latitude/Longitude) sentence data.
-///
-/// # Arguments
-///
-/// * `data` - A string slice that holds the GLL sentence data.
-///
-/// The function splits the GLL sentence into its components and prints the latitude, longitude, UTC time, and data status.
-fn parse_and_display_gll(data: &str, mqtt: mqtt::Client, config: &AppConfig) {
-    let parts: Vec<&str> = data.split(',').collect();
-    if parts.len() < 7 {
-        println!("Invalid GLL Sentence: {}", data);
-        return;
+/// Distance accumulated from the position deltas between RMC fixes.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Trip {
+    /// Last position counted towards the distance
+    last_point: Option<(f64, f64)>,
+    /// Total distance in meters since startup or the last reset
+    distance_m: f64,
+}
+
+impl Trip {
+    /// Adds the movement from the last counted position to `(lat, lon)`.
+    ///
+    /// Movements shorter than `min_distance_m` are ignored and don't move the reference point,
+    /// so GPS jitter while stationary doesn't accumulate while slow movement still adds up.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<f64>` - The bearing of the counted movement in degrees, `None` when ignored
+    fn update(&mut self, lat: f64, lon: f64, min_distance_m: f64) -> Option<f64> {
+        let Some((last_lat, last_lon)) = self.last_point else {
+            self.last_point = Some((lat, lon));
+            return None;
+        };
+
+        let distance = haversine_distance_m(last_lat, last_lon, lat, lon);
+        if distance < min_distance_m {
+            return None;
+        }
+
+        self.distance_m += distance;
+        self.last_point = Some((lat, lon));
+        Some(initial_bearing_deg(last_lat, last_lon, lat, lon))
     }
+}
 
-    let latitude = parse_latitude(parts[1], parts[2]);
-    let longitude = parse_longitude(parts[3], parts[4]);
-    let utc_time = parts[5];
+/// Vertical speed derived from the altitude change between consecutive GGA fixes.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct VerticalSpeed {
+    /// Seconds since midnight UTC and altitude in meters of the last fix
+    last_fix: Option<(f64, f64)>,
+}
 
-    // Parse UTC time
-    let (hour, minute, second) = parse_utc_time(utc_time);
-    let current_time = format!("{:02}:{:02}:{:02}", hour, minute, second);
+impl VerticalSpeed {
+    /// Adds a GGA fix and returns the vertical speed in m/s since the previous one, positive
+    /// when climbing.
+    ///
+    /// Only consecutive fixes with a quality of at least 1 are compared, since the altitude of a
+    /// fix without quality is meaningless and would show up as a large spurious jump. A fix with
+    /// the same time as the previous one is ignored.
+    fn update(&mut self, utc_time: &str, altitude: f64, fix_quality: usize) -> Option<f64> {
+        if fix_quality == 0 || utc_time.len() < 6 {
+            self.last_fix = None;
+            return None;
+        }
 
-    println!(
-        "GLL Latitude: {}, GLL Longitude: {}, GLL UTC Time: {}",
-        latitude, longitude, current_time
-    );
+        let (hour, minute, second, millisecond) = parse_utc_time(utc_time);
+        let time = f64::from(hour * 3600 + minute * 60 + second) + f64::from(millisecond) / 1000.0;
+        let (last_time, last_altitude) = self.last_fix.replace((time, altitude))?;
 
-    // Helper function to publish messages to MQTT
-    fn publish_gll_message(
-        mqtt: &mqtt::Client,
-        topic_suffix: &str,
-        message: &str,
-        config: &AppConfig,
-    ) {
-        if let Err(e) = publish_message(
-            mqtt,
-            &format!("{}{}", config.mqtt_base_topic, topic_suffix),
-            message,
-            0,
-        ) {
-            println!("Error pushing GLL {} to MQTT: {:?}", topic_suffix, e);
+        // Fixes on both sides of midnight
+        let elapsed = (time - last_time).rem_euclid(86400.0);
+        if elapsed == 0.0 {
+            self.last_fix = Some((last_time, last_altitude));
+            return None;
         }
+        Some((altitude - last_altitude) / elapsed)
     }
+}
 
-    // Push GLL data to MQTT
-    publish_gll_message(&mqtt, "GLL_TME", &current_time, config);
-    publish_gll_message(&mqtt, "GLL_LAT", &latitude.to_string(), config);
-    publish_gll_message(&mqtt, "GLL_LNG", &longitude.to_string(), config);
+/// Resets the accumulated trip distance of `device_id`, e.g. on a `TRIP/RESET` message.
+pub fn reset_trip(device_id: &str) {
+    TRIP.lock().unwrap().remove(device_id);
 }
 
-/// Parses latitude or longitude from NMEA format and converts it to decimal degrees.
+/// Returns the milliseconds since the last valid RMC fix of `device_id`, or `None` before the
+/// first one.
+pub fn fix_age_ms(device_id: &str) -> Option<u64> {
+    let last_fix = LAST_RMC_FIX
+        .lock()
+        .unwrap()
+        .get(device_id)
+        .copied()
+        .flatten()?;
+    Some(last_fix.elapsed().as_millis() as u64)
+}
+
+/// Counters of the sentences received, used to monitor the data quality of the receiver link.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SentenceStats {
+    /// Total number of sentences seen
+    pub total: u64,
+    /// Number of sentences seen per sentence type, e.g. `GGA`
+    pub by_type: HashMap<String, u64>,
+    /// Sentences of an unknown type or with missing fields
+    pub invalid: u64,
+    /// Sentences whose checksum didn't match
+    pub checksum_errors: u64,
+}
+
+impl SentenceStats {
+    /// Counts one sentence, whether it could be parsed and whether its checksum matched.
+    ///
+    /// The type is taken from the three characters after the talker ID.
+    fn record(&mut self, sentence: &str, parsed: bool, checksum_valid: bool) {
+        self.total += 1;
+
+        let sentence_type = sentence
+            .trim_start_matches('$')
+            .get(2..5)
+            .unwrap_or("UNKNOWN");
+        *self.by_type.entry(sentence_type.to_string()).or_insert(0) += 1;
+
+        if !parsed {
+            self.invalid += 1;
+        }
+        if !checksum_valid {
+            self.checksum_errors += 1;
+        }
+    }
+}
+
+/// Typed data extracted from a single NMEA sentence by `parse_nmea_sentence`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParsedData {
+    Gsv(GsvSentence),
+    Gga(GgaData),
+    Rmc(RmcData),
+    Vtg(VtgData),
+    Gsa(GsaData),
+    Gll(GllData),
+    Txt(TxtData),
+    Zda(ZdaData),
+    Gst(GstErrors),
+    Gns(GnsData),
+    Dtm(DtmData),
+    Gbs(GbsData),
+}
+
+/// Process and print the received GPS data from NMEA-0183 messages.
 ///
-/// # Arguments
+/// This function takes a slice of bytes representing received data, parses every sentence in it
+/// the way `parse_gps_data` does and publishes each result with `publish_parsed`. Sentences with
+/// a mismatching checksum are only counted in the statistics.
 ///
-/// * `value` - A string slice that holds the coordinate in NMEA format.
-/// * `direction` - A string slice that holds the direction ('N', 'S', 'E', or 'W').
-/// * `degree_len` - The length of the degree part in the NMEA format (2 for latitude, 3 for longitude).
+/// # Arguments
 ///
-/// The function extracts degrees and minutes from the NMEA format, converts them to decimal degrees,
-/// and adjusts the sign based on the direction.
-fn parse_coordinate(value: &str, direction: &str, degree_len: usize) -> f64 {
-    if value.is_empty() || direction.is_empty() {
-        println!("Invalid coordinate input: {}{}", value, direction);
-        return 0.0;
-    }
-
-    if value.len() <= degree_len {
-        println!("Invalid coordinate input: {}{}", value, direction);
-        return 0.0;
-    }
+/// * `data` - A slice of bytes representing received data.
+pub fn process_gps_data(
+    data: &[u8],
+    config: &AppConfig,
+    mqtt: &dyn Publisher,
+) -> Result<(), Box<dyn Error>> {
+    let data = String::from_utf8_lossy(data);
+    for line in sentence_lines(&data) {
+        let Some((parsed, checksum_valid)) =
+            with_device(&STATS, config, |stats| parse_line(line, stats))
+        else {
+            continue;
+        };
+        // Counted in `STATS/CHECKSUM_ERRORS`, but a corrupted sentence could publish garbage
+        if !checksum_valid {
+            debug!("Dropping sentence with a checksum mismatch: {}", line);
+            continue;
+        }
 
-    if !matches!(direction, "N" | "S" | "E" | "W") {
-        println!("Invalid direction: {}", direction);
-        return 0.0;
-    }
+        let talker = talker_id(line);
+        debug!(
+            "Sentence from talker {}: {}",
+            talker.unwrap_or("(proprietary)"),
+            line
+        );
+        if let Some(talker) = talker {
+            publish_talker(talker, &parsed, mqtt, config);
+        }
 
-    // Parse degrees and minutes
-    match (
-        value[..degree_len].parse::<f64>(),
-        value[degree_len..].parse::<f64>(),
-    ) {
-        (Ok(degrees), Ok(minutes)) => {
-            let result = degrees + minutes / 60.0;
-            match direction {
-                "S" | "W" => -result,
-                _ => result,
+        if config.fix_timeout_secs > 0 {
+            let changed = with_device(&FIX_WATCHDOG, config, |watchdog| {
+                if is_valid_fix(&parsed) {
+                    watchdog.record_fix(Instant::now())
+                } else if is_estimated_fix(&parsed) {
+                    watchdog.record_estimate()
+                } else {
+                    None
+                }
+            });
+            if let Some(state) = changed {
+                publish_fix_state(state, mqtt, config);
             }
         }
-        _ => {
-            println!("Failed to parse coordinate: {}{}", value, direction);
-            0.0
+        if matches!(parsed, ParsedData::Rmc(_)) && is_valid_fix(&parsed) {
+            with_device(&LAST_RMC_FIX, config, |last_fix| {
+                *last_fix = Some(Instant::now())
+            });
+            publish_fix_age(true, mqtt, config);
         }
+        publish_parsed(&parsed, mqtt, config);
     }
-}
 
-/// Parses latitude from NMEA format and converts it to decimal degrees.
-fn parse_latitude(value: &str, direction: &str) -> f64 {
-    parse_coordinate(value, direction, 2)
-}
+    check_fix_watchdog(mqtt, config);
 
-/// Parses longitude from NMEA format and converts it to decimal degrees.
-fn parse_longitude(value: &str, direction: &str) -> f64 {
-    parse_coordinate(value, direction, 3)
+    publish_fix_age(false, mqtt, config);
+
+    publish_stats_if_due(mqtt, config);
+
+    Ok(())
 }
 
-/// Parses UTC time from NMEA HHMMSS.ss format into hour, minute, second components.
+/// Processes a frame from the serial port's `FrameReader`.
+///
+/// NMEA sentences go through `process_gps_data`, after being published unparsed to
+/// `raw_nmea_topic` when it is set. The UBX messages that are understood are published directly.
 ///
 /// # Arguments
 ///
-/// * `utc_time` - A string slice in HHMMSS format (e.g., "235959" for 23:59:59)
-///                Optionally may contain decimal seconds after period
+/// * `frame` - An NMEA sentence or UBX message.
+/// * `config` - Configuration settings for the application.
+/// * `mqtt` - An MQTT client to publish the data.
+pub fn process_frame(
+    frame: &Frame,
+    config: &AppConfig,
+    mqtt: &dyn Publisher,
+) -> Result<(), Box<dyn Error>> {
+    match frame {
+        Frame::Nmea(sentence) => {
+            if let Some(topic) = &config.raw_nmea_topic {
+                publish_raw_nmea(sentence, topic, mqtt, config);
+            }
+            stdout_writer::write_sentence(sentence);
+            process_gps_data(sentence.as_bytes(), config, mqtt)
+        }
+        Frame::Ubx(message) => {
+            publish_ubx_message(message, mqtt, config);
+            Ok(())
+        }
+    }
+}
+
+/// Publishes a sentence with a valid checksum as received to `topic`, for remote troubleshooting.
+///
+/// This happens before parsing, so sentences the parser doesn't understand show up too. They are
+/// published at QoS 0 and not retained, since a stale raw sentence is of no use to subscribers.
+fn publish_raw_nmea(sentence: &str, topic: &str, mqtt: &dyn Publisher, config: &AppConfig) {
+    if !has_valid_checksum(sentence) {
+        return;
+    }
+
+    let topic = topic.replace("{device}", &config.device_id);
+    if let Err(e) = publish_message(mqtt, &topic, sentence, 0, false) {
+        error!("Error pushing raw NMEA sentence to MQTT: {:?}", e);
+    }
+}
+
+/// Returns the frame handler of the binary, which processes each frame with `process_frame` and
+/// logs the errors.
+///
+/// # Arguments
+///
+/// * `config` - Configuration settings for the application.
+/// * `mqtt` - An MQTT client to publish the data.
+pub fn publishing_handler<'a>(
+    config: &'a AppConfig,
+    mqtt: &'a dyn Publisher,
+) -> impl Fn(&Frame) + 'a {
+    move |frame| {
+        if let Err(e) = process_frame(frame, config, mqtt) {
+            error!("Error processing GPS data: {:?}", e);
+        }
+    }
+}
+
+/// Returns a frame handler like `publishing_handler` that reads `config` anew for every frame, so
+/// the settings a configuration reload writes to it take effect with the next sentence.
+///
+/// # Arguments
+///
+/// * `config` - Configuration settings, updated by `reload_handler::watch_reloads`.
+/// * `mqtt` - An MQTT client to publish the data.
+pub fn live_publishing_handler<'a>(
+    config: &'a RwLock<AppConfig>,
+    mqtt: &'a dyn Publisher,
+) -> impl Fn(&Frame) + 'a {
+    move |frame| {
+        let config = config.read().unwrap();
+        if let Err(e) = process_frame(frame, &config, mqtt) {
+            error!("Error processing GPS data: {:?}", e);
+        }
+    }
+}
+
+/// Returns a frame handler passing each parsed NMEA sentence to `handler` instead of publishing it.
+///
+/// UBX messages have no `ParsedData` representation and are skipped, as are sentences with a
+/// mismatching checksum. Sentence statistics are only kept for published data.
+///
+/// # Arguments
+///
+/// * `handler` - Called with every sentence that could be parsed.
+pub fn parsing_handler(handler: impl Fn(ParsedData)) -> impl Fn(&Frame) {
+    move |frame| {
+        if let Frame::Nmea(sentence) = frame {
+            let parsed_sentences =
+                parse_gps_data(sentence.as_bytes(), &mut SentenceStats::default());
+            for (parsed, checksum_valid) in parsed_sentences {
+                if checksum_valid {
+                    handler(parsed);
+                }
+            }
+        }
+    }
+}
+
+/// Publishes a UBX message interleaved with the NMEA output of u-blox receivers.
+///
+/// Only UBX-MON-HW is understood; other messages are ignored.
+fn publish_ubx_message(message: &UbxMessage, mqtt: &dyn Publisher, config: &AppConfig) {
+    if (message.class, message.id) == UBX_MON_HW {
+        if let Some(mon_hw) = parse_mon_hw(&message.payload) {
+            publish_mon_hw(&mon_hw, mqtt, config);
+        }
+    } else {
+        debug!(
+            "Ignoring UBX message class 0x{:02X} ID 0x{:02X}",
+            message.class, message.id
+        );
+    }
+}
+
+/// Parses every sentence in a buffer of received data.
+///
+/// The buffer is split into lines at `\r` or `\n`, so any of the `\r\n`, `\r` and `\n` line
+/// endings used by receivers works, and each line starting with '$' and containing '*' is parsed
+/// independently, so a buffer holding several sentences yields all of them in order.
+///
+/// # Arguments
+///
+/// * `data` - A slice of bytes representing received data.
+/// * `stats` - Statistics every sentence is counted in.
+///
+/// # Returns
+///
+/// Returns each parsed sentence together with whether its checksum matched.
+pub fn parse_gps_data(data: &[u8], stats: &mut SentenceStats) -> Vec<(ParsedData, bool)> {
+    let data_str = String::from_utf8_lossy(data);
+
+    sentence_lines(&data_str)
+        .filter_map(|line| parse_line(line, stats))
+        .collect()
+}
+
+/// Splits received data into the lines starting with '$' and containing '*'.
+fn sentence_lines(data: &str) -> impl Iterator<Item = &str> {
+    // Receivers terminate sentences with `\r\n`, but some with only `\r` or `\n`
+    data.split(['\r', '\n'])
+        .map(str::trim)
+        .filter(|line| line.starts_with('$') && line.contains('*'))
+}
+
+/// Parses one line from `sentence_lines` and counts it in `stats`.
+///
+/// # Returns
+///
+/// The parsed data and whether its checksum matched, or `None` if it couldn't be parsed.
+fn parse_line(line: &str, stats: &mut SentenceStats) -> Option<(ParsedData, bool)> {
+    let parsed = parse_nmea_sentence(line);
+    let checksum_valid = has_valid_checksum(line);
+    stats.record(line, parsed.is_some(), checksum_valid);
+    parsed.map(|parsed| (parsed, checksum_valid))
+}
+
+/// Returns the two-character talker ID of a sentence, e.g. `GN` for `$GNGGA,...`.
+///
+/// Proprietary sentences, such as u-blox `$PUBX`, start with `P` and have no talker ID.
+pub fn talker_id(sentence: &str) -> Option<&str> {
+    sentence
+        .trim_start_matches('$')
+        .get(..2)
+        .filter(|talker| !talker.starts_with('P') && talker.bytes().all(|b| b.is_ascii_uppercase()))
+}
+
+/// Publishes the talker ID of a fix sentence to `SAT/GLOBAL/TALKER` when `publish_talker` is set.
+///
+/// Only GGA, RMC, GLL and GNS carry a fix; the talkers of the per-constellation GSV and GSA
+/// sentences would make the topic flip with every burst. The ID is published when it changes.
+fn publish_talker(talker: &str, parsed: &ParsedData, mqtt: &dyn Publisher, config: &AppConfig) {
+    let is_fix_sentence = matches!(
+        parsed,
+        ParsedData::Gga(_) | ParsedData::Rmc(_) | ParsedData::Gll(_) | ParsedData::Gns(_)
+    );
+    if !config.publish_talker || !is_fix_sentence {
+        return;
+    }
+
+    if let Err(e) = publish_if_changed(
+        mqtt,
+        &config.topic("SAT/GLOBAL/TALKER"),
+        talker,
+        config.mqtt_qos,
+        config.mqtt_retain,
+    ) {
+        error!("Error pushing talker ID to MQTT: {:?}", e);
+    }
+}
+
+/// Returns whether the sentence reports a valid satellite fix: GGA with a fix quality other
+/// than 0 (invalid) and 6 (estimated), or RMC with status `A` and a mode other than `N` and `E`.
+fn is_valid_fix(parsed: &ParsedData) -> bool {
+    match parsed {
+        ParsedData::Gga(gga) => !matches!(gga.fix_quality, 0 | 6),
+        ParsedData::Rmc(rmc) => rmc.valid && rmc.mode != Some('E'),
+        _ => false,
+    }
+}
+
+/// Returns whether the sentence reports a dead-reckoning position estimated without satellites:
+/// GGA with fix quality 6, or RMC with status `A` and mode `E`.
+fn is_estimated_fix(parsed: &ParsedData) -> bool {
+    match parsed {
+        ParsedData::Gga(gga) => gga.fix_quality == 6,
+        ParsedData::Rmc(rmc) => rmc.valid && rmc.mode == Some('E'),
+        _ => false,
+    }
+}
+
+/// Set when the fix watchdog asked the application to exit, see `fix_timed_out`.
+static FIX_TIMEOUT_EXIT: AtomicBool = AtomicBool::new(false);
+
+/// Whether a fix timeout with `fix_timeout_action` set to `exit` stopped the sources, in which
+/// case the application should exit with a nonzero code after shutting down.
+pub fn fix_timed_out() -> bool {
+    FIX_TIMEOUT_EXIT.load(Ordering::SeqCst)
+}
+
+/// Publishes `STATUS/FIX` = `lost` once `fix_timeout_secs` passed without a valid fix.
+///
+/// With `fix_timeout_action` set to `exit`, the quit command is then sent to stop every source,
+/// so the application shuts down cleanly and exits with a nonzero code, see `fix_timed_out`, for
+/// a supervisor like systemd to restart it.
+fn check_fix_watchdog(mqtt: &dyn Publisher, config: &AppConfig) {
+    if config.fix_timeout_secs == 0 {
+        return;
+    }
+
+    let timeout = Duration::from_secs(config.fix_timeout_secs);
+    let changed = with_device(&FIX_WATCHDOG, config, |watchdog| {
+        watchdog.check(Instant::now(), timeout)
+    });
+    if changed != Some(FixState::Lost) {
+        return;
+    }
+
+    warn!(
+        "No valid position fix for {} seconds",
+        config.fix_timeout_secs
+    );
+    publish_fix_state(FixState::Lost, mqtt, config);
+
+    if config.fix_timeout_action == FixTimeoutAction::Exit {
+        error!("Exiting after losing the position fix");
+        FIX_TIMEOUT_EXIT.store(true, Ordering::SeqCst);
+        broadcast_quit();
+    }
+}
+
+/// Publishes the fix watchdog state to `STATUS/FIX`.
+fn publish_fix_state(state: FixState, mqtt: &dyn Publisher, config: &AppConfig) {
+    if let Err(e) = publish_message(
+        mqtt,
+        &config.topic("STATUS/FIX"),
+        state.as_str(),
+        config.mqtt_qos,
+        config.mqtt_retain,
+    ) {
+        error!("Error pushing fix state to MQTT: {:?}", e);
+    }
+}
+
+/// Publishes the milliseconds since the last valid RMC fix to `STATUS/FIX_AGE_MS`.
+///
+/// A `new_fix` is published right away, so the age drops back to about 0 with every fix. In
+/// between, the growing age is published at most once per `FIX_AGE_PUBLISH_INTERVAL`. Nothing is
+/// published before the first fix.
+fn publish_fix_age(new_fix: bool, mqtt: &dyn Publisher, config: &AppConfig) {
+    let now = Instant::now();
+    let due = with_device(&LAST_FIX_AGE_PUBLISH, config, |last_publish| {
+        if !new_fix
+            && last_publish.is_some_and(|last| now.duration_since(last) < FIX_AGE_PUBLISH_INTERVAL)
+        {
+            return false;
+        }
+        *last_publish = Some(now);
+        true
+    });
+    if !due {
+        return;
+    }
+
+    let Some(age) = fix_age_ms(&config.device_id) else {
+        return;
+    };
+    if let Err(e) = publish_message(
+        mqtt,
+        &config.topic("STATUS/FIX_AGE_MS"),
+        &age.to_string(),
+        config.mqtt_qos,
+        config.mqtt_retain,
+    ) {
+        error!("Error pushing fix age to MQTT: {:?}", e);
+    }
+}
+
+/// Publishes the sentence statistics to `STATS/` once per `STATS_PUBLISH_INTERVAL`.
+///
+/// The total, invalid and checksum error counts go to `STATS/TOTAL`, `STATS/INVALID` and
+/// `STATS/CHECKSUM_ERRORS`, and the per-type counts to `STATS/TYPE/{type}`.
+fn publish_stats_if_due(mqtt: &dyn Publisher, config: &AppConfig) {
+    let due = with_device(&LAST_STATS_PUBLISH, config, |last_publish| {
+        let last_publish = last_publish.get_or_insert_with(Instant::now);
+        if last_publish.elapsed() < STATS_PUBLISH_INTERVAL {
+            return false;
+        }
+        *last_publish = Instant::now();
+        true
+    });
+    if !due {
+        return;
+    }
+
+    let stats = with_device(&STATS, config, |stats| stats.clone());
+
+    let mut messages = vec![
+        ("STATS/TOTAL".to_string(), stats.total),
+        ("STATS/INVALID".to_string(), stats.invalid),
+        ("STATS/CHECKSUM_ERRORS".to_string(), stats.checksum_errors),
+    ];
+    for (sentence_type, count) in &stats.by_type {
+        messages.push((format!("STATS/TYPE/{}", sentence_type), *count));
+    }
+
+    for (suffix, value) in &messages {
+        if let Err(e) = publish_message(
+            mqtt,
+            &config.topic(suffix),
+            &value.to_string(),
+            config.mqtt_qos,
+            config.mqtt_retain,
+        ) {
+            error!("Error pushing {} to MQTT: {:?}", suffix, e);
+        }
+    }
+}
+
+/// Checks the `*hh` checksum of a `$`-prefixed NMEA sentence.
+///
+/// The checksum is the XOR of every byte between `$` and `*`, written as two hex digits.
+///
+/// # Returns
+///
+/// Returns `false` for sentences without a checksum or with a mismatching one.
+pub fn has_valid_checksum(sentence: &str) -> bool {
+    let Some((body, checksum)) = sentence
+        .strip_prefix('$')
+        .and_then(|sentence| sentence.split_once('*'))
+    else {
+        return false;
+    };
+
+    let checksum = checksum.trim_end();
+    if checksum.len() != 2 {
+        return false;
+    }
+
+    match u8::from_str_radix(checksum, 16) {
+        Ok(expected) => body.bytes().fold(0, |acc, byte| acc ^ byte) == expected,
+        Err(_) => false,
+    }
+}
+
+/// Parses a single NMEA sentence into typed data without publishing anything.
+///
+/// # Arguments
+///
+/// * `sentence` - The sentence, with or without the leading `$` and the `*hh` checksum suffix.
+///
+/// # Returns
+///
+/// Returns `None` for unknown sentence types and sentences with missing fields.
+pub fn parse_nmea_sentence(sentence: &str) -> Option<ParsedData> {
+    let sentence = sentence.trim_start_matches('$');
+    let sentence = sentence.split('*').next().unwrap_or(sentence);
+
+    let sentence_type = NmeaSentence::from_str(sentence);
+    let parsed = match sentence_type {
+        NmeaSentence::GSV => parse_gsv_sentence(sentence).map(ParsedData::Gsv),
+        NmeaSentence::GGA => parse_gga(sentence).map(ParsedData::Gga),
+        NmeaSentence::RMC => parse_rmc(sentence).map(ParsedData::Rmc),
+        NmeaSentence::VTG => parse_vtg(sentence).map(ParsedData::Vtg),
+        NmeaSentence::GSA => parse_gsa(sentence).map(ParsedData::Gsa),
+        NmeaSentence::GLL => parse_gll(sentence).map(ParsedData::Gll),
+        NmeaSentence::TXT => parse_txt(sentence).map(ParsedData::Txt),
+        NmeaSentence::ZDA => parse_zda(sentence).map(ParsedData::Zda),
+        NmeaSentence::GST => parse_gst(sentence).map(ParsedData::Gst),
+        NmeaSentence::GNS => parse_gns(sentence).map(ParsedData::Gns),
+        NmeaSentence::DTM => parse_dtm(sentence).map(ParsedData::Dtm),
+        NmeaSentence::GBS => parse_gbs(sentence).map(ParsedData::Gbs),
+        NmeaSentence::Unknown => {
+            debug!("Unknown Sentence Type: {}", sentence);
+            return None;
+        }
+    };
+
+    if parsed.is_none() {
+        warn!("Invalid {:?} Sentence: {}", sentence_type, sentence);
+    }
+
+    parsed
+}
+
+/// Prints the parsed data and publishes it to MQTT.
+///
+/// Sentences that are only meaningful together are combined here: GSV fragments are buffered
+/// until the burst is complete, and RMC and GGA of the same epoch are merged into a `PositionFix`.
+///
+/// # Arguments
+///
+/// * `parsed` - The data returned by `parse_nmea_sentence`.
+/// * `mqtt` - An MQTT client to publish the data.
+/// * `config` - Configuration settings for the application.
+pub fn publish_parsed(parsed: &ParsedData, mqtt: &dyn Publisher, config: &AppConfig) {
+    match parsed {
+        ParsedData::Gsv(gsv) => publish_gsv(gsv, mqtt, config),
+        ParsedData::Gga(gga) => publish_gga(gga, mqtt, config),
+        ParsedData::Rmc(rmc) => publish_rmc(rmc, mqtt, config),
+        ParsedData::Vtg(vtg) => publish_vtg(vtg, mqtt, config),
+        ParsedData::Gsa(gsa) => publish_gsa(gsa, mqtt, config),
+        ParsedData::Gll(gll) => publish_gll(gll, mqtt, config),
+        ParsedData::Txt(txt) => publish_txt(txt, mqtt, config),
+        ParsedData::Zda(zda) => publish_zda(zda, mqtt, config),
+        ParsedData::Gst(gst) => publish_gst(gst, mqtt, config),
+        ParsedData::Gns(gns) => publish_gns(gns, mqtt, config),
+        ParsedData::Dtm(dtm) => publish_dtm(dtm, mqtt, config),
+        ParsedData::Gbs(gbs) => publish_gbs(gbs, mqtt, config),
+    }
+}
+
+/// A single satellite entry from a GSV sentence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GsvSatellite {
+    pub prn: usize,
+    pub elevation: usize,
+    pub azimuth: usize,
+    pub snr: usize,
+}
+
+/// The fields of one GSV sentence, which is a single fragment of a GSV burst.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GsvSentence {
+    /// Talker ID identifying the constellation, e.g. `GP` or `GL`
+    pub talker: String,
+    pub total_sentences: usize,
+    pub sentence_number: usize,
+    pub satellites_in_view: usize,
+    pub satellites: Vec<GsvSatellite>,
+}
+
+/// Satellites collected so far for one talker's multi-sentence GSV burst.
+#[derive(Debug)]
+struct GsvBurst {
+    total_sentences: usize,
+    last_sentence: usize,
+    satellites_in_view: usize,
+    satellites: Vec<GsvSatellite>,
+}
+
+/// Splits a GSV sentence into its header fields and satellite entries.
+///
+/// Returns `None` if the sentence has fewer than the 4 header fields. A sentence without satellites,
+/// e.g. `GLGSV,1,1,00`, reports that the constellation has none in view.
+fn parse_gsv_sentence(data: &str) -> Option<GsvSentence> {
+    let parts: Vec<&str> = data.split(',').collect();
+    if parts.len() < 4 {
+        return None;
+    }
+
+    let satellites = (0..((parts.len() - 4) / 4))
+        .map(|i| {
+            let sat_index = 4 + i * 4;
+            GsvSatellite {
+                prn: parts[sat_index].parse::<usize>().unwrap_or(0),
+                elevation: parts[sat_index + 1].parse::<usize>().unwrap_or(0),
+                azimuth: parts[sat_index + 2].parse::<usize>().unwrap_or(0),
+                snr: parts[sat_index + 3].parse::<usize>().unwrap_or(0),
+            }
+        })
+        .collect();
+
+    Some(GsvSentence {
+        // Extract message type prefix (e.g., "GP" from "$GPGSV")
+        talker: data.get(0..2).unwrap_or("--").to_string(),
+        total_sentences: parts[1].parse::<usize>().unwrap_or(0),
+        sentence_number: parts[2].parse::<usize>().unwrap_or(0),
+        satellites_in_view: parts[3].parse::<usize>().unwrap_or(0),
+        satellites,
+    })
+}
+
+/// Adds a GSV fragment to the burst buffered for `talker` and returns the burst once it is complete.
+///
+/// A burst is complete when the sentence number reaches the total number of sentences.
+/// Fragments that arrive out of sequence discard the buffered burst, and a new burst
+/// (sentence number 1) replaces any burst that was interrupted before completing.
+fn accumulate_gsv(
+    bursts: &mut HashMap<String, GsvBurst>,
+    talker: &str,
+    sentence: GsvSentence,
+) -> Option<GsvBurst> {
+    let GsvSentence {
+        total_sentences,
+        sentence_number,
+        satellites_in_view,
+        satellites,
+        ..
+    } = sentence;
+
+    if total_sentences == 0 || sentence_number == 0 || sentence_number > total_sentences {
+        warn!(
+            "Invalid GSV sentence number {} of {} for {}",
+            sentence_number, total_sentences, talker
+        );
+        return None;
+    }
+
+    if sentence_number == 1 {
+        if bursts.contains_key(talker) {
+            warn!("Discarding incomplete GSV burst for {}", talker);
+        }
+        bursts.insert(
+            talker.to_string(),
+            GsvBurst {
+                total_sentences,
+                last_sentence: 0,
+                satellites_in_view,
+                satellites: Vec::new(),
+            },
+        );
+    }
+
+    let burst = match bursts.get_mut(talker) {
+        Some(burst)
+            if burst.total_sentences == total_sentences
+                && burst.last_sentence + 1 == sentence_number =>
+        {
+            burst
+        }
+        _ => {
+            warn!(
+                "Discarding out-of-sequence GSV sentence {} of {} for {}",
+                sentence_number, total_sentences, talker
+            );
+            bursts.remove(talker);
+            return None;
+        }
+    };
+
+    burst.last_sentence = sentence_number;
+    burst.satellites.extend(satellites);
+
+    if sentence_number == total_sentences {
+        bursts.remove(talker)
+    } else {
+        None
+    }
+}
+
+/// Records the PRNs of `talker`'s latest complete GSV burst and returns the PRNs of its previous
+/// burst that are gone.
+///
+/// PRNs still reported by another talker are kept, as they share the `SAT/VEHICLES/{prn}` topic.
+fn expired_satellites(
+    seen: &mut HashMap<String, BTreeSet<usize>>,
+    talker: &str,
+    current: BTreeSet<usize>,
+) -> Vec<usize> {
+    let previous = seen.insert(talker.to_string(), current).unwrap_or_default();
+    previous
+        .into_iter()
+        .filter(|prn| !seen.values().any(|prns| prns.contains(prn)))
+        .collect()
+}
+
+/// Buffers a GSV (Satellites in View) fragment and publishes the complete burst to MQTT.
+///
+/// # Arguments
+///
+/// * `gsv` - One parsed GSV sentence.
+/// * `mqtt` - An MQTT client to publish the parsed data.
+/// * `config` - Configuration settings for the application.
+///
+/// A full GSV report is split across several sentences, so fragments are buffered per talker ID
+/// until the last sentence of the burst arrives. The number of satellites in view of the
+/// constellation, the total over the latest bursts of all talkers and the details of each
+/// satellite (PRN, elevation, azimuth and SNR) are then printed and published to MQTT once.
+/// Satellites of the talker's previous burst missing from this one have their retained
+/// `SAT/VEHICLES/{prn}` message cleared, so dashboards don't keep showing them.
+fn publish_gsv(gsv: &GsvSentence, mqtt: &dyn Publisher, config: &AppConfig) {
+    let sat_type = match gsv.talker.as_str() {
+        "GP" => SatelliteType::GPS,
+        "GL" => SatelliteType::GLONASS,
+        "GA" => SatelliteType::Galileo,
+        "GB" | "BD" => SatelliteType::BeiDou,
+        _ => {
+            debug!("Unknown satellite type prefix: {}", gsv.talker);
+            SatelliteType::Unknown
+        }
+    };
+
+    let burst = with_device(&GSV_BURSTS, config, |bursts| {
+        accumulate_gsv(bursts, &gsv.talker, gsv.clone())
+    });
+    let burst = match burst {
+        Some(burst) => burst,
+        None => return,
+    };
+
+    // Each constellation reports its own satellites, so the total adds up all talkers
+    let total = with_device(&SATELLITE_COUNTS, config, |counts| {
+        counts.insert(gsv.talker.clone(), burst.satellites_in_view);
+        counts.values().sum::<usize>()
+    });
+    debug!(
+        "Satellites in view: {} from {}, {} in total",
+        burst.satellites_in_view, gsv.talker, total
+    );
+
+    if sat_type != SatelliteType::Unknown {
+        if let Err(e) = publish_message(
+            mqtt,
+            &config.topic(&format!("SAT/GLOBAL/{}/NUM", sat_type.as_str())),
+            &format!("{}", burst.satellites_in_view),
+            config.mqtt_qos,
+            config.mqtt_retain,
+        ) {
+            error!("Error pushing number of satellites to MQTT: {:?}", e);
+        }
+    }
+
+    // Publish total satellites count
+    if let Err(e) = publish_message(
+        mqtt,
+        &config.topic("SAT/GLOBAL/NUM"),
+        &format!("{}", total),
+        config.mqtt_qos,
+        config.mqtt_retain,
+    ) {
+        error!("Error pushing total number of satellites to MQTT: {:?}", e);
+    }
+
+    // Process each satellite
+    for sat in &burst.satellites {
+        let in_view = sat.snr > 0;
+
+        debug!(
+            "Satellite PRN: {}, Type: {}, Elevation: {}, Azimuth: {}, SNR: {}, In View: {}",
+            sat.prn,
+            sat_type.as_str(),
+            sat.elevation,
+            sat.azimuth,
+            sat.snr,
+            in_view
+        );
+
+        // Keep original MQTT topic structure
+        let sat_topic = config.topic(&format!("SAT/VEHICLES/{}", sat.prn));
+        let sat_info = format!(
+            "PRN: {}, Type: {}, Elevation: {}, Azimuth: {}, SNR: {}, In View: {}",
+            sat.prn,
+            sat_type.as_str(),
+            sat.elevation,
+            sat.azimuth,
+            sat.snr,
+            in_view
+        );
+
+        if let Err(e) = publish_message(
+            mqtt,
+            &sat_topic,
+            &sat_info,
+            config.mqtt_qos,
+            config.mqtt_retain,
+        ) {
+            error!("Error pushing satellite info to MQTT: {:?}", e);
+        }
+    }
+
+    let current = burst.satellites.iter().map(|sat| sat.prn).collect();
+    let expired = with_device(&SATELLITES_IN_VIEW, config, |seen| {
+        expired_satellites(seen, &gsv.talker, current)
+    });
+    for prn in expired {
+        debug!("Satellite PRN {} is no longer in view", prn);
+        let sat_topic = config.topic(&format!("SAT/VEHICLES/{}", prn));
+        if let Err(e) = clear_retained(mqtt, &sat_topic, config.mqtt_qos) {
+            error!("Error clearing satellite info from MQTT: {:?}", e);
+        }
+    }
+}
+
+/// Fix data from a GGA (Global Positioning System Fix Data) sentence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GgaData {
+    /// Raw UTC time field, used to pair the sentence with RMC of the same epoch
+    pub utc_time: String,
+    /// Latitude in decimal degrees
+    pub latitude: f64,
+    /// Longitude in decimal degrees
+    pub longitude: f64,
+    /// Altitude above mean sea level in meters
+    pub altitude: f64,
+    /// Fix quality indicator (0-8)
+    pub fix_quality: usize,
+    /// Number of satellites used in the fix
+    pub satellites_in_use: usize,
+    /// Geoidal separation in meters, when the receiver provides it
+    pub geoid_separation: Option<f64>,
+}
+
+/// Parses the fields of a GGA sentence.
+///
+/// Returns `None` if the sentence has fewer than 10 fields.
+fn parse_gga(data: &str) -> Option<GgaData> {
+    let parts: Vec<&str> = data.split(',').collect();
+    if parts.len() < 10 {
+        return None;
+    }
+
+    Some(GgaData {
+        utc_time: parts[1].to_string(),
+        latitude: parse_latitude(parts[2], parts[3]),
+        longitude: parse_longitude(parts[4], parts[5]),
+        altitude: parts[9].parse::<f64>().unwrap_or(0.0),
+        fix_quality: parts[6].parse::<usize>().unwrap_or(0),
+        satellites_in_use: parts[7].parse::<usize>().unwrap_or(0),
+        geoid_separation: parts.get(11).and_then(|v| v.parse::<f64>().ok()),
+    })
+}
+
+/// Publishes GGA fix data to MQTT.
+///
+/// # Arguments
+///
+/// * `gga` - The parsed GGA sentence.
+/// * `mqtt` - An MQTT client to publish the parsed data.
+/// * `config` - Configuration settings for the application.
+///
+/// The altitude, fix quality (raw to `QTY`, labelled to `QTY_TEXT`) and number of satellites used
+/// in the fix are published to MQTT. The geoidal separation is published to `GEOID_SEP` when present,
+/// and the vertical speed since the previous fix to `VSPEED`.
+fn publish_gga(gga: &GgaData, mqtt: &dyn Publisher, config: &AppConfig) {
+    debug!("Latitude: {}", gga.latitude);
+    debug!("Longitude: {}", gga.longitude);
+    debug!("Altitude: {}", gga.altitude);
+    debug!("Satellites in use: {}", gga.satellites_in_use);
+
+    // Push altitude to MQTT, at most every `position_min_interval_ms`
+    if let Err(e) = publish_rate_limited(
+        mqtt,
+        &config.topic("ALT"),
+        &format_decimals(gga.altitude, config.altitude_decimals),
+        config.mqtt_qos,
+        config.mqtt_retain,
+        position_interval(config),
+    ) {
+        error!("Error pushing altitude to MQTT: {:?}", e);
+    }
+
+    // Push vertical speed to MQTT, once two consecutive fixes are available
+    let vertical_speed = with_device(&VERTICAL_SPEED, config, |vertical_speed| {
+        vertical_speed.update(&gga.utc_time, gga.altitude, gga.fix_quality)
+    });
+    if let Some(vertical_speed) = vertical_speed {
+        if let Err(e) = publish_message(
+            mqtt,
+            &config.topic("VSPEED"),
+            &format_decimals(vertical_speed, config.speed_decimals),
+            config.mqtt_qos,
+            config.mqtt_retain,
+        ) {
+            error!("Error pushing vertical speed to MQTT: {:?}", e);
+        }
+    }
+
+    // Push fix quality to MQTT
+    if let Err(e) = publish_message(
+        mqtt,
+        &config.topic("QTY"),
+        &format!("{}", gga.fix_quality),
+        config.mqtt_qos,
+        config.mqtt_retain,
+    ) {
+        error!("Error pushing fix quality to MQTT: {:?}", e);
+    }
+
+    // Push fix quality label to MQTT
+    if let Err(e) = publish_message(
+        mqtt,
+        &config.topic("QTY_TEXT"),
+        fix_quality_label(gga.fix_quality),
+        config.mqtt_qos,
+        config.mqtt_retain,
+    ) {
+        error!("Error pushing fix quality label to MQTT: {:?}", e);
+    }
+
+    // Push number of satellites used in the fix to MQTT
+    if let Err(e) = publish_message(
+        mqtt,
+        &config.topic("SAT/GLOBAL/IN_USE"),
+        &format!("{}", gga.satellites_in_use),
+        config.mqtt_qos,
+        config.mqtt_retain,
+    ) {
+        error!("Error pushing satellites in use to MQTT: {:?}", e);
+    }
+
+    // Push geoidal separation to MQTT, skipping it when the field is blank or missing
+    if let Some(geoid_separation) = gga.geoid_separation {
+        if let Err(e) = publish_message(
+            mqtt,
+            &config.topic("GEOID_SEP"),
+            &format_decimals(geoid_separation, config.altitude_decimals),
+            config.mqtt_qos,
+            config.mqtt_retain,
+        ) {
+            error!("Error pushing geoidal separation to MQTT: {:?}", e);
+        }
+    }
+
+    let fix = with_device(&FIX_BUFFER, config, |buffer| {
+        buffer.update_from_gga(
+            &gga.utc_time,
+            gga.altitude,
+            gga.fix_quality,
+            gga.satellites_in_use,
+        )
+    });
+    if let Some(fix) = fix {
+        handle_position_fix(&fix, mqtt, config);
+    }
+}
+
+/// Position, speed and time from an RMC (Recommended Minimum Specific GNSS Data) sentence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RmcData {
+    /// Raw UTC time field, used to pair the sentence with GGA of the same epoch
+    pub utc_time: String,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    pub millisecond: u32,
+    pub day: u32,
+    pub month: u32,
+    /// Two-digit year
+    pub year: u32,
+    /// Latitude in decimal degrees
+    pub latitude: f64,
+    /// Longitude in decimal degrees
+    pub longitude: f64,
+    /// Speed over ground in knots, `None` when the field is empty, as some receivers only
+    /// report speed in VTG
+    pub speed: Option<f64>,
+    /// Course over ground in degrees, `None` when the field is empty
+    pub course: Option<f64>,
+    /// Magnetic variation in degrees, west negative, when the receiver provides it
+    pub magnetic_variation: Option<f64>,
+    /// Mode indicator of NMEA 2.3 and later, e.g. `A` (autonomous) or `N` (no fix)
+    pub mode: Option<char>,
+    /// Whether the status field is `A` (valid) rather than `V` (receiver warning), and the mode
+    /// indicator, when present, isn't `N` (no fix)
+    pub valid: bool,
+}
+
+/// Parses the fields of an RMC sentence.
+///
+/// Returns `None` if the sentence has fewer than 10 fields.
+fn parse_rmc(data: &str) -> Option<RmcData> {
+    let parts: Vec<&str> = data.split(',').collect();
+    if parts.len() < 10 {
+        return None;
+    }
+
+    // Parse UTC time and date
+    let (hour, minute, second, millisecond) = parse_utc_time(parts[1]);
+    let (day, month, year) = parse_date(parts[9]);
+    let mode = parts.get(12).and_then(|mode| mode.chars().next());
+
+    Some(RmcData {
+        utc_time: parts[1].to_string(),
+        hour,
+        minute,
+        second,
+        millisecond,
+        day,
+        month,
+        year,
+        latitude: parse_latitude(parts[3], parts[4]),
+        longitude: parse_longitude(parts[5], parts[6]),
+        speed: parts[7].parse::<f64>().ok(),
+        course: parts[8].parse::<f64>().ok(),
+        magnetic_variation: parse_magnetic_variation(
+            parts.get(10).copied().unwrap_or(""),
+            parts.get(11).copied().unwrap_or(""),
+        ),
+        mode,
+        valid: parts[2] == "A" && mode != Some('N'),
+    })
+}
+
+/// Publishes RMC data to MQTT.
+///
+/// # Arguments
+///
+/// * `rmc` - The parsed RMC sentence.
+/// * `mqtt` - An MQTT client to publish the parsed data.
+/// * `config` - Configuration settings for the application.
+///
+/// The RMC time, date, latitude, longitude and speed are published to MQTT, the speed only when
+/// the field is filled and `speed_source` selects RMC. The magnetic variation is published to `MAG_VAR` when the receiver
+/// provides it, and the decoded mode indicator to `RMC_MODE`. For a void (`V`) status or the `N`
+/// (no fix) mode only the time, date and mode are published, unless `publish_invalid_fixes` is
+/// enabled. Positions at exactly 0°, 0° don't reach the filtered position, trip, geofences or
+/// fix outputs.
+fn publish_rmc(rmc: &RmcData, mqtt: &dyn Publisher, config: &AppConfig) {
+    with_device(&RMC_HAS_SPEED, config, |has_speed| {
+        *has_speed = rmc.speed.is_some()
+    });
+
+    // Push time to MQTT when it changes
+    if let Err(e) = publish_if_changed(
+        mqtt,
+        &config.topic("TME"),
+        &format!("{:02}:{:02}:{:02}", rmc.hour, rmc.minute, rmc.second),
+        config.mqtt_qos,
+        config.mqtt_retain,
+    ) {
+        error!("Error pushing time to MQTT: {:?}", e);
+    }
+
+    // Push millisecond-precision time to MQTT, which changes on every sentence at high rates
+    if let Err(e) = publish_message(
+        mqtt,
+        &config.topic("TME_MS"),
+        &format!(
+            "{:02}:{:02}:{:02}.{:03}",
+            rmc.hour, rmc.minute, rmc.second, rmc.millisecond
+        ),
+        config.mqtt_qos,
+        config.mqtt_retain,
+    ) {
+        error!("Error pushing millisecond time to MQTT: {:?}", e);
+    }
+
+    // Push date to MQTT when it changes
+    if let Err(e) = publish_if_changed(
+        mqtt,
+        &date_topic(config),
+        &format!(
+            "{:02}.{:02}.{:04}",
+            rmc.day,
+            rmc.month,
+            expand_year(rmc.year, config.year_pivot)
+        ),
+        config.mqtt_qos,
+        config.mqtt_retain,
+    ) {
+        error!("Error pushing date to MQTT: {:?}", e);
+    }
+
+    // Push date and time together, so consumers don't have to pair TME and DTE
+    let timestamp = rfc3339_timestamp(rmc, config.year_pivot);
+    if let Some(timestamp) = &timestamp {
+        if let Err(e) = publish_message(
+            mqtt,
+            &config.topic("TIMESTAMP"),
+            timestamp,
+            config.mqtt_qos,
+            config.mqtt_retain,
+        ) {
+            error!("Error pushing timestamp to MQTT: {:?}", e);
+        }
+    }
+
+    // Push the navigation mode to MQTT when it changes, only when the receiver sends it
+    if let Some(mode) = rmc.mode {
+        if let Err(e) = publish_if_changed(
+            mqtt,
+            &config.topic("RMC_MODE"),
+            decode_mode_indicator(mode),
+            config.mqtt_qos,
+            config.mqtt_retain,
+        ) {
+            error!("Error pushing RMC mode to MQTT: {:?}", e);
+        }
+    }
+
+    if !rmc.valid && !config.publish_invalid_fixes {
+        debug!("Skipping position of void RMC sentence");
+        return;
+    }
+
+    // An empty speed field counts as standing still for the stationary filter
+    let motion = with_device(&MOTION, config, |filter| {
+        filter.update(
+            rmc.speed.unwrap_or(0.0),
+            rmc.latitude,
+            rmc.longitude,
+            config.stationary_speed_threshold,
+            config.stationary_min_distance_m,
+        )
+    });
+
+    // Push motion state to MQTT when it changes
+    if let Some(stationary) = motion.changed_to {
+        if let Err(e) = publish_message(
+            mqtt,
+            &config.topic("MOTION/STATE"),
+            if stationary { "stationary" } else { "moving" },
+            config.mqtt_qos,
+            config.mqtt_retain,
+        ) {
+            error!("Error pushing motion state to MQTT: {:?}", e);
+        }
+    }
+
+    // Push latitude and longitude to MQTT, unless suppressed as stationary jitter or rate limited
+    if motion.publish_position {
+        if let Err(e) = publish_rate_limited(
+            mqtt,
+            &config.topic("LAT"),
+            &format_position(
+                rmc.latitude,
+                true,
+                config.coordinate_format,
+                config.coordinate_decimals,
+            ),
+            config.mqtt_qos,
+            config.mqtt_retain,
+            position_interval(config),
+        ) {
+            error!("Error pushing latitude to MQTT: {:?}", e);
+        }
+
+        if let Err(e) = publish_rate_limited(
+            mqtt,
+            &config.topic("LNG"),
+            &format_position(
+                rmc.longitude,
+                false,
+                config.coordinate_format,
+                config.coordinate_decimals,
+            ),
+            config.mqtt_qos,
+            config.mqtt_retain,
+            position_interval(config),
+        ) {
+            error!("Error pushing longitude to MQTT: {:?}", e);
+        }
+    }
+    if is_speed_source(SpeedSource::Rmc, rmc.speed.is_some(), config) {
+        publish_speed(motion.speed, mqtt, config);
+    }
+
+    // Push magnetic variation to MQTT, only when the receiver fills the fields
+    if let Some(magnetic_variation) = rmc.magnetic_variation {
+        if let Err(e) = publish_message(
+            mqtt,
+            &config.topic("MAG_VAR"),
+            &format!("{}", magnetic_variation),
+            config.mqtt_qos,
+            config.mqtt_retain,
+        ) {
+            error!("Error pushing magnetic variation to MQTT: {:?}", e);
+        }
+    }
+
+    // Positions at exactly 0°, 0° come from failed coordinate parses
+    if rmc.latitude == 0.0 && rmc.longitude == 0.0 {
+        debug!("Skipping fix outputs of RMC sentence at 0°, 0°");
+        return;
+    }
+    publish_filtered_position(rmc, &motion, mqtt, config);
+
+    // Without a date the fix can't be timestamped, so it's kept out of the fix outputs
+    let fix = match timestamp {
+        Some(timestamp) => {
+            let (fix, latest) = with_device(&FIX_BUFFER, config, |buffer| {
+                let fix = buffer.update_from_rmc(
+                    &rmc.utc_time,
+                    timestamp,
+                    rmc.latitude,
+                    rmc.longitude,
+                    motion.speed,
+                    rmc.course,
+                );
+                (fix, buffer.latest())
+            });
+            csv_writer::write_fix(&latest, &config.device_id);
+            fix
+        }
+        None => {
+            debug!("Skipping fix outputs of RMC sentence without a date");
+            None
+        }
+    };
+    geofence::check_position(rmc.latitude, rmc.longitude, mqtt, config);
+    publish_trip(rmc.latitude, rmc.longitude, mqtt, config);
+    if let Some(fix) = fix {
+        handle_position_fix(&fix, mqtt, config);
+    }
+}
+
+/// Runs a valid RMC fix through the `position_filter` and publishes the result to `LAT_FILTERED`
+/// and `LNG_FILTERED`.
+///
+/// The filter is updated with every valid fix, while the topics follow `LAT`/`LNG`: they are
+/// rate limited the same way and skipped while stationary jitter is suppressed.
+fn publish_filtered_position(
+    rmc: &RmcData,
+    motion: &MotionUpdate,
+    mqtt: &dyn Publisher,
+    config: &AppConfig,
+) {
+    if config.position_filter == PositionFilter::None || !rmc.valid {
+        return;
+    }
+
+    let time = f64::from(rmc.hour * 3600 + rmc.minute * 60 + rmc.second)
+        + f64::from(rmc.millisecond) / 1000.0;
+    let (lat, lon) = with_device(&KALMAN, config, |kalman| {
+        kalman.update(
+            time,
+            (rmc.latitude, rmc.longitude),
+            motion.speed,
+            rmc.course,
+            config.position_filter_process_noise,
+            config.position_filter_measurement_noise,
+        )
+    });
+    if !motion.publish_position {
+        return;
+    }
+
+    for (suffix, value, is_lat) in [("LAT_FILTERED", lat, true), ("LNG_FILTERED", lon, false)] {
+        if let Err(e) = publish_rate_limited(
+            mqtt,
+            &config.topic(suffix),
+            &format_position(
+                value,
+                is_lat,
+                config.coordinate_format,
+                config.coordinate_decimals,
+            ),
+            config.mqtt_qos,
+            config.mqtt_retain,
+            position_interval(config),
+        ) {
+            error!("Error pushing {} to MQTT: {:?}", suffix, e);
+        }
+    }
+}
+
+/// Updates the trip with a new position and publishes `TRIP/DISTANCE_M` and `TRIP/BEARING`.
+///
+/// Nothing is published for the first position or while movement stays below
+/// `trip_min_distance_m`.
+fn publish_trip(lat: f64, lon: f64, mqtt: &dyn Publisher, config: &AppConfig) {
+    let update = with_device(&TRIP, config, |trip| {
+        trip.update(lat, lon, config.trip_min_distance_m)
+            .map(|bearing| (bearing, trip.distance_m))
+    });
+    let Some((bearing, distance_m)) = update else {
+        return;
+    };
+
+    for (suffix, value) in [("TRIP/DISTANCE_M", distance_m), ("TRIP/BEARING", bearing)] {
+        if let Err(e) = publish_message(
+            mqtt,
+            &config.topic(suffix),
+            &format!("{:.1}", value),
+            config.mqtt_qos,
+            config.mqtt_retain,
+        ) {
+            error!("Error pushing {} to MQTT: {:?}", suffix, e);
+        }
+    }
+}
+
+/// Handles a complete position fix combined from RMC and GGA.
+///
+/// The fix is appended to the GPX track, the InfluxDB batch, the SQLite database and the `jsonl`
+/// stdout stream when those sinks are enabled. When `mqtt_json_mode` is
+/// enabled, the fix is published as a single JSON document to `POSITION` so subscribers get a
+/// consistent snapshot instead of assembling it from separate topics. When `geojson_topic` is
+/// set, the fix is also published there as a GeoJSON `Feature`, and when `maps_url_topic` is set
+/// a maps link to a valid fix is published there.
+fn handle_position_fix(fix: &PositionFix, mqtt: &dyn Publisher, config: &AppConfig) {
+    gpx_writer::write_fix(fix, &config.device_id);
+    influx_writer::write_fix(fix, &config.device_id);
+    #[cfg(feature = "sqlite")]
+    sqlite_writer::write_fix(fix, &config.device_id);
+    stdout_writer::write_fix(fix, &config.device_id);
+
+    if config.mqtt_json_mode {
+        publish_position_json(fix, mqtt, config);
+    }
+    if let Some(topic) = &config.geojson_topic {
+        publish_geojson(fix, topic, mqtt, config);
+    }
+    if let Some(topic) = &config.maps_url_topic {
+        publish_maps_url(fix, topic, mqtt, config);
+    }
+}
+
+/// Publishes a retained maps link to the fix built from `maps_url_template`.
+///
+/// Only valid satellite fixes are linked, so the link never points at the 0°, 0° position of a
+/// receiver without a fix or at a dead-reckoning estimate.
+fn publish_maps_url(fix: &PositionFix, topic: &str, mqtt: &dyn Publisher, config: &AppConfig) {
+    if !fix.is_valid() {
+        return;
+    }
+
+    let topic = topic.replace("{device}", &config.device_id);
+    let url = config
+        .maps_url_template
+        .replace(
+            "{lat}",
+            &format_decimals(fix.lat, config.coordinate_decimals),
+        )
+        .replace(
+            "{lon}",
+            &format_decimals(fix.lon, config.coordinate_decimals),
+        );
+    if let Err(e) = publish_message(mqtt, &topic, &url, config.mqtt_qos, true) {
+        error!("Error pushing maps URL to MQTT: {:?}", e);
+    }
+}
+
+/// Serializes the fix tagged with `device_id` as the single-line JSON document of `POSITION`.
+pub fn position_json(fix: &PositionFix, device_id: &str) -> serde_json::Result<String> {
+    serde_json::to_string(&DevicePosition { device_id, fix })
+}
+
+/// Publishes the fix tagged with the `device_id` as one JSON document to `POSITION`.
+fn publish_position_json(fix: &PositionFix, mqtt: &dyn Publisher, config: &AppConfig) {
+    let payload = match position_json(fix, &config.device_id) {
+        Ok(payload) => payload,
+        Err(e) => {
+            error!("Error serializing position to JSON: {:?}", e);
+            return;
+        }
+    };
+
+    if let Err(e) = publish_message(
+        mqtt,
+        &config.topic("POSITION"),
+        &payload,
+        config.mqtt_qos,
+        config.mqtt_retain,
+    ) {
+        error!("Error pushing position JSON to MQTT: {:?}", e);
+    }
+}
+
+/// Builds a GeoJSON `Feature` with a `Point` geometry for the fix.
+///
+/// GeoJSON orders coordinates longitude first, the reverse of the latitude/longitude order
+/// used everywhere else.
+fn geojson_feature(fix: &PositionFix) -> serde_json::Value {
+    serde_json::json!({
+        "type": "Feature",
+        "geometry": {
+            "type": "Point",
+            "coordinates": [fix.lon, fix.lat],
+        },
+        "properties": {
+            "alt": fix.alt,
+            "speed": fix.speed,
+            "course": fix.course,
+            "timestamp": fix.timestamp,
+        },
+    })
+}
+
+/// Publishes the fix as a GeoJSON `Feature` to `topic`.
+///
+/// The message is always retained, so a map opened later immediately shows the last position.
+fn publish_geojson(fix: &PositionFix, topic: &str, mqtt: &dyn Publisher, config: &AppConfig) {
+    let topic = topic.replace("{device}", &config.device_id);
+    let payload = geojson_feature(fix).to_string();
+    if let Err(e) = publish_message(mqtt, &topic, &payload, config.mqtt_qos, true) {
+        error!("Error pushing GeoJSON position to MQTT: {:?}", e);
+    }
+}
+
+/// JSON document published to `POSITION`: the fix tagged with the `device_id`.
+#[derive(Serialize)]
+struct DevicePosition<'a> {
+    device_id: &'a str,
+    #[serde(flatten)]
+    fix: &'a PositionFix,
+}
+
+/// Whether the speed topics are published from `sentence` (RMC or VTG), given whether its speed
+/// field is filled. Nothing is published for an empty field.
+///
+/// With `SpeedSource::Auto` RMC takes precedence while its speed field is filled, and VTG is used
+/// once an RMC sentence arrives without one, or when the receiver sends no RMC at all.
+fn is_speed_source(sentence: SpeedSource, has_speed: bool, config: &AppConfig) -> bool {
+    has_speed
+        && match config.speed_source {
+            SpeedSource::Auto => {
+                sentence == SpeedSource::Rmc
+                    || !with_device(&RMC_HAS_SPEED, config, |rmc_has_speed| *rmc_has_speed)
+            }
+            source => source == sentence,
+        }
+}
+
+/// Minimum interval between position updates, see `position_min_interval_ms`.
+fn position_interval(config: &AppConfig) -> Duration {
+    Duration::from_millis(config.position_min_interval_ms)
+}
+
+/// Minimum interval between speed and course updates, see `speed_min_interval_ms`.
+fn speed_interval(config: &AppConfig) -> Duration {
+    Duration::from_millis(config.speed_min_interval_ms)
+}
+
+/// Publishes the speed over ground in knots to `SPD`, and converted to `SPD_MS` and `SPD_MPH`.
+///
+/// `SPD` is averaged over `smoothing_window` samples when configured.
+fn publish_speed(speed: f64, mqtt: &dyn Publisher, config: &AppConfig) {
+    let smoothed = if config.smoothing_window > 1 {
+        with_device(&SMOOTHER, config, |smoother| {
+            smoother.smooth_speed(speed, config.smoothing_window)
+        })
+    } else {
+        speed
+    };
+
+    for (value, suffix) in [
+        (smoothed, "SPD"),
+        (knots_to_ms(speed), "SPD_MS"),
+        (knots_to_mph(speed), "SPD_MPH"),
+    ] {
+        if let Err(e) = publish_rate_limited(
+            mqtt,
+            &config.topic(suffix),
+            &format_decimals(value, config.speed_decimals),
+            config.mqtt_qos,
+            config.mqtt_retain,
+            speed_interval(config),
+        ) {
+            error!("Error pushing {} to MQTT: {:?}", suffix, e);
+        }
+    }
+}
+
+/// Course and speed from a VTG (Course Over Ground and Ground Speed) sentence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VtgData {
+    /// True course over ground in degrees
+    pub course: Option<f64>,
+    pub speed_knots: Option<f64>,
+    pub speed_kph: Option<f64>,
+}
+
+/// Parses the fields of a VTG sentence.
+///
+/// Returns `None` if the sentence has fewer than 9 fields. Empty fields, e.g. the course while
+/// the receiver has no fix, are `None`.
+fn parse_vtg(data: &str) -> Option<VtgData> {
+    let parts: Vec<&str> = data.split(',').collect();
+    if parts.len() < 9 {
+        return None;
+    }
+
+    Some(VtgData {
+        course: parts[1].parse::<f64>().ok(),
+        speed_knots: parts[5].parse::<f64>().ok(),
+        speed_kph: parts[7].parse::<f64>().ok(),
+    })
+}
+
+/// Publishes VTG data to MQTT.
+///
+/// # Arguments
+///
+/// * `vtg` - The parsed VTG sentence.
+/// * `mqtt` - An MQTT client to publish the parsed data.
+/// * `config` - Configuration settings for the application.
+///
+/// The course and the speed in knots and kph are published to MQTT. `SPD`, `SPD_MS` and `SPD_MPH`
+/// are published only when `speed_source` selects VTG. Empty fields are skipped rather than
+/// published as 0, which would e.g. swing a compass needle north during a brief dropout.
+fn publish_vtg(vtg: &VtgData, mqtt: &dyn Publisher, config: &AppConfig) {
+    // Course averaged over `smoothing_window` samples when configured
+    let course = match vtg.course {
+        Some(course) if config.smoothing_window > 1 => {
+            Some(with_device(&SMOOTHER, config, |smoother| {
+                smoother.smooth_course(course, config.smoothing_window)
+            }))
+        }
+        course => course,
+    };
+
+    let messages = [
+        (course, "CRS"),
+        (vtg.speed_knots, "SPD_KTS"),
+        (vtg.speed_kph, "SPD_KPH"),
+    ];
+
+    for (value, suffix) in messages {
+        let Some(value) = value else {
+            continue;
+        };
+        if let Err(e) = publish_rate_limited(
+            mqtt,
+            &config.topic(suffix),
+            &format_decimals(value, config.speed_decimals),
+            config.mqtt_qos,
+            config.mqtt_retain,
+            speed_interval(config),
+        ) {
+            error!("Error pushing {} to MQTT: {:?}", suffix, e);
+        }
+    }
+
+    if let Some(speed_knots) = vtg.speed_knots {
+        if is_speed_source(SpeedSource::Vtg, true, config) {
+            publish_speed(speed_knots, mqtt, config);
+        }
+    }
+}
+
+/// Fix type and dilution of precision from a GSA (GNSS DOP and Active Satellites) sentence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GsaData {
+    pub message_id: String,
+    /// Fix type label (`Not Available`, `2D`, `3D` or `Unknown`)
+    pub fix_type: &'static str,
+    /// PRN of the first satellite used in the fix
+    pub prn: usize,
+    /// Constellation from the NMEA 4.10 system ID, when present
+    pub system: Option<&'static str>,
+    pub pdop: Option<f64>,
+    pub hdop: Option<f64>,
+    pub vdop: Option<f64>,
+}
+
+/// Parses the fields of a GSA sentence.
+///
+/// Returns `None` if the sentence has fewer than 17 fields.
+fn parse_gsa(data: &str) -> Option<GsaData> {
+    let parts: Vec<&str> = data.split(',').collect();
+    if parts.len() < 17 {
+        return None;
+    }
+
+    let fix_type = match parts[2] {
+        "1" => "Not Available",
+        "2" => "2D",
+        "3" => "3D",
+        _ => "Unknown",
+    };
+    let (pdop, hdop, vdop) = parse_gsa_dops(&parts);
+
+    Some(GsaData {
+        message_id: parts[0].to_string(),
+        fix_type,
+        prn: parts[3].parse::<usize>().unwrap_or(0),
+        system: parts.get(18).and_then(|id| gsa_system_label(id)),
+        pdop,
+        hdop,
+        vdop,
+    })
+}
+
+/// Publishes GSA data to MQTT.
+///
+/// # Arguments
+///
+/// * `gsa` - The parsed GSA sentence.
+/// * `mqtt` - An MQTT client to publish the parsed data.
+/// * `config` - Configuration settings for the application.
+///
+/// The fix type is published under the PRN of the first satellite, and the PDOP, HDOP and VDOP
+/// values (fields 15, 16 and 17) are published when they are present.
+fn publish_gsa(gsa: &GsaData, mqtt: &dyn Publisher, config: &AppConfig) {
+    debug!(
+        "GSA Sentence - Message ID: {}, Fix Type: {}, PRN: {}, System: {:?}",
+        gsa.message_id, gsa.fix_type, gsa.prn, gsa.system
+    );
+
+    // Publish fix type to MQTT
+    let sat_topic = config.topic(&gsa_fix_type_field(gsa.prn, gsa.system));
+    if let Err(e) = publish_message(
+        mqtt,
+        &sat_topic,
+        gsa.fix_type,
+        config.mqtt_qos,
+        config.mqtt_retain,
+    ) {
+        error!("Error pushing fix type to MQTT: {:?}", e);
+    }
+
+    // Publish dilution of precision values, skipping any the receiver left empty
+    debug!(
+        "GSA DOP - PDOP: {:?}, HDOP: {:?}, VDOP: {:?}",
+        gsa.pdop, gsa.hdop, gsa.vdop
+    );
+
+    for (value, suffix) in [(gsa.pdop, "PDOP"), (gsa.hdop, "HDOP"), (gsa.vdop, "VDOP")] {
+        if let Some(value) = value {
+            if let Err(e) = publish_message(
+                mqtt,
+                &config.topic(&format!("SAT/GLOBAL/{}", suffix)),
+                &format!("{}", value),
+                config.mqtt_qos,
+                config.mqtt_retain,
+            ) {
+                error!("Error pushing {} to MQTT: {:?}", suffix, e);
+            }
+        }
+    }
+}
+
+/// Maps the NMEA 4.10 GSA system ID (field 18) to a constellation label.
+///
+/// Returns `None` for an empty or unknown ID.
+fn gsa_system_label(system_id: &str) -> Option<&'static str> {
+    let system = match system_id {
+        "1" => SatelliteType::GPS,
+        "2" => SatelliteType::GLONASS,
+        "3" => SatelliteType::Galileo,
+        "4" => SatelliteType::BeiDou,
+        "5" => SatelliteType::Qzss,
+        "6" => SatelliteType::NavIC,
+        _ => return None,
+    };
+    Some(system.as_str())
+}
+
+/// Builds the field name the GSA fix type is published to.
+///
+/// PRNs overlap between constellations in NMEA 4.10, so when the sentence carries a system ID
+/// the constellation is included as `SAT/VEHICLES/{system}/{prn}/FIX_TYPE`. Older sentences keep
+/// the `SAT/VEHICLES/{prn}/FIX_TYPE` topic.
+fn gsa_fix_type_field(prn: usize, system: Option<&str>) -> String {
+    match system {
+        Some(system) => format!("SAT/VEHICLES/{}/{}/FIX_TYPE", system, prn),
+        None => format!("SAT/VEHICLES/{}/FIX_TYPE", prn),
+    }
+}
+
+/// Extracts the PDOP, HDOP and VDOP values from the fields of a GSA sentence.
+///
+/// Each value is `None` when the field is missing, empty or not a number.
+fn parse_gsa_dops(parts: &[&str]) -> (Option<f64>, Option<f64>, Option<f64>) {
+    let dop = |index: usize| parts.get(index).and_then(|v| v.parse::<f64>().ok());
+    (dop(15), dop(16), dop(17))
+}
+
+/// Text message from a TXT (Text Transmission) sentence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TxtData {
+    pub text: String,
+}
+
+/// Parses the text field of a TXT sentence.
+///
+/// Returns `None` if the sentence has fewer than 4 fields.
+fn parse_txt(data: &str) -> Option<TxtData> {
+    let mut parts = data.splitn(4, ',');
+    if let (Some(_msg_id), Some(_msg_num), Some(_msg_total), Some(text)) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    {
+        let message = text.split_once(',').map_or(text, |(_, message)| message);
+        Some(TxtData {
+            text: message.to_string(),
+        })
+    } else {
+        None
+    }
+}
+
+/// Prints a GNTXT (Text Transmission) message and publishes the known status values.
+///
+/// # Arguments
+///
+/// * `txt` - The parsed TXT sentence.
+/// * `mqtt` - An MQTT client used to publish messages.
+/// * `config` - Configuration settings for the application.
+///
+/// If the message contains "ANTSTATUS=", it publishes the value after "=" to the MQTT topic.
+/// If the message contains "PF=", it publishes the value after "=" to the MQTT topic.
+/// If the message contains "GNSS OTP=", it publishes the value after "=" to the MQTT topic.
+fn publish_txt(txt: &TxtData, mqtt: &dyn Publisher, config: &AppConfig) {
+    let message = txt.text.as_str();
+
+    if message.contains("txbuf alloc") {
+        return;
+    }
+
+    debug!("GNTXT Text: {}", message);
+
+    let topics = [
+        ("ANTSTATUS=", "SAT/GLOBAL/ANTSTATUS"),
+        ("PF=", "SAT/GLOBAL/PF"),
+        ("GNSS OTP=", "SAT/GLOBAL/GNSS_OTP"),
+    ];
+
+    for (prefix, topic_suffix) in &topics {
+        if let Some(value) = message.strip_prefix(prefix) {
+            if let Err(e) = publish_message(
+                mqtt,
+                &config.topic(topic_suffix),
+                value,
+                config.mqtt_qos,
+                config.mqtt_retain,
+            ) {
+                error!(
+                    "Error pushing {} to MQTT: {:?}",
+                    prefix.trim_end_matches('='),
+                    e
+                );
+            }
+            break;
+        }
+    }
+}
+
+/// Publishes the antenna and jamming state of a UBX-MON-HW message.
+///
+/// The antenna status goes to `SAT/GLOBAL/ANTSTATUS`, the same topic as the GNTXT
+/// `ANTSTATUS=` message, the CW jamming indicator (0-255) to `SAT/GLOBAL/JAMMING` and the
+/// jamming state to `SAT/GLOBAL/JAMMING_STATE`.
+///
+/// # Arguments
+///
+/// * `mon_hw` - The parsed MON-HW message.
+/// * `mqtt` - An MQTT client used to publish messages.
+/// * `config` - Configuration settings for the application.
+fn publish_mon_hw(mon_hw: &MonHw, mqtt: &dyn Publisher, config: &AppConfig) {
+    debug!(
+        "MON-HW antenna: {}, jamming: {} ({})",
+        mon_hw.antenna_status, mon_hw.jamming_indicator, mon_hw.jamming_state
+    );
+
+    let messages = [
+        ("SAT/GLOBAL/ANTSTATUS", mon_hw.antenna_status.to_string()),
+        ("SAT/GLOBAL/JAMMING", mon_hw.jamming_indicator.to_string()),
+        ("SAT/GLOBAL/JAMMING_STATE", mon_hw.jamming_state.to_string()),
+    ];
+
+    for (suffix, value) in &messages {
+        if let Err(e) = publish_message(
+            mqtt,
+            &config.topic(suffix),
+            value,
+            config.mqtt_qos,
+            config.mqtt_retain,
+        ) {
+            error!("Error pushing {} to MQTT: {:?}", suffix, e);
+        }
+    }
+}
+
+/// Position and time from a GLL (Geographic Position - Latitude/Longitude) sentence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GllData {
+    /// Latitude in decimal degrees
+    pub latitude: f64,
+    /// Longitude in decimal degrees
+    pub longitude: f64,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    /// Status field, `A` (valid) or `V` (void)
+    pub status: String,
+    /// Mode indicator of NMEA 2.3 and later, e.g. `A` (autonomous) or `N` (no fix)
+    pub mode: Option<char>,
+    /// Whether the status is `A` and the mode indicator, when present, isn't `N` (no fix)
+    pub valid: bool,
+}
+
+/// Parses the fields of a GLL sentence.
+///
+/// Returns `None` if the sentence has fewer than 7 fields.
+fn parse_gll(data: &str) -> Option<GllData> {
+    let parts: Vec<&str> = data.split(',').collect();
+    if parts.len() < 7 {
+        return None;
+    }
+
+    let (hour, minute, second, _) = parse_utc_time(parts[5]);
+    let mode = parts.get(7).and_then(|mode| mode.chars().next());
+
+    Some(GllData {
+        latitude: parse_latitude(parts[1], parts[2]),
+        longitude: parse_longitude(parts[3], parts[4]),
+        hour,
+        minute,
+        second,
+        status: parts[6].to_string(),
+        mode,
+        valid: parts[6] == "A" && mode != Some('N'),
+    })
+}
+
+/// Publishes GLL data to MQTT.
+///
+/// # Arguments
+///
+/// * `gll` - The parsed GLL sentence.
+/// * `mqtt` - An MQTT client to publish the parsed data.
+/// * `config` - Configuration settings for the application.
+///
+/// The latitude, longitude and UTC time are published to `GLL_LAT`, `GLL_LNG` and `GLL_TME`, the
+/// status to `GLL_STATUS` and the decoded mode indicator, when present, to `GLL_MODE`. For a void
+/// (`V`) status or the `N` (no fix) mode only the time, status and mode are published, unless
+/// `publish_invalid_fixes` is enabled.
+fn publish_gll(gll: &GllData, mqtt: &dyn Publisher, config: &AppConfig) {
+    let current_time = format!("{:02}:{:02}:{:02}", gll.hour, gll.minute, gll.second);
+
+    debug!(
+        "GLL Latitude: {}, GLL Longitude: {}, GLL UTC Time: {}",
+        gll.latitude, gll.longitude, current_time
+    );
+
+    // Helper function to publish messages to MQTT, at most every `min_interval`
+    fn publish_gll_message(
+        mqtt: &dyn Publisher,
+        topic_suffix: &str,
+        message: &str,
+        config: &AppConfig,
+        min_interval: Duration,
+    ) {
+        if let Err(e) = publish_rate_limited(
+            mqtt,
+            &config.topic(topic_suffix),
+            message,
+            config.mqtt_qos,
+            config.mqtt_retain,
+            min_interval,
+        ) {
+            error!("Error pushing GLL {} to MQTT: {:?}", topic_suffix, e);
+        }
+    }
+
+    // Push GLL data to MQTT
+    publish_gll_message(mqtt, "GLL_TME", &current_time, config, Duration::ZERO);
+    if !gll.status.is_empty() {
+        publish_gll_message(mqtt, "GLL_STATUS", &gll.status, config, Duration::ZERO);
+    }
+    if let Some(mode) = gll.mode {
+        publish_gll_message(
+            mqtt,
+            "GLL_MODE",
+            decode_mode_indicator(mode),
+            config,
+            Duration::ZERO,
+        );
+    }
+    if !gll.valid && !config.publish_invalid_fixes {
+        return;
+    }
+    for (suffix, value, is_lat) in [
+        ("GLL_LAT", gll.latitude, true),
+        ("GLL_LNG", gll.longitude, false),
+    ] {
+        publish_gll_message(
+            mqtt,
+            suffix,
+            &format_position(
+                value,
+                is_lat,
+                config.coordinate_format,
+                config.coordinate_decimals,
+            ),
+            config,
+            position_interval(config),
+        );
+    }
+}
+
+/// Position error estimates from a GST sentence, in meters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GstErrors {
+    pub rms: Option<f64>,
+    pub lat_std: Option<f64>,
+    pub lon_std: Option<f64>,
+    pub alt_std: Option<f64>,
+}
+
+/// Parses the fields of a GST sentence.
+///
+/// Returns `None` if the sentence has fewer than 9 fields.
+fn parse_gst(data: &str) -> Option<GstErrors> {
+    let parts: Vec<&str> = data.split(',').collect();
+    if parts.len() < 9 {
+        return None;
+    }
+
+    Some(parse_gst_errors(&parts))
+}
+
+/// Extracts the RMS and the latitude, longitude and altitude standard deviations from GST fields.
+///
+/// Empty or invalid fields are `None`, so a receiver leaving the RMS blank doesn't hide the
+/// standard deviations.
+fn parse_gst_errors(parts: &[&str]) -> GstErrors {
+    let value = |index: usize| parts.get(index).and_then(|v| v.parse::<f64>().ok());
+    GstErrors {
+        rms: value(2),
+        lat_std: value(6),
+        lon_std: value(7),
+        alt_std: value(8),
+    }
+}
+
+/// Publishes GST (GNSS Pseudorange Error Statistics) data to MQTT.
+///
+/// # Arguments
+///
+/// * `errors` - The parsed GST sentence.
+/// * `mqtt` - An MQTT client to publish the parsed data.
+/// * `config` - Configuration settings for the application.
+///
+/// The standard deviations of the latitude, longitude and altitude errors are published to
+/// `ERR/LAT_STD`, `ERR/LON_STD` and `ERR/ALT_STD`, and the pseudorange RMS to `ERR/RMS`.
+/// Fields the receiver leaves empty are skipped.
+fn publish_gst(errors: &GstErrors, mqtt: &dyn Publisher, config: &AppConfig) {
+    debug!(
+        "GST Errors - RMS: {:?}, Lat Std: {:?}, Lon Std: {:?}, Alt Std: {:?}",
+        errors.rms, errors.lat_std, errors.lon_std, errors.alt_std
+    );
+
+    let messages = [
+        (errors.rms, "ERR/RMS"),
+        (errors.lat_std, "ERR/LAT_STD"),
+        (errors.lon_std, "ERR/LON_STD"),
+        (errors.alt_std, "ERR/ALT_STD"),
+    ];
+
+    for (value, suffix) in &messages {
+        if let Some(value) = value {
+            if let Err(e) = publish_message(
+                mqtt,
+                &config.topic(suffix),
+                &format!("{}", value),
+                config.mqtt_qos,
+                config.mqtt_retain,
+            ) {
+                error!("Error pushing {} to MQTT: {:?}", suffix, e);
+            }
+        }
+    }
+}
+
+/// Receiver autonomous integrity monitoring results from a GBS (GNSS Satellite Fault
+/// Detection) sentence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GbsData {
+    /// Expected latitude error in meters
+    pub lat_err: Option<f64>,
+    /// Expected longitude error in meters
+    pub lon_err: Option<f64>,
+    /// Expected altitude error in meters
+    pub alt_err: Option<f64>,
+    /// PRN of the most likely failed satellite, `None` when no fault is detected
+    pub failed_prn: Option<usize>,
+    /// Probability of missed detection for the failed satellite
+    pub failed_probability: Option<f64>,
+    /// Estimated range bias of the failed satellite in meters
+    pub bias: Option<f64>,
+    /// Standard deviation of the bias estimate in meters
+    pub bias_std: Option<f64>,
+}
+
+/// Parses the fields of a GBS sentence.
+///
+/// Receivers leave most fields empty when no fault is detected, so every value is optional.
+/// Returns `None` if the sentence has fewer than 9 fields.
+fn parse_gbs(data: &str) -> Option<GbsData> {
+    let parts: Vec<&str> = data.split(',').collect();
+    if parts.len() < 9 {
+        return None;
+    }
+
+    let value = |index: usize| parts[index].parse::<f64>().ok();
+    Some(GbsData {
+        lat_err: value(2),
+        lon_err: value(3),
+        alt_err: value(4),
+        failed_prn: parts[5].parse::<usize>().ok(),
+        failed_probability: value(6),
+        bias: value(7),
+        bias_std: value(8),
+    })
+}
+
+/// Publishes GBS (GNSS Satellite Fault Detection) data to MQTT.
+///
+/// # Arguments
+///
+/// * `gbs` - The parsed GBS sentence.
+/// * `mqtt` - An MQTT client to publish the parsed data.
+/// * `config` - Configuration settings for the application.
+///
+/// The expected errors are published to `INTEGRITY/LAT_ERR`, `INTEGRITY/LON_ERR` and
+/// `INTEGRITY/ALT_ERR`. `INTEGRITY/FAILED_PRN` carries the PRN of a failed satellite, or `none`
+/// so a retained fault is cleared, and its probability, bias and bias standard deviation go
+/// to `INTEGRITY/FAILED_PROBABILITY`, `INTEGRITY/BIAS` and `INTEGRITY/BIAS_STD`. Fields the
+/// receiver leaves empty are skipped.
+fn publish_gbs(gbs: &GbsData, mqtt: &dyn Publisher, config: &AppConfig) {
+    debug!(
+        "GBS Integrity - Lat Err: {:?}, Lon Err: {:?}, Alt Err: {:?}, Failed PRN: {:?}",
+        gbs.lat_err, gbs.lon_err, gbs.alt_err, gbs.failed_prn
+    );
+
+    let mut messages: Vec<(&str, String)> = [
+        ("INTEGRITY/LAT_ERR", gbs.lat_err),
+        ("INTEGRITY/LON_ERR", gbs.lon_err),
+        ("INTEGRITY/ALT_ERR", gbs.alt_err),
+    ]
+    .iter()
+    .filter_map(|(suffix, value)| value.map(|value| (*suffix, value.to_string())))
+    .collect();
+
+    match gbs.failed_prn {
+        Some(prn) => {
+            messages.push(("INTEGRITY/FAILED_PRN", prn.to_string()));
+            let details = [
+                ("INTEGRITY/FAILED_PROBABILITY", gbs.failed_probability),
+                ("INTEGRITY/BIAS", gbs.bias),
+                ("INTEGRITY/BIAS_STD", gbs.bias_std),
+            ];
+            for (suffix, value) in details {
+                if let Some(value) = value {
+                    messages.push((suffix, value.to_string()));
+                }
+            }
+        }
+        None => messages.push(("INTEGRITY/FAILED_PRN", "none".to_string())),
+    }
+
+    for (suffix, value) in &messages {
+        if let Err(e) = publish_message(
+            mqtt,
+            &config.topic(suffix),
+            value,
+            config.mqtt_qos,
+            config.mqtt_retain,
+        ) {
+            error!("Error pushing {} to MQTT: {:?}", suffix, e);
+        }
+    }
+}
+
+/// Time, date and local zone from a ZDA (Time and Date) sentence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZdaData {
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    pub day: u32,
+    pub month: u32,
+    /// Four-digit year
+    pub year: u32,
+    /// Local zone offset as `(hours, minutes)`, when the receiver provides it
+    pub zone: Option<(i32, u32)>,
+}
+
+/// Parses the fields of a ZDA sentence.
+///
+/// Returns `None` if the sentence has fewer than 5 fields or the date is invalid.
+fn parse_zda(data: &str) -> Option<ZdaData> {
+    let parts: Vec<&str> = data.split(',').collect();
+    if parts.len() < 5 {
+        return None;
+    }
+
+    let (hour, minute, second, _) = parse_utc_time(parts[1]);
+    let (day, month, year) = parse_zda_date(parts[2], parts[3], parts[4])?;
+
+    // The local zone fields are optional and usually left empty by receivers
+    let zone = match (parts.get(5), parts.get(6)) {
+        (Some(zone_hours), Some(zone_minutes)) => zone_hours
+            .parse::<i32>()
+            .ok()
+            .zip(zone_minutes.parse::<u32>().ok()),
+        _ => None,
+    };
+
+    Some(ZdaData {
+        hour,
+        minute,
+        second,
+        day,
+        month,
+        year,
+        zone,
+    })
+}
+
+/// Publishes ZDA data to MQTT.
+///
+/// # Arguments
+///
+/// * `zda` - The parsed ZDA sentence.
+/// * `mqtt` - An MQTT client to publish the parsed data.
+/// * `config` - Configuration settings for the application.
+///
+/// ZDA carries the UTC time together with the day, month and full four-digit year, so it is a
+/// more reliable date source than RMC. The date, time and local zone offset are published to
+/// `ZDA_DTE`, `ZDA_TME` and `ZDA_TZ`.
+fn publish_zda(zda: &ZdaData, mqtt: &dyn Publisher, config: &AppConfig) {
+    let current_time = format!("{:02}:{:02}:{:02}", zda.hour, zda.minute, zda.second);
+    let current_date = format!("{:02}.{:02}.{:04}", zda.day, zda.month, zda.year);
+
+    debug!("ZDA Date: {}, ZDA UTC Time: {}", current_date, current_time);
+
+    let mut messages = vec![("ZDA_TME", current_time), ("ZDA_DTE", current_date)];
+
+    if let Some((zone_hours, zone_minutes)) = zda.zone {
+        let sign = if zone_hours < 0 { '-' } else { '+' };
+        messages.push((
+            "ZDA_TZ",
+            format!("{}{:02}:{:02}", sign, zone_hours.abs(), zone_minutes),
+        ));
+    }
+
+    for (suffix, value) in &messages {
+        if let Err(e) = publish_message(
+            mqtt,
+            &config.topic(suffix),
+            value,
+            config.mqtt_qos,
+            config.mqtt_retain,
+        ) {
+            error!("Error pushing {} to MQTT: {:?}", suffix, e);
+        }
+    }
+}
+
+/// DTM datum code of WGS84, the datum every published coordinate is assumed to be in.
+const WGS84_DATUM: &str = "W84";
+
+/// Local datum from a DTM (Datum Reference) sentence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DtmData {
+    /// Local datum code, e.g. `W84`, `W72`, `S85`, `P90` or `999` for user defined
+    pub datum: String,
+    /// Local datum subdivision code, often empty
+    pub sub_datum: String,
+    /// Reference datum code the offsets are relative to
+    pub reference_datum: String,
+}
+
+/// Parses the fields of a DTM sentence.
+///
+/// Returns `None` if the sentence has fewer than 9 fields or no datum code.
+fn parse_dtm(data: &str) -> Option<DtmData> {
+    let parts: Vec<&str> = data.split(',').collect();
+    if parts.len() < 9 || parts[1].is_empty() {
+        return None;
+    }
+
+    Some(DtmData {
+        datum: parts[1].to_string(),
+        sub_datum: parts[2].to_string(),
+        reference_datum: parts[8].to_string(),
+    })
+}
+
+/// Publishes the local datum code of a DTM sentence to `DATUM`.
+///
+/// Coordinates in the other sentences are referenced to this datum, and are published without
+/// any transformation. A warning is logged whenever the receiver switches to a datum other than
+/// WGS84, so users know the coordinates may need converting.
+///
+/// # Arguments
+///
+/// * `dtm` - The parsed DTM sentence.
+/// * `mqtt` - An MQTT client to publish the parsed data.
+/// * `config` - Configuration settings for the application.
+fn publish_dtm(dtm: &DtmData, mqtt: &dyn Publisher, config: &AppConfig) {
+    debug!(
+        "Datum: {} {} (reference {})",
+        dtm.datum, dtm.sub_datum, dtm.reference_datum
+    );
+
+    let changed = with_device(&ACTIVE_DATUM, config, |active| {
+        datum_changed(active, &dtm.datum)
+    });
+    if changed && dtm.datum != WGS84_DATUM {
+        warn!(
+            "Receiver reports non-WGS84 datum {}; published coordinates are not transformed",
+            dtm.datum
+        );
+    }
+
+    if let Err(e) = publish_if_changed(
+        mqtt,
+        &config.topic("DATUM"),
+        &dtm.datum,
+        config.mqtt_qos,
+        config.mqtt_retain,
+    ) {
+        error!("Error pushing datum to MQTT: {:?}", e);
+    }
+}
+
+/// Records `datum` as the active datum and returns whether it differs from the previous one.
+fn datum_changed(active: &mut Option<String>, datum: &str) -> bool {
+    if active.as_deref() == Some(datum) {
+        return false;
+    }
+    *active = Some(datum.to_string());
+    true
+}
+
+/// Constellations in the order of the GNS mode indicator characters.
+const GNS_CONSTELLATIONS: [SatelliteType; 6] = [
+    SatelliteType::GPS,
+    SatelliteType::GLONASS,
+    SatelliteType::Galileo,
+    SatelliteType::BeiDou,
+    SatelliteType::Qzss,
+    SatelliteType::NavIC,
+];
+
+/// Position and per-constellation mode from a GNS (GNSS Fix Data) sentence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GnsData {
+    /// Latitude in decimal degrees
+    pub latitude: f64,
+    /// Longitude in decimal degrees
+    pub longitude: f64,
+    /// Altitude above mean sea level in meters, when present
+    pub altitude: Option<f64>,
+    /// Mode indicator, one character per constellation
+    pub mode: String,
+}
+
+/// Parses the fields of a GNS sentence.
+///
+/// Returns `None` if the sentence has fewer than 10 fields.
+fn parse_gns(data: &str) -> Option<GnsData> {
+    let parts: Vec<&str> = data.split(',').collect();
+    if parts.len() < 10 {
+        return None;
+    }
+
+    Some(GnsData {
+        latitude: parse_latitude(parts[2], parts[3]),
+        longitude: parse_longitude(parts[4], parts[5]),
+        altitude: parts[9].parse::<f64>().ok(),
+        mode: parts[6].to_string(),
+    })
+}
+
+/// Publishes GNS data to MQTT.
+///
+/// # Arguments
+///
+/// * `gns` - The parsed GNS sentence.
+/// * `mqtt` - An MQTT client to publish the parsed data.
+/// * `config` - Configuration settings for the application.
+///
+/// The latitude, longitude and altitude are published to `GNS_LAT`, `GNS_LNG` and `GNS_ALT`, the
+/// raw mode indicator (one character per constellation) to `GNS_MODE`, and the decoded status of
+/// each constellation to `GNS_STATUS/{constellation}`. When no constellation has a fix (mode `N`
+/// for all of them, or an empty mode) the position is skipped, unless `publish_invalid_fixes` is
+/// enabled.
+fn publish_gns(gns: &GnsData, mqtt: &dyn Publisher, config: &AppConfig) {
+    debug!(
+        "GNS Latitude: {}, Longitude: {}, Mode: {}",
+        gns.latitude, gns.longitude, gns.mode
+    );
+
+    let mut messages = Vec::new();
+
+    // Without a fix the coordinate fields are empty and would be published as 0°, 0°
+    if gns.mode.chars().any(|mode| mode != 'N') || config.publish_invalid_fixes {
+        messages.push((
+            "GNS_LAT".to_string(),
+            format_position(
+                gns.latitude,
+                true,
+                config.coordinate_format,
+                config.coordinate_decimals,
+            ),
+        ));
+        messages.push((
+            "GNS_LNG".to_string(),
+            format_position(
+                gns.longitude,
+                false,
+                config.coordinate_format,
+                config.coordinate_decimals,
+            ),
+        ));
+        if let Some(altitude) = gns.altitude {
+            messages.push((
+                "GNS_ALT".to_string(),
+                format_decimals(altitude, config.altitude_decimals),
+            ));
+        }
+    }
+
+    if !gns.mode.is_empty() {
+        messages.push(("GNS_MODE".to_string(), gns.mode.clone()));
+    }
+
+    for (constellation, status) in decode_gns_modes(&gns.mode) {
+        messages.push((format!("GNS_STATUS/{}", constellation), status.to_string()));
+    }
+
+    for (suffix, value) in &messages {
+        if let Err(e) = publish_message(
+            mqtt,
+            &config.topic(suffix),
+            value,
+            config.mqtt_qos,
+            config.mqtt_retain,
+        ) {
+            error!("Error pushing {} to MQTT: {:?}", suffix, e);
+        }
+    }
+}
+
+/// Decodes a GNS mode indicator into a status per constellation.
+///
+/// # Arguments
+///
+/// * `mode` - The mode indicator field, e.g. `"AAN"` (GPS and GLONASS autonomous, Galileo no fix).
+///
+/// Characters beyond the known constellations are ignored.
+fn decode_gns_modes(mode: &str) -> Vec<(&'static str, &'static str)> {
+    GNS_CONSTELLATIONS
+        .iter()
+        .zip(mode.chars())
+        .map(|(constellation, indicator)| {
+            (constellation.as_str(), decode_mode_indicator(indicator))
+        })
+        .collect()
+}
+
+/// Decodes one mode indicator character, as used by RMC, GLL, VTG and GNS, into a readable mode.
+fn decode_mode_indicator(indicator: char) -> &'static str {
+    match indicator {
+        'N' => "No fix",
+        'A' => "Autonomous",
+        'D' => "Differential",
+        'P' => "Precise",
+        'R' => "RTK",
+        'F' => "Float RTK",
+        'E' => "Estimated",
+        'M' => "Manual",
+        'S' => "Simulator",
+        _ => "Unknown",
+    }
+}
+
+/// Parses latitude or longitude from NMEA format and converts it to decimal degrees.
+///
+/// # Arguments
+///
+/// * `value` - A string slice that holds the coordinate in NMEA format.
+/// * `direction` - A string slice that holds the direction ('N', 'S', 'E', or 'W').
+/// * `degree_len` - The length of the degree part in the NMEA format (2 for latitude, 3 for longitude).
+/// * `max_degrees` - The largest valid magnitude (90 for latitude, 180 for longitude).
+///
+/// The function extracts degrees and minutes from the NMEA format, converts them to decimal degrees,
+/// and adjusts the sign based on the direction. Negative degrees, minutes outside `[0, 60)` and
+/// results beyond `max_degrees` are rejected.
+fn parse_coordinate(value: &str, direction: &str, degree_len: usize, max_degrees: f64) -> f64 {
+    if value.is_empty() || direction.is_empty() {
+        warn!("Invalid coordinate input: {}{}", value, direction);
+        return 0.0;
+    }
+
+    if value.len() <= degree_len {
+        warn!("Invalid coordinate input: {}{}", value, direction);
+        return 0.0;
+    }
+
+    if !matches!(direction, "N" | "S" | "E" | "W") {
+        warn!("Invalid direction: {}", direction);
+        return 0.0;
+    }
+
+    // A corrupt byte decoded as a multi-byte U+FFFD can straddle the degree/minute split
+    let (Some(degrees), Some(minutes)) = (value.get(..degree_len), value.get(degree_len..)) else {
+        warn!("Invalid coordinate input: {}{}", value, direction);
+        return 0.0;
+    };
+
+    // Parse degrees and minutes
+    match (degrees.parse::<f64>(), minutes.parse::<f64>()) {
+        (Ok(degrees), Ok(minutes))
+            if degrees.is_sign_negative() || !(0.0..60.0).contains(&minutes) =>
+        {
+            warn!("Invalid coordinate input: {}{}", value, direction);
+            0.0
+        }
+        (Ok(degrees), Ok(minutes)) if degrees + minutes / 60.0 > max_degrees => {
+            warn!("Coordinate out of range: {}{}", value, direction);
+            0.0
+        }
+        (Ok(degrees), Ok(minutes)) => {
+            let result = degrees + minutes / 60.0;
+            match direction {
+                "S" | "W" => -result,
+                _ => result,
+            }
+        }
+        _ => {
+            error!("Failed to parse coordinate: {}{}", value, direction);
+            0.0
+        }
+    }
+}
+
+/// Formats decimal degrees as an NMEA coordinate and hemisphere, e.g. `4916.4500,N`.
+///
+/// The inverse of `parse_coordinate`, with the minutes rounded to 4 decimal places.
+///
+/// # Arguments
+///
+/// * `decimal` - The coordinate in decimal degrees, negative for south or west.
+/// * `is_lat` - Whether the coordinate is a latitude (2 degree digits) or a longitude (3).
+pub fn format_coordinate(decimal: f64, is_lat: bool) -> String {
+    let (degree_len, direction) = match (is_lat, decimal < 0.0) {
+        (true, false) => (2, "N"),
+        (true, true) => (2, "S"),
+        (false, false) => (3, "E"),
+        (false, true) => (3, "W"),
+    };
+
+    // Round in whole units of 0.0001' so 59.99996' carries into the degrees instead of
+    // producing 60.0000'
+    let total = (decimal.abs() * 60.0 * 10_000.0).round() as u64;
+    let degrees = total / 600_000;
+    let minutes = (total % 600_000) as f64 / 10_000.0;
+    format!(
+        "{:0width$}{:07.4},{}",
+        degrees,
+        minutes,
+        direction,
+        width = degree_len
+    )
+}
+
+/// Formats decimal degrees for the position topics in the configured `coordinate_format`.
+///
+/// `Ddm` gives e.g. `49°16.450'N` and `Dms` `49°16'27.0"N`, with the hemisphere instead of the
+/// sign. `Decimal` keeps the signed decimal degrees, rounded to `decimals` places.
+///
+/// # Arguments
+///
+/// * `decimal` - The coordinate in decimal degrees, negative for south or west.
+/// * `is_lat` - Whether the coordinate is a latitude (`N`/`S`) or a longitude (`E`/`W`).
+/// * `format` - The configured format.
+/// * `decimals` - The configured `coordinate_decimals`, used by `Decimal`.
+fn format_position(
+    decimal: f64,
+    is_lat: bool,
+    format: CoordinateFormat,
+    decimals: usize,
+) -> String {
+    let direction = match (is_lat, decimal < 0.0) {
+        (true, false) => 'N',
+        (true, true) => 'S',
+        (false, false) => 'E',
+        (false, true) => 'W',
+    };
+
+    // Round in whole units of the last digit, so 59.9999' carries into the degrees
+    match format {
+        CoordinateFormat::Decimal => format_decimals(decimal, decimals),
+        CoordinateFormat::Ddm => {
+            let total = (decimal.abs() * 60_000.0).round() as u64;
+            format!(
+                "{}°{:06.3}'{}",
+                total / 60_000,
+                (total % 60_000) as f64 / 1000.0,
+                direction
+            )
+        }
+        CoordinateFormat::Dms => {
+            let total = (decimal.abs() * 36_000.0).round() as u64;
+            format!(
+                "{}°{:02}'{:04.1}\"{}",
+                total / 36_000,
+                total % 36_000 / 600,
+                (total % 600) as f64 / 10.0,
+                direction
+            )
+        }
+    }
+}
+
+/// Formats a value rounded to at most `decimals` places for publishing.
+///
+/// Trailing zeros are dropped, so 22.4 stays `22.4` rather than `22.40`, and a value rounding to
+/// zero is `0` rather than `-0`.
+fn format_decimals(value: f64, decimals: usize) -> String {
+    let formatted = format!("{:.*}", decimals, value);
+    let trimmed = if formatted.contains('.') {
+        formatted.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        formatted.as_str()
+    };
+
+    if trimmed == "-0" {
+        "0".to_string()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+/// Parses latitude from NMEA format and converts it to decimal degrees.
+fn parse_latitude(value: &str, direction: &str) -> f64 {
+    parse_coordinate(value, direction, 2, 90.0)
+}
+
+/// Parses longitude from NMEA format and converts it to decimal degrees.
+fn parse_longitude(value: &str, direction: &str) -> f64 {
+    parse_coordinate(value, direction, 3, 180.0)
+}
+
+/// Maps the GGA fix quality indicator (field 6) to a human readable label.
+///
+/// # Arguments
+///
+/// * `quality` - The fix quality indicator (0-8).
+///
+/// Values outside the NMEA-defined range are labelled `Unknown`.
+fn fix_quality_label(quality: usize) -> &'static str {
+    match quality {
+        0 => "Invalid",
+        1 => "GPS",
+        2 => "DGPS",
+        3 => "PPS",
+        4 => "RTK",
+        5 => "Float RTK",
+        6 => "Estimated",
+        7 => "Manual",
+        8 => "Simulation",
+        _ => "Unknown",
+    }
+}
+
+/// Returns the topic the RMC date is published to.
+///
+/// # Arguments
+///
+/// * `config` - Configuration settings providing the base topic.
+fn date_topic(config: &AppConfig) -> String {
+    config.topic("DTE")
+}
+
+/// Parses the RMC magnetic variation into signed decimal degrees.
+///
+/// # Arguments
+///
+/// * `value` - The variation magnitude in degrees (e.g. "003.1").
+/// * `direction` - The direction of the variation ('E' or 'W').
+///
+/// West variation is negative, matching the coordinate sign convention. Returns `None` when
+/// either field is empty or invalid, which is common for receivers without a magnetic model.
+fn parse_magnetic_variation(value: &str, direction: &str) -> Option<f64> {
+    let magnitude = value.parse::<f64>().ok()?;
+    match direction {
+        "E" => Some(magnitude),
+        "W" => Some(-magnitude),
+        _ => None,
+    }
+}
+
+/// Parses UTC time from NMEA HHMMSS.ss format into hour, minute, second and millisecond components.
+///
+/// # Arguments
+///
+/// * `utc_time` - A string slice in HHMMSS format (e.g., "235959" for 23:59:59)
+///                Optionally may contain decimal seconds after period
+///
+/// # Returns
+///
+/// A tuple of `(hour, minute, second, millisecond)` where:
+/// * `hour` - Hours in 24-hour format (0-23)
+/// * `minute` - Minutes (0-59)  
+/// * `second` - Seconds (0-59)
+/// * `millisecond` - Fractional seconds in milliseconds (0-999), 0 when absent
+///
+/// Returns `(0, 0, 0, 0)` if:
+/// * Input string is less than 6 characters
+/// * Any time component is out of valid range
+/// * Any component fails to parse as a number
+///
+/// Fractional digits beyond milliseconds are truncated; an unparsable fraction is treated as 0.
+fn parse_utc_time(utc_time: &str) -> (u32, u32, u32, u32) {
+    if utc_time.len() < 6 {
+        return (0, 0, 0, 0);
+    }
+
+    // `get` instead of slicing, as corrupt bytes decode to multi-byte U+FFFD characters
+    let component = |range| {
+        utc_time
+            .get(range)
+            .and_then(|digits: &str| digits.parse::<u32>().ok())
+    };
+    let (Some(hour), Some(minute), Some(second)) =
+        (component(0..2), component(2..4), component(4..6))
+    else {
+        return (0, 0, 0, 0);
+    };
+
+    if hour > 23 || minute > 59 || second > 59 {
+        return (0, 0, 0, 0);
+    }
+
+    let millisecond = utc_time
+        .get(6..)
+        .and_then(|rest| rest.strip_prefix('.'))
+        .filter(|fraction| !fraction.is_empty() && fraction.bytes().all(|b| b.is_ascii_digit()))
+        .map(|fraction| {
+            // Scale to exactly three digits: ".5" is 500ms, ".123456" is 123ms
+            format!("{:0<3}", &fraction[..fraction.len().min(3)])
+                .parse::<u32>()
+                .unwrap_or(0)
+        })
+        .unwrap_or(0);
+
+    (hour, minute, second, millisecond)
+}
+
+/// Parses a date string in DDMMYY format and returns the components as integers.
+///
+/// # Arguments
+///
+/// * `date` - A string slice in DDMMYY format (e.g., "230394" for March 23, 1994)
 ///
 /// # Returns
 ///
-/// A tuple of `(hour, minute, second)` where:
-/// * `hour` - Hours in 24-hour format (0-23)
-/// * `minute` - Minutes (0-59)  
-/// * `second` - Seconds (0-59)
+/// A tuple of `(day, month, year)` where:
+/// * `day` - Day of month (1-31)
+/// * `month` - Month number (1-12)
+/// * `year` - Two-digit year (0-99)
 ///
 /// Returns `(0, 0, 0)` if:
-/// * Input string is less than 6 characters
-/// * Any time component is out of valid range
+/// * Input string is not exactly 6 characters
+/// * Day is 0 or > 31
+/// * Month is 0 or > 12
 /// * Any component fails to parse as a number
 ///
-fn parse_utc_time(utc_time: &str) -> (u32, u32, u32) {
-    if utc_time.len() < 6 {
+fn parse_date(date: &str) -> (u32, u32, u32) {
+    if date.len() != 6 {
+        return (0, 0, 0);
+    }
+
+    let component = |range| {
+        date.get(range)
+            .and_then(|digits: &str| digits.parse::<u32>().ok())
+            .unwrap_or(0)
+    };
+    let day = component(0..2);
+    let month = component(2..4);
+    let year = component(4..6);
+
+    if day == 0 || day > 31 || month == 0 || month > 12 {
         return (0, 0, 0);
     }
 
-    let hour = utc_time[0..2].parse::<u32>().unwrap_or(0);
-    let minute = utc_time[2..4].parse::<u32>().unwrap_or(0);
-    let second = utc_time[4..6].parse::<u32>().unwrap_or(0);
+    (day, month, year)
+}
+
+/// Expands a two-digit RMC year to four digits: years below `pivot` are in the 2000s, the others
+/// in the 1900s. With the default `year_pivot` of 80, 94 becomes 1994 and 24 becomes 2024.
+fn expand_year(year: u32, pivot: u32) -> u32 {
+    if year < pivot {
+        2000 + year
+    } else {
+        1900 + year
+    }
+}
+
+/// Formats the date and time of an RMC sentence as an RFC 3339 UTC timestamp, e.g.
+/// `2024-03-23T12:35:19Z`. Milliseconds are added when the receiver reports them.
+///
+/// The year is expanded with `expand_year` around `pivot`. Returns `None` when the date field is
+/// empty or invalid, as it is before the receiver has synchronized its clock.
+fn rfc3339_timestamp(rmc: &RmcData, pivot: u32) -> Option<String> {
+    if rmc.day == 0 {
+        return None;
+    }
+
+    let mut timestamp = format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        expand_year(rmc.year, pivot),
+        rmc.month,
+        rmc.day,
+        rmc.hour,
+        rmc.minute,
+        rmc.second
+    );
+    if rmc.millisecond > 0 {
+        timestamp.push_str(&format!(".{:03}", rmc.millisecond));
+    }
+    timestamp.push('Z');
+    Some(timestamp)
+}
+
+/// Parses the separate day, month and four-digit year fields of a ZDA sentence.
+///
+/// # Returns
+///
+/// `Some((day, month, year))` if all three fields parse and the day and month are in range,
+/// `None` otherwise.
+fn parse_zda_date(day: &str, month: &str, year: &str) -> Option<(u32, u32, u32)> {
+    let day = day.parse::<u32>().ok()?;
+    let month = month.parse::<u32>().ok()?;
+    let year = year.parse::<u32>().ok()?;
+
+    if day == 0 || day > 31 || month == 0 || month > 12 || year < 1000 {
+        return None;
+    }
+
+    Some((day, month, year))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::mqtt_handler::RecordingPublisher;
+
+    fn get_test_config() -> AppConfig {
+        AppConfig::builder()
+            .port_name("/dev/ttyACM0")
+            .mqtt_host("localhost")
+            .mqtt_base_topic("/GOLF86/GPS/")
+            .device_id("golf86-pi")
+            .build()
+    }
+
+    /// Parses a sentence, publishes it and returns the recorded `(topic, payload)` pairs.
+    fn parse_and_publish(data: &str) -> Vec<(String, String)> {
+        let mqtt = RecordingPublisher::default();
+        if let Some(parsed) = parse_nmea_sentence(data) {
+            publish_parsed(&parsed, &mqtt, &get_test_config());
+        }
+        mqtt.messages()
+    }
+
+    /// Returns the payload published to `suffix` under the test base topic, if any.
+    fn payload_of<'a>(messages: &'a [(String, String)], suffix: &str) -> Option<&'a str> {
+        let topic = format!("/GOLF86/GPS/{}", suffix);
+        messages
+            .iter()
+            .find(|(t, _)| *t == topic)
+            .map(|(_, payload)| payload.as_str())
+    }
+
+    #[test]
+    fn test_parse_latitude() {
+        assert_eq!(parse_latitude("4916.45", "N"), 49.274166666666666);
+        assert_eq!(parse_latitude("4916.45", "S"), -49.274166666666666);
+        assert_eq!(parse_latitude("0000.00", "N"), 0.0);
+        assert_eq!(parse_latitude("0000.00", "S"), -0.0);
+    }
+
+    #[test]
+    fn test_parse_longitude() {
+        assert_eq!(parse_longitude("12311.12", "E"), 123.18533333333333);
+        assert_eq!(parse_longitude("12311.12", "W"), -123.18533333333333);
+        assert_eq!(parse_longitude("00000.00", "E"), 0.0);
+        assert_eq!(parse_longitude("00000.00", "W"), -0.0);
+    }
+
+    #[test]
+    fn test_parse_utc_time() {
+        assert_eq!(parse_utc_time("123519"), (12, 35, 19, 0));
+        assert_eq!(parse_utc_time("000000"), (0, 0, 0, 0));
+        assert_eq!(parse_utc_time("235959"), (23, 59, 59, 0));
+    }
+
+    #[test]
+    fn test_parse_utc_time_fractional_seconds() {
+        assert_eq!(parse_utc_time("123519.00"), (12, 35, 19, 0));
+        assert_eq!(parse_utc_time("123519.10"), (12, 35, 19, 100));
+        assert_eq!(parse_utc_time("123519.5"), (12, 35, 19, 500));
+        assert_eq!(parse_utc_time("123519.123456"), (12, 35, 19, 123));
+        assert_eq!(parse_utc_time("123519."), (12, 35, 19, 0));
+        assert_eq!(parse_utc_time("123519.x"), (12, 35, 19, 0));
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("230394"), (23, 3, 94));
+        assert_eq!(parse_date("010100"), (1, 1, 0));
+        assert_eq!(parse_date("311299"), (31, 12, 99));
+    }
+
+    #[test]
+    fn test_expand_year() {
+        assert_eq!(expand_year(0, 80), 2000);
+        assert_eq!(expand_year(24, 80), 2024);
+        assert_eq!(expand_year(69, 80), 2069);
+        assert_eq!(expand_year(79, 80), 2079);
+        assert_eq!(expand_year(80, 80), 1980);
+        assert_eq!(expand_year(94, 80), 1994);
+        assert_eq!(expand_year(99, 80), 1999);
+
+        // Other pivots
+        assert_eq!(expand_year(69, 70), 2069);
+        assert_eq!(expand_year(70, 70), 1970);
+        assert_eq!(expand_year(0, 0), 1900);
+        assert_eq!(expand_year(99, 99), 1999);
+    }
+
+    #[test]
+    fn test_rfc3339_timestamp() {
+        let timestamp = |sentence: &str| match parse_nmea_sentence(sentence) {
+            Some(ParsedData::Rmc(rmc)) => rfc3339_timestamp(&rmc, 80),
+            other => panic!("{:?}", other),
+        };
+
+        assert_eq!(
+            timestamp("GNRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230324,,").as_deref(),
+            Some("2024-03-23T12:35:19Z")
+        );
+        // Around the pivot
+        assert_eq!(
+            timestamp("GNRMC,235959,A,,,,,,,311299,,").as_deref(),
+            Some("1999-12-31T23:59:59Z")
+        );
+        assert_eq!(
+            timestamp("GNRMC,000000.50,A,,,,,,,010100,,").as_deref(),
+            Some("2000-01-01T00:00:00.500Z")
+        );
+        assert_eq!(
+            timestamp("GNRMC,120000,A,,,,,,,311279,,").as_deref(),
+            Some("2079-12-31T12:00:00Z")
+        );
+        assert_eq!(
+            timestamp("GNRMC,120000,A,,,,,,,010180,,").as_deref(),
+            Some("1980-01-01T12:00:00Z")
+        );
+        // No date before the receiver's clock is synchronized
+        assert_eq!(timestamp("GNRMC,120000,V,,,,,,,,,"), None);
+    }
+
+    #[test]
+    fn test_parse_and_publish_gsv() {
+        let data = "GPGSV,3,1,11,07,79,045,42,08,62,272,43,09,59,138,42,10,57,359,43*70";
+        parse_and_publish(data);
+    }
+
+    #[test]
+    fn test_gsv_satellite_expiry() {
+        let mut config = get_test_config();
+        config.device_id = "gsv-expiry".to_string();
+        let mqtt = RecordingPublisher::default();
+        let cycles = [
+            "GPGSV,2,1,05,07,79,045,42,08,62,272,43,09,59,138,42,10,57,359,43",
+            "GPGSV,2,2,05,11,10,100,20",
+            "GLGSV,1,1,01,11,40,140,30",
+            // PRN 08 and 11 drop out, 11 is still in view of GLONASS
+            "GPGSV,1,1,03,07,79,045,42,09,59,138,42,10,57,359,43",
+        ];
+        for data in cycles {
+            publish_parsed(&parse_nmea_sentence(data).unwrap(), &mqtt, &config);
+        }
+
+        let cleared: Vec<_> = mqtt
+            .messages()
+            .into_iter()
+            .filter(|(_, payload)| payload.is_empty())
+            .map(|(topic, _)| topic)
+            .collect();
+        assert_eq!(cleared, ["/GOLF86/GPS/SAT/VEHICLES/8"]);
+
+        // Once GLONASS loses it as well, PRN 11 is cleared
+        publish_parsed(
+            &parse_nmea_sentence("GLGSV,1,1,00").unwrap(),
+            &mqtt,
+            &config,
+        );
+        assert_eq!(
+            mqtt.messages().last(),
+            Some(&("/GOLF86/GPS/SAT/VEHICLES/11".to_string(), String::new()))
+        );
+    }
+
+    #[test]
+    fn test_gsv_satellite_counts() {
+        let mut config = get_test_config();
+        config.device_id = "gsv-counts".to_string();
+        let mqtt = RecordingPublisher::default();
+        let cycle = [
+            "GPGSV,2,1,05,07,79,045,42,08,62,272,43,09,59,138,42,10,57,359,43",
+            "GPGSV,2,2,05,11,10,100,20",
+            "GLGSV,1,1,02,65,40,140,30,66,20,200,25",
+            "GAGSV,1,1,01,301,30,120,35",
+            "GBGSV,1,1,00",
+        ];
+        for data in cycle {
+            publish_parsed(&parse_nmea_sentence(data).unwrap(), &mqtt, &config);
+        }
+
+        let messages = mqtt.messages();
+        assert_eq!(payload_of(&messages, "SAT/GLOBAL/GPS/NUM"), Some("5"));
+        assert_eq!(payload_of(&messages, "SAT/GLOBAL/GLONASS/NUM"), Some("2"));
+        assert_eq!(payload_of(&messages, "SAT/GLOBAL/Galileo/NUM"), Some("1"));
+        assert_eq!(payload_of(&messages, "SAT/GLOBAL/BeiDou/NUM"), Some("0"));
+        let totals: Vec<_> = messages
+            .iter()
+            .filter(|(topic, _)| topic.ends_with("SAT/GLOBAL/NUM"))
+            .map(|(_, payload)| payload.as_str())
+            .collect();
+        assert_eq!(totals, ["5", "7", "8", "8"]);
+
+        // The next GPS burst replaces the GPS count instead of adding to it
+        let mqtt = RecordingPublisher::default();
+        publish_parsed(
+            &parse_nmea_sentence("GPGSV,1,1,03,07,79,045,42,09,59,138,42,10,57,359,43").unwrap(),
+            &mqtt,
+            &config,
+        );
+        assert_eq!(payload_of(&mqtt.messages(), "SAT/GLOBAL/NUM"), Some("6"));
+    }
+
+    #[test]
+    fn test_gsv_three_sentence_burst() {
+        let mut bursts = HashMap::new();
+        let lines = [
+            "GPGSV,3,1,11,03,03,111,00,04,15,270,00,06,01,010,00,13,06,292,00",
+            "GPGSV,3,2,11,14,25,170,00,16,57,208,39,18,67,296,40,19,40,246,00",
+            "GPGSV,3,3,11,22,42,067,42,24,14,311,43,27,05,244,00",
+        ];
+
+        let first = parse_gsv_sentence(lines[0]).unwrap();
+        assert!(accumulate_gsv(&mut bursts, "GP", first).is_none());
+        let second = parse_gsv_sentence(lines[1]).unwrap();
+        assert!(accumulate_gsv(&mut bursts, "GP", second).is_none());
+        let third = parse_gsv_sentence(lines[2]).unwrap();
+        let burst = accumulate_gsv(&mut bursts, "GP", third).expect("burst should be complete");
+
+        assert_eq!(burst.satellites_in_view, 11);
+        assert_eq!(burst.satellites.len(), 11);
+        assert_eq!(burst.satellites[0].prn, 3);
+        assert_eq!(
+            burst.satellites[10],
+            GsvSatellite {
+                prn: 27,
+                elevation: 5,
+                azimuth: 244,
+                snr: 0
+            }
+        );
+        assert!(bursts.is_empty());
+    }
+
+    #[test]
+    fn test_gsv_interrupted_burst() {
+        let mut bursts = HashMap::new();
+
+        // First burst is abandoned after one sentence by a new burst starting
+        let stale =
+            parse_gsv_sentence("GPGSV,3,1,09,01,10,100,20,02,20,200,30,03,30,300,40,04,40,040,50")
+                .unwrap();
+        assert!(accumulate_gsv(&mut bursts, "GP", stale).is_none());
+        let first =
+            parse_gsv_sentence("GPGSV,2,1,05,05,50,050,25,06,60,060,35,07,70,070,45,08,80,080,55")
+                .unwrap();
+        assert!(accumulate_gsv(&mut bursts, "GP", first).is_none());
+        let second = parse_gsv_sentence("GPGSV,2,2,05,09,15,150,15").unwrap();
+        let burst = accumulate_gsv(&mut bursts, "GP", second).expect("burst should be complete");
+        assert_eq!(burst.satellites_in_view, 5);
+        assert_eq!(
+            burst.satellites.iter().map(|s| s.prn).collect::<Vec<_>>(),
+            vec![5, 6, 7, 8, 9]
+        );
+
+        // A fragment without its predecessors is dropped
+        let orphan = parse_gsv_sentence("GLGSV,2,2,05,09,15,150,15").unwrap();
+        assert!(accumulate_gsv(&mut bursts, "GL", orphan).is_none());
+        assert!(bursts.is_empty());
+    }
+
+    #[test]
+    fn test_parse_and_publish_gga() {
+        let data = "GNGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47";
+        let messages = parse_and_publish(data);
+        assert_eq!(payload_of(&messages, "ALT"), Some("545.4"));
+        assert_eq!(payload_of(&messages, "QTY"), Some("1"));
+        assert_eq!(payload_of(&messages, "QTY_TEXT"), Some("GPS"));
+        assert_eq!(payload_of(&messages, "SAT/GLOBAL/IN_USE"), Some("8"));
+        assert_eq!(payload_of(&messages, "GEOID_SEP"), Some("46.9"));
+
+        // Empty satellites-in-use field falls back to 0
+        let data = "GNGGA,123519,4807.038,N,01131.000,E,1,,0.9,545.4,M,46.9,M,,*47";
+        let messages = parse_and_publish(data);
+        assert_eq!(payload_of(&messages, "SAT/GLOBAL/IN_USE"), Some("0"));
+
+        // Short sentence without the geoidal separation field must not panic
+        let data = "GNGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4";
+        let messages = parse_and_publish(data);
+        assert_eq!(payload_of(&messages, "GEOID_SEP"), None);
+
+        // Blank geoidal separation field is skipped
+        let data = "GNGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,,M,,";
+        let messages = parse_and_publish(data);
+        assert_eq!(payload_of(&messages, "ALT"), Some("545.4"));
+        assert_eq!(payload_of(&messages, "GEOID_SEP"), None);
+    }
+
+    #[test]
+    fn test_parse_and_publish_rmc() {
+        let data = "GNRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A";
+        let messages = parse_and_publish(data);
+        assert_eq!(payload_of(&messages, "LAT"), Some("48.1173"));
+        assert_eq!(payload_of(&messages, "SPD"), Some("22.4"));
+        assert_eq!(payload_of(&messages, "MAG_VAR"), Some("-3.1"));
+        assert_eq!(
+            payload_of(&messages, "TIMESTAMP"),
+            Some("1994-03-23T12:35:19Z")
+        );
+        assert!(payload_of(&messages, "LNG").is_some());
+
+        // NMEA 2.3 mode indicator
+        let data = "GNRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W,D";
+        let messages = parse_and_publish(data);
+        assert_eq!(payload_of(&messages, "RMC_MODE"), Some("Differential"));
+        assert_eq!(payload_of(&messages, "LAT"), Some("48.1173"));
+
+        // An active status with the no-fix mode is still an invalid fix
+        let data = "GNRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W,N";
+        match parse_nmea_sentence(data) {
+            Some(ParsedData::Rmc(rmc)) => assert_eq!((rmc.mode, rmc.valid), (Some('N'), false)),
+            other => panic!("expected RMC, got {:?}", other),
+        }
+        let messages = parse_and_publish(data);
+        assert_eq!(payload_of(&messages, "RMC_MODE"), Some("No fix"));
+        assert_eq!(payload_of(&messages, "LAT"), None);
+
+        // No fix: the void status suppresses the zeroed position and speed
+        let data = "GNRMC,123520,V,,,,,,,230394,,";
+        let messages = parse_and_publish(data);
+        assert_eq!(payload_of(&messages, "TME"), Some("12:35:20"));
+        assert_eq!(payload_of(&messages, "LAT"), None);
+        assert_eq!(payload_of(&messages, "SPD"), None);
+
+        let mut config = get_test_config();
+        config.publish_invalid_fixes = true;
+        config.speed_source = SpeedSource::Rmc;
+        let mqtt = RecordingPublisher::default();
+        publish_parsed(&parse_nmea_sentence(data).unwrap(), &mqtt, &config);
+        assert!(payload_of(&mqtt.messages(), "LAT").is_some());
+        assert_eq!(payload_of(&mqtt.messages(), "SPD"), None);
+
+        // A speed field present and zero is still published
+        let data = "GNRMC,123521,V,,,,,000.0,,230394,,";
+        publish_parsed(&parse_nmea_sentence(data).unwrap(), &mqtt, &config);
+        assert_eq!(payload_of(&mqtt.messages(), "SPD"), Some("0"));
+    }
+
+    #[test]
+    fn test_empty_vtg_fields_not_published() {
+        assert_eq!(
+            parse_nmea_sentence("GNVTG,,T,,M,,N,,K,N"),
+            Some(ParsedData::Vtg(VtgData {
+                course: None,
+                speed_knots: None,
+                speed_kph: None,
+            }))
+        );
+
+        let mut config = get_test_config();
+        config.device_id = "empty-vtg".to_string();
+        config.speed_source = SpeedSource::Vtg;
+        let mqtt = RecordingPublisher::default();
+        publish_parsed(
+            &parse_nmea_sentence("GNVTG,,T,,M,,N,,K,N").unwrap(),
+            &mqtt,
+            &config,
+        );
+        for suffix in ["CRS", "SPD_KTS", "SPD_KPH", "SPD"] {
+            assert_eq!(payload_of(&mqtt.messages(), suffix), None, "{}", suffix);
+        }
+
+        // Present zeros are published, a standing receiver still reports speed 0
+        publish_parsed(
+            &parse_nmea_sentence("GNVTG,,T,,M,0.0,N,0.0,K,A").unwrap(),
+            &mqtt,
+            &config,
+        );
+        assert_eq!(payload_of(&mqtt.messages(), "CRS"), None);
+        assert_eq!(payload_of(&mqtt.messages(), "SPD_KTS"), Some("0"));
+        assert_eq!(payload_of(&mqtt.messages(), "SPD"), Some("0"));
+    }
+
+    #[test]
+    fn test_parse_nmea_sentence_typed() {
+        let parsed = parse_nmea_sentence(
+            "$GNGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47",
+        );
+        assert_eq!(
+            parsed,
+            Some(ParsedData::Gga(GgaData {
+                utc_time: "123519".to_string(),
+                latitude: 48.1173,
+                longitude: 11.516666666666667,
+                altitude: 545.4,
+                fix_quality: 1,
+                satellites_in_use: 8,
+                geoid_separation: Some(46.9),
+            }))
+        );
+
+        match parse_nmea_sentence(
+            "GNRMC,123519.50,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W",
+        ) {
+            Some(ParsedData::Rmc(rmc)) => {
+                assert_eq!(
+                    (rmc.hour, rmc.minute, rmc.second, rmc.millisecond),
+                    (12, 35, 19, 500)
+                );
+                assert_eq!((rmc.day, rmc.month, rmc.year), (23, 3, 94));
+                assert_eq!(rmc.speed, Some(22.4));
+                assert_eq!(rmc.course, Some(84.4));
+                assert_eq!(rmc.magnetic_variation, Some(-3.1));
+            }
+            other => panic!("expected RMC, got {:?}", other),
+        }
+
+        assert_eq!(
+            parse_nmea_sentence("GNVTG,054.7,T,034.4,M,005.5,N,010.2,K"),
+            Some(ParsedData::Vtg(VtgData {
+                course: Some(54.7),
+                speed_knots: Some(5.5),
+                speed_kph: Some(10.2),
+            }))
+        );
+
+        // Short and unknown sentences don't parse
+        assert_eq!(parse_nmea_sentence("GNGGA,123519"), None);
+        assert_eq!(parse_nmea_sentence("GNXYZ,1,2,3"), None);
+    }
+
+    #[test]
+    fn test_fix_quality_label() {
+        let labels = [
+            "Invalid",
+            "GPS",
+            "DGPS",
+            "PPS",
+            "RTK",
+            "Float RTK",
+            "Estimated",
+            "Manual",
+            "Simulation",
+        ];
+        for (quality, label) in labels.iter().enumerate() {
+            assert_eq!(fix_quality_label(quality), *label);
+        }
+        assert_eq!(fix_quality_label(9), "Unknown");
+    }
+
+    #[test]
+    fn test_date_topic_uses_base_topic() {
+        let mut config = get_test_config();
+        assert_eq!(date_topic(&config), "/GOLF86/GPS/DTE");
+
+        config.mqtt_base_topic = "/CAR/NAV/".to_string();
+        assert_eq!(date_topic(&config), "/CAR/NAV/DTE");
+    }
+
+    #[test]
+    fn test_parse_magnetic_variation() {
+        let data = "GNRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W";
+        let parts: Vec<&str> = data.split(',').collect();
+        assert_eq!(parse_magnetic_variation(parts[10], parts[11]), Some(-3.1));
+
+        assert_eq!(parse_magnetic_variation("003.1", "E"), Some(3.1));
+        assert_eq!(parse_magnetic_variation("", ""), None);
+        assert_eq!(parse_magnetic_variation("003.1", ""), None);
+        assert_eq!(parse_magnetic_variation("abc", "W"), None);
+    }
+
+    #[test]
+    fn test_trip_accumulates_distance_above_minimum() {
+        let mut trip = Trip::default();
+
+        // The first position only sets the reference point
+        assert_eq!(trip.update(56.9496, 24.1052, 5.0), None);
+
+        // About 1m north is jitter
+        assert_eq!(trip.update(56.94961, 24.1052, 5.0), None);
+        assert_eq!(trip.distance_m, 0.0);
+
+        // About 111m north
+        let bearing = trip.update(56.9506, 24.1052, 5.0).unwrap();
+        assert!(bearing.abs() < 1e-6);
+        assert!((trip.distance_m - 111.2).abs() < 0.1, "{}", trip.distance_m);
+
+        // Back south adds to the total
+        let bearing = trip.update(56.9496, 24.1052, 5.0).unwrap();
+        assert!((bearing - 180.0).abs() < 1e-6);
+        assert!((trip.distance_m - 222.4).abs() < 0.1, "{}", trip.distance_m);
+    }
+
+    #[test]
+    fn test_vertical_speed() {
+        let mut vertical_speed = VerticalSpeed::default();
+
+        // Climbing 2.5m per second, then descending at 1.25 m/s over two seconds
+        assert_eq!(vertical_speed.update("120000", 100.0, 1), None);
+        assert_eq!(vertical_speed.update("120001", 102.5, 1), Some(2.5));
+        assert_eq!(vertical_speed.update("120001.50", 104.0, 2), Some(3.0));
+        assert_eq!(vertical_speed.update("120003.50", 101.5, 1), Some(-1.25));
+        // A repeated epoch is ignored
+        assert_eq!(vertical_speed.update("120003.50", 101.5, 1), None);
+
+        // The jump to the altitude of a fix without quality isn't a vertical speed
+        assert_eq!(vertical_speed.update("120004", 0.0, 0), None);
+        assert_eq!(vertical_speed.update("120005", 101.0, 1), None);
+        assert_eq!(vertical_speed.update("120006", 100.0, 1), Some(-1.0));
+
+        // Across midnight
+        let mut vertical_speed = VerticalSpeed::default();
+        assert_eq!(vertical_speed.update("235959", 50.0, 1), None);
+        assert_eq!(vertical_speed.update("000001", 51.0, 1), Some(0.5));
+    }
+
+    #[test]
+    fn test_motion_filter() {
+        let mut filter = MotionFilter::default();
+
+        // Disabled with a zero threshold
+        let update = filter.update(0.3, 56.9496, 24.1052, 0.0, 10.0);
+        assert_eq!(update.speed, 0.3);
+        assert!(update.publish_position);
+        assert_eq!(update.changed_to, None);
+
+        let update = filter.update(0.3, 56.9496, 24.1052, 1.0, 10.0);
+        assert_eq!(update.speed, 0.0);
+        assert!(update.publish_position);
+        assert_eq!(update.changed_to, Some(true));
+
+        // About 2m of wobble is suppressed
+        let update = filter.update(0.4, 56.94962, 24.1052, 1.0, 10.0);
+        assert!(!update.publish_position);
+        assert_eq!(update.changed_to, None);
+
+        // Driving off publishes every position with the real speed
+        let update = filter.update(12.5, 56.94963, 24.1052, 1.0, 10.0);
+        assert_eq!(update.speed, 12.5);
+        assert!(update.publish_position);
+        assert_eq!(update.changed_to, Some(false));
+    }
+
+    #[test]
+    fn test_circular_mean_across_north() {
+        let mean = circular_mean_deg([359.0, 1.0].into_iter());
+        assert!(mean < 1e-9 || (360.0 - mean) < 1e-9, "{}", mean);
+
+        let mean = circular_mean_deg([350.0, 10.0, 20.0].into_iter());
+        assert!((mean - 6.7).abs() < 0.1, "{}", mean);
+
+        let mean = circular_mean_deg([90.0, 180.0].into_iter());
+        assert!((mean - 135.0).abs() < 1e-9, "{}", mean);
+    }
+
+    #[test]
+    fn test_smoother_window() {
+        let mut smoother = Smoother::default();
+        assert_eq!(smoother.smooth_speed(10.0, 3), 10.0);
+        assert_eq!(smoother.smooth_speed(20.0, 3), 15.0);
+        assert_eq!(smoother.smooth_speed(30.0, 3), 20.0);
+        // The first sample drops out of the window
+        assert_eq!(smoother.smooth_speed(40.0, 3), 30.0);
+
+        smoother.smooth_course(358.0, 2);
+        let course = smoother.smooth_course(4.0, 2);
+        assert!((course - 1.0).abs() < 1e-9, "{}", course);
+    }
+
+    #[test]
+    fn test_position_kalman_converges() {
+        let (lat, lon) = (56.9496, 24.1052);
+        // Zero-mean noise of up to about 5m around a stationary position
+        let noise = [
+            (3.0, -4.0),
+            (-5.0, 2.0),
+            (4.0, 5.0),
+            (-2.0, -3.0),
+            (0.0, 0.0),
+        ];
+
+        let mut kalman = PositionKalman::default();
+        let mut error_m = Vec::new();
+        for second in 0..100 {
+            let (north_m, east_m) = noise[second % noise.len()];
+            let measured = offset_position(lat, lon, north_m, east_m);
+            let (filtered_lat, filtered_lon) =
+                kalman.update(second as f64, measured, 0.0, Some(0.0), 1.0, 5.0);
+            if second == 0 {
+                // Seeded from the first fix
+                assert_eq!((filtered_lat, filtered_lon), measured);
+            }
+            error_m.push(haversine_distance_m(lat, lon, filtered_lat, filtered_lon));
+        }
+        assert!(
+            error_m[95..].iter().all(|error| *error < 1.5),
+            "{:?}",
+            error_m
+        );
+
+        // Moving north at 10 knots the estimate keeps up with the measurements
+        let mut kalman = PositionKalman::default();
+        let mut filtered = (lat, lon);
+        for second in 0..20 {
+            let measured = offset_position(lat, lon, knots_to_ms(10.0) * second as f64, 0.0);
+            filtered = kalman.update(second as f64, measured, 10.0, Some(0.0), 1.0, 5.0);
+        }
+        let expected = offset_position(lat, lon, knots_to_ms(10.0) * 19.0, 0.0);
+        assert!(haversine_distance_m(expected.0, expected.1, filtered.0, filtered.1) < 0.01);
+    }
+
+    #[test]
+    fn test_position_filter_topics() {
+        let mut config = get_test_config();
+        config.device_id = "kalman".to_string();
+        let rmc = b"$GPRMC,123519,A,4807.038,N,01131.000,E,000.0,084.4,230394,003.1,W*6E\r\n";
+
+        let mqtt = RecordingPublisher::default();
+        process_gps_data(rmc, &config, &mqtt).unwrap();
+        assert_eq!(payload_of(&mqtt.messages(), "LAT_FILTERED"), None);
+
+        config.position_filter = PositionFilter::Kalman;
+        let mqtt = RecordingPublisher::default();
+        process_gps_data(rmc, &config, &mqtt).unwrap();
+        let messages = mqtt.messages();
+        assert_eq!(payload_of(&messages, "LAT_FILTERED"), Some("48.1173"));
+        assert_eq!(payload_of(&messages, "LNG_FILTERED"), Some("11.516667"));
+    }
+
+    #[test]
+    fn test_null_island_skips_fix_outputs() {
+        let mut config = get_test_config();
+        config.device_id = "null-island".to_string();
+        config.mqtt_json_mode = true;
+        config.position_filter = PositionFilter::Kalman;
+        let mqtt = RecordingPublisher::default();
+        process_gps_data(
+            b"$GPRMC,123521,A,0000.000,N,00000.000,E,022.4,084.4,230394,003.1,W*63\r\n\
+              $GPGGA,123521,0000.000,N,00000.000,E,1,08,0.9,545.4,M,46.9,M,,*4E\r\n",
+            &config,
+            &mqtt,
+        )
+        .unwrap();
+        let messages = mqtt.messages();
+        assert_eq!(payload_of(&messages, "SPD"), Some("22.4"));
+        assert_eq!(payload_of(&messages, "LAT_FILTERED"), None);
+        assert_eq!(payload_of(&messages, "POSITION"), None);
+    }
+
+    #[test]
+    fn test_fix_buffer_pairs_rmc_and_gga() {
+        let mut buffer = FixBuffer::default();
+
+        // GGA alone is not a complete fix
+        assert!(buffer.update_from_gga("123519", 545.4, 1, 8).is_none());
+
+        let fix = buffer
+            .update_from_rmc(
+                "123519",
+                "1994-03-23T12:35:19Z".to_string(),
+                48.1173,
+                11.5166,
+                22.4,
+                Some(84.4),
+            )
+            .expect("RMC and GGA share the same timestamp");
+        assert_eq!(fix.alt, 545.4);
+        assert_eq!(fix.fix_quality, 1);
+        assert_eq!(fix.sats_used, 8);
+        assert_eq!(fix.speed, 22.4);
+
+        // The same epoch is only flushed once
+        assert!(buffer.update_from_gga("123519", 545.4, 1, 8).is_none());
+
+        // RMC for the next epoch waits for its GGA
+        assert!(buffer
+            .update_from_rmc("123520", String::new(), 48.1, 11.5, 22.0, Some(84.0))
+            .is_none());
+        assert_eq!(buffer.latest().alt, 545.4);
+        assert!(buffer.update_from_gga("123520", 546.0, 2, 9).is_some());
+    }
+
+    #[test]
+    fn test_position_fix_json() {
+        let fix = PositionFix {
+            lat: 48.5,
+            lon: 11.25,
+            ..test_fix()
+        };
+        assert_eq!(
+            serde_json::to_string(&fix).unwrap(),
+            r#"{"timestamp":"2024-03-23T12:35:19Z","lat":48.5,"lon":11.25,"alt":545.4,"speed":22.4,"course":84.4,"fix_quality":1,"sats_used":8}"#
+        );
 
-    if hour > 23 || minute > 59 || second > 59 {
-        return (0, 0, 0);
+        let mut config = get_test_config();
+        config.mqtt_json_mode = true;
+        let mqtt = RecordingPublisher::default();
+        handle_position_fix(&fix, &mqtt, &config);
+        assert_eq!(
+            payload_of(&mqtt.messages(), "POSITION"),
+            Some(
+                r#"{"device_id":"golf86-pi","timestamp":"2024-03-23T12:35:19Z","lat":48.5,"lon":11.25,"alt":545.4,"speed":22.4,"course":84.4,"fix_quality":1,"sats_used":8}"#
+            )
+        );
+
+        // Fixes of high-rate receivers keep their milliseconds
+        config.device_id = "position-json-ms".to_string();
+        let mqtt = RecordingPublisher::default();
+        process_gps_data(
+            b"$GPRMC,123519.50,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*41\r\n\
+              $GPGGA,123519.50,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*6C\r\n",
+            &config,
+            &mqtt,
+        )
+        .unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(payload_of(&mqtt.messages(), "POSITION").unwrap()).unwrap();
+        assert_eq!(json["timestamp"], "1994-03-23T12:35:19.500Z");
+
+        // No fix is made up before the receiver has a date
+        config.device_id = "position-json-no-date".to_string();
+        let mqtt = RecordingPublisher::default();
+        process_gps_data(
+            b"$GPRMC,123520,A,4807.038,N,01131.000,E,022.4,084.4,,003.1,W*6F\r\n\
+              $GPGGA,123520,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*4D\r\n",
+            &config,
+            &mqtt,
+        )
+        .unwrap();
+        assert!(payload_of(&mqtt.messages(), "LAT").is_some());
+        assert_eq!(payload_of(&mqtt.messages(), "POSITION"), None);
     }
 
-    (hour, minute, second)
-}
+    #[test]
+    fn test_maps_url() {
+        let mut fix = PositionFix {
+            lon: -11.5166667,
+            ..test_fix()
+        };
 
-/// Parses a date string in DDMMYY format and returns the components as integers.
-///
-/// # Arguments
-///
-/// * `date` - A string slice in DDMMYY format (e.g., "230394" for March 23, 1994)
-///
-/// # Returns
-///
-/// A tuple of `(day, month, year)` where:
-/// * `day` - Day of month (1-31)
-/// * `month` - Month number (1-12)
-/// * `year` - Two-digit year (0-99)
-///
-/// Returns `(0, 0, 0)` if:
-/// * Input string is not exactly 6 characters
-/// * Day is 0 or > 31
-/// * Month is 0 or > 12
-/// * Any component fails to parse as a number
-///
-fn parse_date(date: &str) -> (u32, u32, u32) {
-    if date.len() != 6 {
-        return (0, 0, 0);
+        let mut config = get_test_config();
+        config.maps_url_topic = Some("/GOLF86/{device}/MAPS_URL".to_string());
+        let mqtt = RecordingPublisher::default();
+        handle_position_fix(&fix, &mqtt, &config);
+        assert_eq!(
+            mqtt.messages(),
+            [(
+                format!("/GOLF86/{}/MAPS_URL", config.device_id),
+                "https://www.openstreetmap.org/?mlat=48.1173&mlon=-11.516667".to_string()
+            )]
+        );
+
+        // No link to the position of a fix without quality
+        fix.fix_quality = 0;
+        config.maps_url_template = "https://maps.google.com/?q={lat},{lon}".to_string();
+        let mqtt = RecordingPublisher::default();
+        handle_position_fix(&fix, &mqtt, &config);
+        assert!(mqtt.messages().is_empty());
+
+        // Nor to a dead-reckoning estimate
+        fix.fix_quality = 6;
+        handle_position_fix(&fix, &mqtt, &config);
+        assert!(mqtt.messages().is_empty());
+
+        fix.fix_quality = 2;
+        handle_position_fix(&fix, &mqtt, &config);
+        assert_eq!(
+            mqtt.messages()[0].1,
+            "https://maps.google.com/?q=48.1173,-11.516667"
+        );
     }
 
-    let day = date[0..2].parse::<u32>().unwrap_or(0);
-    let month = date[2..4].parse::<u32>().unwrap_or(0);
-    let year = date[4..6].parse::<u32>().unwrap_or(0);
+    #[test]
+    fn test_geojson_feature() {
+        let fix = PositionFix {
+            lat: 48.5,
+            lon: 11.25,
+            ..test_fix()
+        };
 
-    if day == 0 || day > 31 || month == 0 || month > 12 {
-        return (0, 0, 0);
+        let feature = geojson_feature(&fix);
+        // GeoJSON puts longitude before latitude
+        assert_eq!(feature["geometry"]["coordinates"][0], 11.25);
+        assert_eq!(feature["geometry"]["coordinates"][1], 48.5);
+
+        let mut config = get_test_config();
+        config.geojson_topic = Some("/GOLF86/MAP".to_string());
+        let mqtt = RecordingPublisher::default();
+        handle_position_fix(&fix, &mqtt, &config);
+        assert_eq!(
+            mqtt.messages(),
+            [(
+                "/GOLF86/MAP".to_string(),
+                r#"{"geometry":{"coordinates":[11.25,48.5],"type":"Point"},"properties":{"alt":545.4,"course":84.4,"speed":22.4,"timestamp":"2024-03-23T12:35:19Z"},"type":"Feature"}"#
+                    .to_string()
+            )]
+        );
     }
 
-    (day, month, year)
-}
+    #[test]
+    fn test_parse_and_publish_vtg() {
+        let data = "GNVTG,054.7,T,034.4,M,005.5,N,010.2,K*48";
+        let mut config = get_test_config();
+        config.speed_source = SpeedSource::Vtg;
+        let mqtt = RecordingPublisher::default();
+        publish_parsed(&parse_nmea_sentence(data).unwrap(), &mqtt, &config);
+        let messages = mqtt.messages();
+        let topics: Vec<&str> = messages.iter().map(|(t, _)| t.as_str()).collect();
+        assert_eq!(
+            topics,
+            [
+                "/GOLF86/GPS/CRS",
+                "/GOLF86/GPS/SPD_KTS",
+                "/GOLF86/GPS/SPD_KPH",
+                "/GOLF86/GPS/SPD",
+                "/GOLF86/GPS/SPD_MS",
+                "/GOLF86/GPS/SPD_MPH",
+            ]
+        );
+        assert_eq!(payload_of(&messages, "CRS"), Some("54.7"));
+        assert_eq!(payload_of(&messages, "SPD_KTS"), Some("5.5"));
+        assert_eq!(payload_of(&messages, "SPD_KPH"), Some("10.2"));
+        assert_eq!(payload_of(&messages, "SPD"), Some("5.5"));
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::config::AppConfig;
-    use paho_mqtt as mqtt;
+    #[test]
+    fn test_position_and_speed_rate_limited() {
+        let mut config = get_test_config();
+        config.device_id = "rate-limited".to_string();
+        config.topic_template = "{base}{device}/{field}".to_string();
+        config.position_min_interval_ms = 60_000;
+        config.speed_min_interval_ms = 60_000;
 
-    fn get_test_config() -> AppConfig {
-        AppConfig {
-            mqtt_base_topic: "/GOLF86/GPS/".to_string(),
-            baud_rate: 9600,
-            mqtt_host: "localhost".to_string(),
-            mqtt_port: 1883,
-            set_gps_to_10hz: false,
-            port_name: "/dev/ttyACM0".to_string(),
+        // Rapid updates within the interval only publish the first position and speed
+        let mut published = Vec::new();
+        for second in 19..22 {
+            let mqtt = RecordingPublisher::default();
+            let sentence = format!(
+                "GNRMC,1235{},A,4807.0{},N,01131.000,E,022.{},084.4,230394,,",
+                second, second, second
+            );
+            publish_parsed(&parse_nmea_sentence(&sentence).unwrap(), &mqtt, &config);
+            let messages = mqtt.messages();
+            let payload = |field: &str| {
+                let topic = format!("/GOLF86/GPS/rate-limited/{}", field);
+                let found = messages.iter().find(|(t, _)| *t == topic);
+                found.map(|(_, payload)| payload.clone())
+            };
+            published.push((payload("LAT").is_some(), payload("SPD"), payload("TME")));
         }
+        assert_eq!(
+            published,
+            [
+                (
+                    true,
+                    Some("22.19".to_string()),
+                    Some("12:35:19".to_string())
+                ),
+                (false, None, Some("12:35:20".to_string())),
+                (false, None, Some("12:35:21".to_string())),
+            ]
+        );
     }
 
     #[test]
-    fn test_parse_latitude() {
-        assert_eq!(parse_latitude("4916.45", "N"), 49.274166666666666);
-        assert_eq!(parse_latitude("4916.45", "S"), -49.274166666666666);
-        assert_eq!(parse_latitude("0000.00", "N"), 0.0);
-        assert_eq!(parse_latitude("0000.00", "S"), -0.0);
+    fn test_speed_source_auto() {
+        let mut config = get_test_config();
+        config.device_id = "speed-auto".to_string();
+        let publish = |sentence: &str| {
+            let mqtt = RecordingPublisher::default();
+            publish_parsed(&parse_nmea_sentence(sentence).unwrap(), &mqtt, &config);
+            payload_of(&mqtt.messages(), "SPD").map(str::to_string)
+        };
+        let rmc = "GNRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W";
+        let rmc_without_speed = "GNRMC,123520,A,4807.038,N,01131.000,E,,,230394,003.1,W";
+        let vtg = "GNVTG,054.7,T,034.4,M,005.5,N,010.2,K";
+
+        // Without RMC, VTG provides the speed
+        assert_eq!(publish(vtg).as_deref(), Some("5.5"));
+        // RMC with a speed field takes precedence
+        assert_eq!(publish(rmc).as_deref(), Some("22.4"));
+        assert_eq!(publish(vtg), None);
+        // RMC without one hands over to VTG again
+        assert_eq!(publish(rmc_without_speed), None);
+        assert_eq!(publish(vtg).as_deref(), Some("5.5"));
+        assert_eq!(publish("GNVTG,054.7,T,034.4,M,,N,,K"), None);
     }
 
     #[test]
-    fn test_parse_longitude() {
-        assert_eq!(parse_longitude("12311.12", "E"), 123.18533333333333);
-        assert_eq!(parse_longitude("12311.12", "W"), -123.18533333333333);
-        assert_eq!(parse_longitude("00000.00", "E"), 0.0);
-        assert_eq!(parse_longitude("00000.00", "W"), -0.0);
+    fn test_parse_and_publish_gsa() {
+        let data = "GNGSA,A,3,04,05,,09,12,,24,,,,,1.8,1.0,1.5*33";
+        parse_and_publish(data);
     }
 
     #[test]
-    fn test_parse_utc_time() {
-        assert_eq!(parse_utc_time("123519"), (12, 35, 19));
-        assert_eq!(parse_utc_time("000000"), (0, 0, 0));
-        assert_eq!(parse_utc_time("235959"), (23, 59, 59));
+    fn test_gsa_system_id() {
+        // NMEA 4.10 adds the system ID after VDOP
+        let data = "GNGSA,A,3,65,66,,,,,,,,,,,1.8,1.0,1.5,2";
+        let parts: Vec<&str> = data.split(',').collect();
+        let system = parts.get(18).and_then(|id| gsa_system_label(id));
+        assert_eq!(system, Some("GLONASS"));
+        assert_eq!(
+            gsa_fix_type_field(65, system),
+            "SAT/VEHICLES/GLONASS/65/FIX_TYPE"
+        );
+        assert_eq!(parse_gsa_dops(&parts), (Some(1.8), Some(1.0), Some(1.5)));
+
+        // Older sentences without the field keep the previous topic
+        assert_eq!(gsa_system_label("3"), Some("Galileo"));
+        assert_eq!(gsa_system_label(""), None);
+        assert_eq!(gsa_system_label("9"), None);
+        assert_eq!(gsa_fix_type_field(4, None), "SAT/VEHICLES/4/FIX_TYPE");
+
+        parse_and_publish(data);
     }
 
     #[test]
-    fn test_parse_date() {
-        assert_eq!(parse_date("230394"), (23, 3, 94));
-        assert_eq!(parse_date("010100"), (1, 1, 0));
-        assert_eq!(parse_date("311299"), (31, 12, 99));
+    fn test_parse_gsa_dops() {
+        // GSA always carries 12 PRN slots before the DOP values
+        let data = "GNGSA,A,3,04,05,,09,12,,,24,,,,,1.8,1.0,1.5";
+        let parts: Vec<&str> = data.split(',').collect();
+        assert_eq!(parse_gsa_dops(&parts), (Some(1.8), Some(1.0), Some(1.5)));
+
+        // Empty trailing VDOP field
+        let data = "GNGSA,A,3,04,05,,09,12,,,24,,,,,1.8,1.0,";
+        let parts: Vec<&str> = data.split(',').collect();
+        assert_eq!(parse_gsa_dops(&parts), (Some(1.8), Some(1.0), None));
+
+        // Missing DOP fields entirely
+        let parts: Vec<&str> = "GNGSA,A,1".split(',').collect();
+        assert_eq!(parse_gsa_dops(&parts), (None, None, None));
     }
 
     #[test]
-    fn test_parse_and_display_gsv() {
-        let config = get_test_config();
-        let mqtt = mqtt::Client::new("tcp://localhost:1883").unwrap();
-        let data = "GPGSV,3,1,11,07,79,045,42,08,62,272,43,09,59,138,42,10,57,359,43*70";
-        parse_and_display_gsv(data, mqtt, &config);
+    fn test_parse_and_publish_gntxt() {
+        let data = "GNTXT,01,01,02,u-blox ag - www.u-blox.com*4E";
+        parse_and_publish(data);
     }
 
     #[test]
-    fn test_parse_and_display_gga() {
-        let config = get_test_config();
-        let mqtt = mqtt::Client::new("tcp://localhost:1883").unwrap();
-        let data = "GNGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47";
-        parse_and_display_gga(data, mqtt, &config);
+    fn test_parse_and_publish_gll() {
+        let data = "GNGLL,4916.45,N,12311.12,W,225444,A";
+        let messages = parse_and_publish(data);
+        assert_eq!(payload_of(&messages, "GLL_TME"), Some("22:54:44"));
+        assert_eq!(payload_of(&messages, "GLL_STATUS"), Some("A"));
+        assert_eq!(payload_of(&messages, "GLL_MODE"), None);
+        assert_eq!(payload_of(&messages, "GLL_LAT"), Some("49.274167"));
+        assert_eq!(payload_of(&messages, "GLL_LNG"), Some("-123.185333"));
+
+        // A void status publishes only the time, unless invalid fixes are enabled
+        let data = "GNGLL,,,,,225445,V";
+        let messages = parse_and_publish(data);
+        assert_eq!(payload_of(&messages, "GLL_TME"), Some("22:54:45"));
+        assert_eq!(payload_of(&messages, "GLL_STATUS"), Some("V"));
+        assert_eq!(payload_of(&messages, "GLL_LAT"), None);
+
+        // NMEA 2.3 mode indicator, where the no-fix mode also makes the position invalid
+        let data = "GNGLL,4916.45,N,12311.12,W,225446,A,D";
+        let messages = parse_and_publish(data);
+        assert_eq!(payload_of(&messages, "GLL_MODE"), Some("Differential"));
+        assert!(payload_of(&messages, "GLL_LAT").is_some());
+        let data = "GNGLL,4916.45,N,12311.12,W,225447,A,N";
+        let messages = parse_and_publish(data);
+        assert_eq!(payload_of(&messages, "GLL_STATUS"), Some("A"));
+        assert_eq!(payload_of(&messages, "GLL_MODE"), Some("No fix"));
+        assert_eq!(payload_of(&messages, "GLL_LAT"), None);
+
+        let data = "GNGLL,,,,,225445,V";
+
+        let mut config = get_test_config();
+        config.publish_invalid_fixes = true;
+        let mqtt = RecordingPublisher::default();
+        publish_parsed(&parse_nmea_sentence(data).unwrap(), &mqtt, &config);
+        assert_eq!(payload_of(&mqtt.messages(), "GLL_LAT"), Some("0"));
     }
 
     #[test]
-    fn test_parse_and_display_rmc() {
-        let config = get_test_config();
-        let mqtt = mqtt::Client::new("tcp://localhost:1883").unwrap();
-        let data = "GNRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A";
-        parse_and_display_rmc(data, mqtt, &config);
+    fn test_parse_and_publish_zda() {
+        let data = "GNZDA,201530.00,04,07,2002,00,00";
+        let messages = parse_and_publish(data);
+        assert_eq!(
+            messages,
+            [
+                ("/GOLF86/GPS/ZDA_TME".to_string(), "20:15:30".to_string()),
+                ("/GOLF86/GPS/ZDA_DTE".to_string(), "04.07.2002".to_string()),
+                ("/GOLF86/GPS/ZDA_TZ".to_string(), "+00:00".to_string()),
+            ]
+        );
     }
 
     #[test]
-    fn test_parse_and_display_vtg() {
-        let config = get_test_config();
-        let mqtt = mqtt::Client::new("tcp://localhost:1883").unwrap();
-        let data = "GNVTG,054.7,T,034.4,M,005.5,N,010.2,K*48";
-        parse_and_display_vtg(data, mqtt, &config);
+    fn test_decode_gns_modes() {
+        assert_eq!(
+            decode_gns_modes("ADNR"),
+            vec![
+                ("GPS", "Autonomous"),
+                ("GLONASS", "Differential"),
+                ("Galileo", "No fix"),
+                ("BeiDou", "RTK"),
+            ]
+        );
+        assert_eq!(decode_gns_modes("FE")[1], ("GLONASS", "Estimated"));
+        assert!(decode_gns_modes("").is_empty());
     }
 
     #[test]
-    fn test_parse_and_display_gsa() {
-        let config = get_test_config();
-        let mqtt = mqtt::Client::new("tcp://localhost:1883").unwrap();
-        let data = "GNGSA,A,3,04,05,,09,12,,24,,,,,1.8,1.0,1.5*33";
-        parse_and_display_gsa(data, mqtt, &config);
+    fn test_parse_gns_sentence() {
+        let messages =
+            parse_and_publish("GNGNS,122310.00,4807.038,N,01131.000,E,AAN,10,0.9,545.4,46.9,,,V");
+        assert_eq!(payload_of(&messages, "GNS_LAT"), Some("48.1173"));
+        assert_eq!(payload_of(&messages, "GNS_LNG"), Some("11.516667"));
+        assert_eq!(payload_of(&messages, "GNS_ALT"), Some("545.4"));
+        assert_eq!(payload_of(&messages, "GNS_MODE"), Some("AAN"));
+        assert_eq!(payload_of(&messages, "GNS_STATUS/GPS"), Some("Autonomous"));
+        assert_eq!(
+            payload_of(&messages, "GNS_STATUS/GLONASS"),
+            Some("Autonomous")
+        );
+        assert_eq!(payload_of(&messages, "GNS_STATUS/Galileo"), Some("No fix"));
+        assert_eq!(payload_of(&messages, "GNS_STATUS/BeiDou"), None);
+
+        // No position without a fix
+        let messages = parse_and_publish("GNGNS,122310.00,,,,,NN,00,,,,,,V");
+        assert_eq!(payload_of(&messages, "GNS_LAT"), None);
+        assert_eq!(payload_of(&messages, "GNS_LNG"), None);
+        assert_eq!(payload_of(&messages, "GNS_MODE"), Some("NN"));
+        assert_eq!(payload_of(&messages, "GNS_STATUS/GPS"), Some("No fix"));
+
+        let mut config = get_test_config();
+        config.publish_invalid_fixes = true;
+        let mqtt = RecordingPublisher::default();
+        publish_parsed(
+            &parse_nmea_sentence("GNGNS,122310.00,,,,,NN,00,,,,,,V").unwrap(),
+            &mqtt,
+            &config,
+        );
+        assert_eq!(payload_of(&mqtt.messages(), "GNS_LAT"), Some("0"));
+
+        // An empty mode is neither published nor a fix
+        let messages = parse_and_publish("GNGNS,122310.00,,,,,,00,,,,,,V");
+        assert!(messages.is_empty());
+
+        // Too short to parse
+        assert!(parse_and_publish("GNGNS,122310.00").is_empty());
     }
 
     #[test]
-    fn test_parse_and_display_gntxt() {
-        let config = get_test_config();
-        let mqtt = mqtt::Client::new("tcp://localhost:1883").unwrap();
-        let data = "GNTXT,01,01,02,u-blox ag - www.u-blox.com*4E";
-        parse_and_display_gntxt(data, mqtt, &config);
+    fn test_parse_gst_errors() {
+        let data = "GNGST,172814.0,0.006,0.023,0.020,273.6,0.023,0.020,0.031";
+        let parts: Vec<&str> = data.split(',').collect();
+        assert_eq!(
+            parse_gst_errors(&parts),
+            GstErrors {
+                rms: Some(0.006),
+                lat_std: Some(0.023),
+                lon_std: Some(0.020),
+                alt_std: Some(0.031),
+            }
+        );
+
+        // Blank RMS field doesn't affect the standard deviations
+        let data = "GNGST,172814.00,,,,,1.2,0.9,2.5";
+        let parts: Vec<&str> = data.split(',').collect();
+        let errors = parse_gst_errors(&parts);
+        assert_eq!(errors.rms, None);
+        assert_eq!(errors.lat_std, Some(1.2));
+        assert_eq!(errors.alt_std, Some(2.5));
+
+        parse_and_publish(data);
+        parse_and_publish("GNGST,172814.00");
     }
 
     #[test]
-    fn test_parse_and_display_gll() {
-        let config = get_test_config();
-        let mqtt = mqtt::Client::new("tcp://localhost:1883").unwrap();
-        let data = "GNGLL,4916.45,N,12311.12,W,225444,A";
-        parse_and_display_gll(data, mqtt, &config);
+    fn test_parse_and_publish_gbs() {
+        let data = "GPGBS,235458.00,1.4,1.3,3.1,03,,-21.4,3.8,1,0";
+        assert_eq!(
+            parse_gbs(data),
+            Some(GbsData {
+                lat_err: Some(1.4),
+                lon_err: Some(1.3),
+                alt_err: Some(3.1),
+                failed_prn: Some(3),
+                failed_probability: None,
+                bias: Some(-21.4),
+                bias_std: Some(3.8),
+            })
+        );
+
+        let messages = parse_and_publish(data);
+        assert_eq!(payload_of(&messages, "INTEGRITY/LAT_ERR"), Some("1.4"));
+        assert_eq!(payload_of(&messages, "INTEGRITY/FAILED_PRN"), Some("3"));
+        assert_eq!(payload_of(&messages, "INTEGRITY/BIAS"), Some("-21.4"));
+        assert_eq!(payload_of(&messages, "INTEGRITY/FAILED_PROBABILITY"), None);
+
+        // No fault detected: most fields are empty and the failed PRN is cleared
+        let messages = parse_and_publish("GNGBS,235503.00,1.6,1.4,3.2,,,,,,");
+        assert_eq!(payload_of(&messages, "INTEGRITY/ALT_ERR"), Some("3.2"));
+        assert_eq!(payload_of(&messages, "INTEGRITY/FAILED_PRN"), Some("none"));
+        assert_eq!(payload_of(&messages, "INTEGRITY/BIAS"), None);
+
+        let messages = parse_and_publish("GNGBS,235503.00,,,,,,,");
+        assert_eq!(
+            messages,
+            [(
+                "/GOLF86/GPS/INTEGRITY/FAILED_PRN".to_string(),
+                "none".to_string()
+            )]
+        );
+        assert_eq!(parse_gbs("GNGBS,235503.00,1.6"), None);
+    }
+
+    #[test]
+    fn test_parse_dtm() {
+        assert_eq!(
+            parse_dtm("GPDTM,999,CH,0.08,N,0.07,E,-47.7,W84"),
+            Some(DtmData {
+                datum: "999".to_string(),
+                sub_datum: "CH".to_string(),
+                reference_datum: "W84".to_string(),
+            })
+        );
+        assert_eq!(parse_dtm("GPDTM,,,,,,,,"), None);
+        assert_eq!(parse_dtm("GPDTM,W84"), None);
+
+        let messages = parse_and_publish("GPDTM,W72,,0.0,N,0.0,E,0.0,W84*6F");
+        assert_eq!(payload_of(&messages, "DATUM"), Some("W72"));
+    }
+
+    #[test]
+    fn test_datum_changed() {
+        let mut active = None;
+        assert!(datum_changed(&mut active, "W84"));
+        assert!(!datum_changed(&mut active, "W84"));
+        assert!(datum_changed(&mut active, "P90"));
+        assert!(!datum_changed(&mut active, "P90"));
+        assert_eq!(active.as_deref(), Some("P90"));
+    }
+
+    #[test]
+    fn test_parse_zda_date() {
+        assert_eq!(parse_zda_date("04", "07", "2002"), Some((4, 7, 2002)));
+        assert_eq!(parse_zda_date("31", "12", "1999"), Some((31, 12, 1999)));
+        assert_eq!(parse_zda_date("", "", ""), None);
+        assert_eq!(parse_zda_date("00", "07", "2002"), None);
+        assert_eq!(parse_zda_date("04", "13", "2002"), None);
+        assert_eq!(parse_zda_date("04", "07", "02"), None);
     }
 
     #[test]
@@ -725,6 +4483,10 @@ mod tests {
         assert!(matches!(NmeaSentence::from_str("GNGSA"), NmeaSentence::GSA));
         assert!(matches!(NmeaSentence::from_str("GNGLL"), NmeaSentence::GLL));
         assert!(matches!(NmeaSentence::from_str("GNTXT"), NmeaSentence::TXT));
+        assert!(matches!(NmeaSentence::from_str("GNZDA"), NmeaSentence::ZDA));
+        assert!(matches!(NmeaSentence::from_str("GNGST"), NmeaSentence::GST));
+        assert!(matches!(NmeaSentence::from_str("GPDTM"), NmeaSentence::DTM));
+        assert!(matches!(NmeaSentence::from_str("GNGBS"), NmeaSentence::GBS));
         assert!(matches!(
             NmeaSentence::from_str("INVALID"),
             NmeaSentence::Unknown
@@ -737,24 +4499,400 @@ mod tests {
         assert_eq!(SatelliteType::GLONASS.as_str(), "GLONASS");
         assert_eq!(SatelliteType::Galileo.as_str(), "Galileo");
         assert_eq!(SatelliteType::BeiDou.as_str(), "BeiDou");
+        assert_eq!(SatelliteType::Qzss.as_str(), "QZSS");
+        assert_eq!(SatelliteType::NavIC.as_str(), "NavIC");
         assert_eq!(SatelliteType::Unknown.as_str(), "Unknown");
     }
 
+    #[test]
+    fn test_parse_gps_data_multiple_sentences() {
+        let data = b"$GNRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A\r\n\
+                     $GNGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47\r\n";
+        let parsed = parse_gps_data(data, &mut SentenceStats::default());
+
+        assert_eq!(parsed.len(), 2);
+        assert!(matches!(parsed[0], (ParsedData::Rmc(_), _)));
+        assert!(matches!(parsed[1], (ParsedData::Gga(_), _)));
+
+        // Lines that aren't complete sentences are skipped without dropping the rest
+        let data = b"garbage\n$GNVTG,054.7,T,034.4,M,005.5,N,010.2,K*48\n$GNGGA,1235";
+        let parsed = parse_gps_data(data, &mut SentenceStats::default());
+        assert_eq!(parsed.len(), 1);
+        // Parsed despite the checksum mismatch (the sentence sums to 56)
+        assert!(matches!(parsed[0], (ParsedData::Vtg(_), false)));
+
+        // but not published
+        let mut config = get_test_config();
+        config.device_id = "checksum-mismatch".to_string();
+        let mqtt = RecordingPublisher::default();
+        assert!(process_gps_data(data, &config, &mqtt).is_ok());
+        assert_eq!(payload_of(&mqtt.messages(), "SPD_KPH"), None);
+        assert_eq!(
+            STATS.lock().unwrap()["checksum-mismatch"].checksum_errors,
+            1
+        );
+
+        // Sentences separated by `\r` only are each parsed, not glued into one line
+        let data = b"$GNRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*74\r\
+                     $GNVTG,054.7,T,034.4,M,005.5,N,010.2,K*56\r\
+                     $GNGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*59\r";
+        let parsed = parse_gps_data(data, &mut SentenceStats::default());
+        assert_eq!(parsed.len(), 3);
+        assert!(matches!(parsed[0], (ParsedData::Rmc(_), true)));
+        assert!(matches!(parsed[1], (ParsedData::Vtg(_), true)));
+        assert!(matches!(parsed[2], (ParsedData::Gga(_), true)));
+    }
+
+    #[test]
+    fn test_has_valid_checksum() {
+        assert!(has_valid_checksum(
+            "$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47"
+        ));
+        assert!(has_valid_checksum(
+            "$GNVTG,054.7,T,034.4,M,005.5,N,010.2,K*56\r"
+        ));
+        assert!(!has_valid_checksum(
+            "$GNGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47"
+        ));
+        assert!(!has_valid_checksum(
+            "$GNVTG,054.7,T,034.4,M,005.5,N,010.2,K"
+        ));
+        assert!(!has_valid_checksum("$GNVTG,054.7,T*Z"));
+        assert!(!has_valid_checksum(
+            "GNVTG,054.7,T,034.4,M,005.5,N,010.2,K*56"
+        ));
+    }
+
+    #[test]
+    fn test_process_frame_publishes_mon_hw() {
+        let mut payload = vec![0; 60];
+        payload[20] = 3; // Antenna short
+        payload[22] = 0x08; // Jamming warning
+        payload[45] = 97;
+
+        let frame = Frame::Ubx(UbxMessage {
+            class: 0x0A,
+            id: 0x09,
+            payload,
+        });
+
+        let mqtt = RecordingPublisher::default();
+        assert!(process_frame(&frame, &get_test_config(), &mqtt).is_ok());
+        let messages = mqtt.messages();
+        assert_eq!(payload_of(&messages, "SAT/GLOBAL/ANTSTATUS"), Some("SHORT"));
+        assert_eq!(payload_of(&messages, "SAT/GLOBAL/JAMMING"), Some("97"));
+        assert_eq!(
+            payload_of(&messages, "SAT/GLOBAL/JAMMING_STATE"),
+            Some("warning")
+        );
+    }
+
+    #[test]
+    fn test_process_frame_publishes_raw_nmea() {
+        let mut config = get_test_config();
+        config.raw_nmea_topic = Some("/GOLF86/RAW/{device}".to_string());
+        let mqtt = RecordingPublisher::default();
+
+        // Unknown sentences are published as well, before any parsed field
+        let unknown = "$GPXYZ,1,2,3*50";
+        let vtg = "$GNVTG,054.7,T,034.4,M,005.5,N,010.2,K*56";
+        for sentence in [unknown, "$GPXYZ,1,2,3*51", vtg] {
+            let frame = Frame::Nmea(sentence.to_string());
+            assert!(process_frame(&frame, &config, &mqtt).is_ok());
+        }
+
+        let messages = mqtt.messages();
+        let raw: Vec<&str> = messages
+            .iter()
+            .filter(|(topic, _)| topic == "/GOLF86/RAW/golf86-pi")
+            .map(|(_, payload)| payload.as_str())
+            .collect();
+        assert_eq!(raw, [unknown, vtg]);
+        let position = |payload: &str| messages.iter().position(|(_, p)| p == payload);
+        assert!(position(vtg) < position("54.7"));
+    }
+
+    #[test]
+    fn test_sources_keep_separate_state() {
+        let mut first = get_test_config();
+        first.device_id = "state-a".to_string();
+        first.topic_template = "{base}{device}/{field}".to_string();
+        let mut second = first.clone();
+        second.device_id = "state-b".to_string();
+
+        let rmc = |lat: &str| {
+            parse_nmea_sentence(&format!(
+                "GNRMC,123519,A,{},N,01131.000,E,022.4,084.4,230394,,",
+                lat
+            ))
+            .unwrap()
+        };
+        let mqtt = RecordingPublisher::default();
+        publish_parsed(&rmc("4807.038"), &mqtt, &first);
+        publish_parsed(&rmc("4907.038"), &mqtt, &second);
+        publish_parsed(&rmc("4807.138"), &mqtt, &first);
+
+        // Each device's first position starts its own trip
+        let messages = mqtt.messages();
+        assert_eq!(payload_of(&messages, "state-b/TRIP/DISTANCE_M"), None);
+        assert_eq!(
+            payload_of(&messages, "state-a/TRIP/DISTANCE_M"),
+            Some("185.3")
+        );
+    }
+
+    #[test]
+    fn test_parsing_handler() {
+        let received = Mutex::new(Vec::new());
+        let handler = parsing_handler(|parsed| received.lock().unwrap().push(parsed));
+
+        handler(&Frame::Nmea(
+            "$GNVTG,054.7,T,034.4,M,005.5,N,010.2,K*56".to_string(),
+        ));
+        handler(&Frame::Nmea("$GNXYZ,1,2,3*00".to_string()));
+        // Checksum mismatch
+        handler(&Frame::Nmea(
+            "$GNVTG,054.7,T,034.4,M,005.5,N,010.2,K*48".to_string(),
+        ));
+        handler(&Frame::Ubx(UbxMessage {
+            class: 0x0A,
+            id: 0x09,
+            payload: vec![0; 60],
+        }));
+
+        drop(handler);
+        let received = received.into_inner().unwrap();
+        assert_eq!(received.len(), 1);
+        assert!(matches!(&received[0], ParsedData::Vtg(vtg) if vtg.speed_kph == Some(10.2)));
+    }
+
+    #[test]
+    fn test_fix_watchdog() {
+        let start = Instant::now();
+        let timeout = Duration::from_secs(5);
+        let mut watchdog = FixWatchdog::new(start);
+
+        // Lost after the timeout without any fix, reported once
+        assert_eq!(
+            watchdog.check(start + Duration::from_secs(4), timeout),
+            None
+        );
+        assert_eq!(
+            watchdog.check(start + Duration::from_secs(5), timeout),
+            Some(FixState::Lost)
+        );
+        assert_eq!(
+            watchdog.check(start + Duration::from_secs(6), timeout),
+            None
+        );
+
+        // The next fix resets it
+        let fix = start + Duration::from_secs(7);
+        assert_eq!(watchdog.record_fix(fix), Some(FixState::Ok));
+        assert_eq!(watchdog.record_fix(fix), None);
+        assert_eq!(watchdog.check(fix + Duration::from_secs(4), timeout), None);
+        assert_eq!(
+            watchdog.check(fix + Duration::from_secs(5), timeout),
+            Some(FixState::Lost)
+        );
+    }
+
+    #[test]
+    fn test_estimated_fix_quality() {
+        let gga = |quality: usize| {
+            ParsedData::Gga(GgaData {
+                utc_time: "123519".to_string(),
+                latitude: 48.1173,
+                longitude: 11.516666666666667,
+                altitude: 545.4,
+                fix_quality: quality,
+                satellites_in_use: 8,
+                geoid_separation: Some(46.9),
+            })
+        };
+        assert!(!is_valid_fix(&gga(0)) && !is_estimated_fix(&gga(0)));
+        assert!(is_valid_fix(&gga(1)) && !is_estimated_fix(&gga(1)));
+        assert!(is_valid_fix(&gga(4)) && !is_estimated_fix(&gga(4)));
+        assert!(!is_valid_fix(&gga(6)) && is_estimated_fix(&gga(6)));
+
+        let mut config = get_test_config();
+        config.device_id = "estimated-fix".to_string();
+        config.fix_timeout_secs = 60;
+        let fix_state = |data: &[u8]| {
+            let mqtt = RecordingPublisher::default();
+            process_gps_data(data, &config, &mqtt).unwrap();
+            payload_of(&mqtt.messages(), "STATUS/FIX").map(str::to_string)
+        };
+
+        let quality_0 = b"$GPGGA,123519,4807.038,N,01131.000,E,0,08,0.9,545.4,M,46.9,M,,*46\r\n";
+        let quality_1 = b"$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47\r\n";
+        let quality_4 = b"$GPGGA,123519,4807.038,N,01131.000,E,4,08,0.9,545.4,M,46.9,M,,*42\r\n";
+        let quality_6 = b"$GPGGA,123519,4807.038,N,01131.000,E,6,08,0.9,545.4,M,46.9,M,,*40\r\n";
+        assert_eq!(fix_state(quality_0), None);
+        assert_eq!(fix_state(quality_6).as_deref(), Some("estimated"));
+        assert_eq!(fix_state(quality_1).as_deref(), Some("ok"));
+        assert_eq!(fix_state(quality_4), None);
+        assert_eq!(fix_state(quality_6).as_deref(), Some("estimated"));
+        assert_eq!(fix_state(quality_6), None);
+        assert_eq!(fix_state(quality_0), None);
+        assert_eq!(fix_state(quality_4).as_deref(), Some("ok"));
+
+        // Estimates don't hold off the timeout, and don't end a lost fix
+        let start = Instant::now();
+        let mut watchdog = FixWatchdog::new(start);
+        assert_eq!(watchdog.record_estimate(), Some(FixState::Estimated));
+        assert_eq!(
+            watchdog.check(start + Duration::from_secs(60), Duration::from_secs(60)),
+            Some(FixState::Lost)
+        );
+        assert_eq!(watchdog.record_estimate(), None);
+    }
+
+    #[test]
+    fn test_fix_age() {
+        let mut config = get_test_config();
+        config.device_id = "fix-age".to_string();
+        let mqtt = RecordingPublisher::default();
+        let rmc = b"$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A\r\n";
+        let fix_age = |mqtt: &RecordingPublisher| {
+            payload_of(&mqtt.messages(), "STATUS/FIX_AGE_MS").map(|age| age.parse::<u64>().unwrap())
+        };
+
+        // Nothing before the first fix
+        process_gps_data(
+            b"$GPGGA,123519,4807.038,N,01131.000,E,0,00,,,M,,M,,*52\r\n",
+            &config,
+            &mqtt,
+        )
+        .unwrap();
+        assert_eq!(fix_age(&mqtt), None);
+        assert_eq!(fix_age_ms("fix-age"), None);
+
+        process_gps_data(rmc, &config, &mqtt).unwrap();
+        assert!(fix_age(&mqtt).unwrap() < 100);
+
+        // Grows between fixes
+        std::thread::sleep(Duration::from_millis(20));
+        let first = fix_age_ms("fix-age").unwrap();
+        assert!(first >= 20);
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(fix_age_ms("fix-age").unwrap() > first);
+
+        // Back to about 0 on the next fix, even within the publish interval
+        let mqtt = RecordingPublisher::default();
+        process_gps_data(rmc, &config, &mqtt).unwrap();
+        assert!(fix_age(&mqtt).unwrap() < first);
+    }
+
+    #[test]
+    fn test_talker_id() {
+        assert_eq!(
+            talker_id("$GNGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47"),
+            Some("GN")
+        );
+        assert_eq!(
+            talker_id("$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47"),
+            Some("GP")
+        );
+        assert_eq!(talker_id("$PUBX,00,123519.00*00"), None);
+        assert_eq!(talker_id("$G"), None);
+
+        let mut config = get_test_config();
+        config.device_id = "talker".to_string();
+        config.publish_talker = true;
+        let mqtt = RecordingPublisher::default();
+        process_gps_data(
+            b"$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47\r\n\
+              $GLGSV,1,1,01,65,30,120,40*53\r\n",
+            &config,
+            &mqtt,
+        )
+        .unwrap();
+        // GSV talkers don't change the fix talker
+        let talkers: Vec<_> = mqtt
+            .messages()
+            .into_iter()
+            .filter(|(topic, _)| topic.ends_with("SAT/GLOBAL/TALKER"))
+            .map(|(_, payload)| payload)
+            .collect();
+        assert_eq!(talkers, vec!["GP".to_string()]);
+
+        // Published again only when it changes
+        let mqtt = RecordingPublisher::default();
+        process_gps_data(
+            b"$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47\r\n",
+            &config,
+            &mqtt,
+        )
+        .unwrap();
+        assert_eq!(payload_of(&mqtt.messages(), "SAT/GLOBAL/TALKER"), None);
+        process_gps_data(
+            b"$GNGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*59\r\n",
+            &config,
+            &mqtt,
+        )
+        .unwrap();
+        assert_eq!(
+            payload_of(&mqtt.messages(), "SAT/GLOBAL/TALKER"),
+            Some("GN")
+        );
+    }
+
+    #[test]
+    fn test_is_valid_fix() {
+        let valid_fix = |sentence| is_valid_fix(&parse_nmea_sentence(sentence).unwrap());
+        assert!(valid_fix(
+            "GNGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,"
+        ));
+        assert!(!valid_fix("GNGGA,123519,,,,,0,00,99.9,,M,,M,,"));
+        assert!(valid_fix(
+            "GNRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W"
+        ));
+        assert!(!valid_fix("GNRMC,123519,V,,,,,,,230394,,"));
+        assert!(!valid_fix("GNVTG,054.7,T,034.4,M,005.5,N,010.2,K"));
+    }
+
+    #[test]
+    fn test_sentence_stats() {
+        let mut stats = SentenceStats::default();
+        let data = b"$GNRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*74\n\
+                     $GNGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*59\n\
+                     $GNGGA,123519*00\n\
+                     $GNXYZ,1,2,3*00\n\
+                     not a sentence\n";
+        let parsed = parse_gps_data(data, &mut stats);
+
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(stats.total, 4);
+        assert_eq!(stats.invalid, 2);
+        assert_eq!(stats.checksum_errors, 2);
+        assert_eq!(stats.by_type.get("GGA"), Some(&2));
+        assert_eq!(stats.by_type.get("RMC"), Some(&1));
+        assert_eq!(stats.by_type.get("XYZ"), Some(&1));
+
+        // Counts accumulate across buffers
+        parse_gps_data(b"$GNVTG,054.7,T,034.4,M,005.5,N,010.2,K*48", &mut stats);
+        assert_eq!(stats.total, 5);
+        // The VTG sentence sums to 56
+        assert_eq!(stats.checksum_errors, 3);
+        assert_eq!(stats.by_type.get("VTG"), Some(&1));
+    }
+
     #[test]
     fn test_process_gps_data_invalid_input() {
         let config = get_test_config();
-        let mqtt = mqtt::Client::new("tcp://localhost:1883").unwrap();
+        let mqtt = RecordingPublisher::default();
 
         // Test data not starting with $
-        let result = process_gps_data(b"Invalid data", &config, mqtt.clone());
+        let result = process_gps_data(b"Invalid data", &config, &mqtt);
         assert!(result.is_ok());
 
         // Test data without checksum separator
-        let result = process_gps_data(b"$GPGGA,Invalid", &config, mqtt.clone());
+        let result = process_gps_data(b"$GPGGA,Invalid", &config, &mqtt);
         assert!(result.is_ok());
 
         // Test empty data
-        let result = process_gps_data(b"", &config, mqtt.clone());
+        let result = process_gps_data(b"", &config, &mqtt);
         assert!(result.is_ok());
     }
 
@@ -779,23 +4917,166 @@ mod tests {
         // Test short inputs
         assert_eq!(parse_latitude("1", "N"), 0.0);
         assert_eq!(parse_longitude("1", "E"), 0.0);
+
+        // Minutes must be below 60, and the sign comes from the direction only
+        assert_eq!(parse_latitude("4960.00", "N"), 0.0);
+        assert_eq!(parse_longitude("12399.50", "E"), 0.0);
+        assert_eq!(parse_latitude("-916.45", "N"), 0.0);
+        assert_eq!(parse_longitude("-0011.12", "E"), 0.0);
+        assert_eq!(parse_latitude("6075.00", "N"), 0.0);
+
+        // Results beyond the poles or the antimeridian
+        assert_eq!(parse_latitude("9100.00", "N"), 0.0);
+        assert_eq!(parse_latitude("9000.01", "S"), 0.0);
+        assert_eq!(parse_longitude("18000.01", "W"), 0.0);
+        assert_eq!(parse_longitude("19000.00", "E"), 0.0);
+        assert_eq!(parse_latitude("4\u{FFFD}7.038", "N"), 0.0);
+        assert_eq!(parse_longitude("01\u{FFFD}1.000", "E"), 0.0);
+    }
+
+    #[test]
+    fn test_coordinate_round_trip() {
+        let round_trip = |nmea: &str, is_lat: bool| {
+            let (value, direction) = nmea.split_once(',').unwrap();
+            let decimal = if is_lat {
+                parse_latitude(value, direction)
+            } else {
+                parse_longitude(value, direction)
+            };
+            format_coordinate(decimal, is_lat)
+        };
+
+        for nmea in [
+            "8959.9990,N",
+            "8959.9990,S",
+            "9000.0000,N",
+            "9000.0000,S",
+            "0000.0060,S",
+            "4916.4500,N",
+        ] {
+            assert_eq!(round_trip(nmea, true), nmea);
+        }
+        for nmea in [
+            "17959.9990,E",
+            "17959.9990,W",
+            "18000.0000,E",
+            "18000.0000,W",
+            "00000.0060,W",
+            "12311.1200,E",
+        ] {
+            assert_eq!(round_trip(nmea, false), nmea);
+        }
+
+        // Decimal to NMEA and back, including small negatives near the equator and meridian
+        for decimal in [-0.0001, -1.5, 0.25, 89.99999, -179.99999, 180.0] {
+            let nmea = format_coordinate(decimal, false);
+            let (value, direction) = nmea.split_once(',').unwrap();
+            assert!((parse_longitude(value, direction) - decimal).abs() < 1e-5);
+        }
+
+        // Minutes rounding up to 60 carry into the degrees
+        assert_eq!(format_coordinate(-179.9999999, false), "18000.0000,W");
+        assert_eq!(format_coordinate(49.274166666666666, true), "4916.4500,N");
+    }
+
+    #[test]
+    fn test_format_position() {
+        use CoordinateFormat::{Ddm, Decimal, Dms};
+
+        // 4916.45,N and 12310.80,W
+        let (lat, lon) = (49.274166666666666, -123.18);
+        assert_eq!(format_position(lat, true, Decimal, 6), "49.274167");
+        assert_eq!(format_position(lon, false, Decimal, 6), "-123.18");
+        assert_eq!(format_position(lat, true, Ddm, 6), "49°16.450'N");
+        assert_eq!(format_position(lon, false, Ddm, 6), "123°10.800'W");
+        assert_eq!(format_position(lat, true, Dms, 6), "49°16'27.0\"N");
+        assert_eq!(format_position(lon, false, Dms, 6), "123°10'48.0\"W");
+
+        // The other hemispheres
+        assert_eq!(format_position(-lat, true, Ddm, 6), "49°16.450'S");
+        assert_eq!(format_position(-lon, false, Dms, 6), "123°10'48.0\"E");
+        assert_eq!(format_position(-0.5, true, Ddm, 6), "0°30.000'S");
+
+        // Minutes and seconds rounding up to 60 carry into the degrees
+        assert_eq!(format_position(56.9999999, true, Ddm, 6), "57°00.000'N");
+        assert_eq!(format_position(24.0833333, false, Dms, 6), "24°05'00.0\"E");
+        assert_eq!(format_position(-24.9999999, false, Dms, 6), "25°00'00.0\"W");
+
+        let mut config = get_test_config();
+        config.coordinate_format = Ddm;
+        let mqtt = RecordingPublisher::default();
+        let data = "GNGLL,4916.45,N,12310.80,W,225444,A";
+        publish_parsed(&parse_nmea_sentence(data).unwrap(), &mqtt, &config);
+        assert_eq!(payload_of(&mqtt.messages(), "GLL_LAT"), Some("49°16.450'N"));
+        assert_eq!(
+            payload_of(&mqtt.messages(), "GLL_LNG"),
+            Some("123°10.800'W")
+        );
+    }
+
+    #[test]
+    fn test_decimal_precision() {
+        assert_eq!(format_decimals(49.274166666666666, 6), "49.274167");
+        assert_eq!(format_decimals(49.274166666666666, 0), "49");
+        assert_eq!(format_decimals(22.4, 2), "22.4");
+        assert_eq!(format_decimals(545.44, 1), "545.4");
+        assert_eq!(format_decimals(2.0, 3), "2");
+        assert_eq!(format_decimals(-0.004, 2), "0");
+
+        let mut config = get_test_config();
+        config.device_id = "decimal-precision".to_string();
+        config.coordinate_decimals = 4;
+        config.speed_decimals = 1;
+        config.altitude_decimals = 0;
+        let mqtt = RecordingPublisher::default();
+        for data in [
+            "GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,",
+            "GPRMC,123519,A,4807.038,N,01131.000,E,22.4,84.4,230394,003.1,W",
+            "GPVTG,84.45,T,,M,22.4,N,41.48,K",
+        ] {
+            publish_parsed(&parse_nmea_sentence(data).unwrap(), &mqtt, &config);
+        }
+        let messages = mqtt.messages();
+
+        // 4807.038,N is 48.1173 exactly, 01131.000,E 11.516666...
+        let lng = payload_of(&messages, "LNG").unwrap();
+        assert_eq!(lng, "11.5167");
+        assert_eq!(lng.split('.').nth(1).map(str::len), Some(4));
+        assert_eq!(payload_of(&messages, "LAT"), Some("48.1173"));
+        assert_eq!(payload_of(&messages, "ALT"), Some("545"));
+        assert_eq!(payload_of(&messages, "GEOID_SEP"), Some("47"));
+        assert_eq!(payload_of(&messages, "SPD_MS"), Some("11.5"));
+        assert_eq!(payload_of(&messages, "SPD_KPH"), Some("41.5"));
+        assert_eq!(payload_of(&messages, "CRS"), Some("84.5"));
     }
 
     #[test]
     fn test_time_parsing_edge_cases() {
         // Test empty string
-        assert_eq!(parse_utc_time(""), (0, 0, 0));
+        assert_eq!(parse_utc_time(""), (0, 0, 0, 0));
 
         // Test invalid formats
-        assert_eq!(parse_utc_time("abc"), (0, 0, 0));
-        assert_eq!(parse_utc_time("12"), (0, 0, 0));
+        assert_eq!(parse_utc_time("abc"), (0, 0, 0, 0));
+        assert_eq!(parse_utc_time("12"), (0, 0, 0, 0));
 
         // Test invalid values
-        assert_eq!(parse_utc_time("246101"), (0, 0, 0));
+        assert_eq!(parse_utc_time("246101"), (0, 0, 0, 0));
+        assert_eq!(parse_utc_time("ab3519"), (0, 0, 0, 0));
+
+        // A corrupt byte in a lossily decoded frame becomes a multi-byte U+FFFD
+        assert_eq!(parse_utc_time("1\u{FFFD}3519"), (0, 0, 0, 0));
+        assert_eq!(parse_date("2\u{FFFD}94"), (0, 0, 0));
+        let line = String::from_utf8_lossy(
+            b"GPRMC,1\xff3519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W",
+        );
+        assert!(matches!(
+            parse_nmea_sentence(&line),
+            Some(ParsedData::Rmc(RmcData { hour: 0, .. }))
+        ));
 
         // Test valid values
-        assert_eq!(parse_utc_time("235959"), (23, 59, 59));
-        assert_eq!(parse_utc_time("000000"), (0, 0, 0));
+        assert_eq!(parse_utc_time("235959"), (23, 59, 59, 0));
+        assert_eq!(parse_utc_time("000000"), (0, 0, 0, 0));
     }
 
     #[test]
@@ -817,29 +5098,23 @@ mod tests {
 
     #[test]
     fn test_gsa_parsing_invalid_input() {
-        let config = get_test_config();
-        let mqtt = mqtt::Client::new("tcp://localhost:1883").unwrap();
-
         // Test with empty data
         let data = "GNGSA,,,,,,,,,,,,,,,,,";
-        parse_and_display_gsa(data, mqtt.clone(), &config);
+        parse_and_publish(data);
 
         // Test with invalid fix type
         let data = "GNGSA,A,9,04,05,,09,12,,24,,,,,1.8,1.0,1.5*33";
-        parse_and_display_gsa(data, mqtt, &config);
+        parse_and_publish(data);
     }
 
     #[test]
     fn test_gll_parsing_invalid_input() {
-        let config = get_test_config();
-        let mqtt = mqtt::Client::new("tcp://localhost:1883").unwrap();
-
         // Test with insufficient fields
         let data = "GNGLL,4916.45,N,12311.12";
-        parse_and_display_gll(data, mqtt.clone(), &config);
+        parse_and_publish(data);
 
         // Test with invalid coordinates
         let data = "GNGLL,invalid,N,invalid,W,225444,A";
-        parse_and_display_gll(data, mqtt, &config);
+        parse_and_publish(data);
     }
 }
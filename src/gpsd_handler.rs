@@ -0,0 +1,138 @@
+use crate::config::AppConfig;
+use crate::frame_reader::Frame;
+use crate::mqtt_handler::retry_with_backoff;
+use crate::serial_port_handler::quit_channel;
+use log::{error, info, warn};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Asks gpsd to relay the raw NMEA sentences of its receivers.
+const GPSD_WATCH_COMMAND: &str = "?WATCH={\"enable\":true,\"nmea\":true}\n";
+
+/// Number of connection attempts made before giving up on gpsd.
+const GPSD_CONNECT_ATTEMPTS: u32 = 5;
+/// Delay before the second connection attempt, doubled for each further attempt.
+const GPSD_CONNECT_BASE_DELAY: Duration = Duration::from_millis(500);
+/// Read timeout, bounding how long a quit command waits while gpsd is silent.
+const GPSD_READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Why `relay_nmea` returned.
+#[derive(Debug, PartialEq)]
+enum StreamEnd {
+    Quit,
+    Disconnected,
+}
+
+/// Read NMEA sentences relayed by gpsd and process them.
+///
+/// Connects to gpsd at `gpsd_host:gpsd_port` and enables NMEA watching. When gpsd restarts,
+/// the connection is re-established with the same bounded backoff used for MQTT reconnects.
+/// The loop exits when 'q' is entered on stdin, the process receives SIGINT/SIGTERM, or gpsd
+/// stays unreachable.
+///
+/// # Arguments
+///
+/// * `config` - A reference to the `AppConfig` struct.
+/// * `handler` - Called with every sentence, e.g. the `publishing_handler`.
+pub fn read_from_gpsd(config: &AppConfig, handler: &dyn Fn(&Frame)) {
+    let address = format!("{}:{}", config.gpsd_host, config.gpsd_port);
+    let quit = quit_channel();
+
+    loop {
+        info!("Connecting to gpsd at {}", address);
+
+        let mut stream = None;
+        retry_with_backoff(GPSD_CONNECT_ATTEMPTS, GPSD_CONNECT_BASE_DELAY, || {
+            stream = connect_gpsd(&address)
+                .map_err(|e| warn!("gpsd connection attempt failed: {}", e))
+                .ok();
+            stream.is_some()
+        });
+
+        let Some(stream) = stream else {
+            error!(
+                "Unable to connect to gpsd at {} after {} attempts",
+                address, GPSD_CONNECT_ATTEMPTS
+            );
+            return;
+        };
+
+        match relay_nmea(BufReader::new(stream), &quit, handler) {
+            StreamEnd::Quit => return,
+            StreamEnd::Disconnected => warn!("Lost connection to gpsd, reconnecting"),
+        }
+    }
+}
+
+/// Connects to gpsd and sends the `?WATCH` command enabling NMEA output.
+fn connect_gpsd(address: &str) -> io::Result<TcpStream> {
+    let mut stream = TcpStream::connect(address)?;
+    stream.set_read_timeout(Some(GPSD_READ_TIMEOUT))?;
+    stream.write_all(GPSD_WATCH_COMMAND.as_bytes())?;
+    Ok(stream)
+}
+
+/// Passes the NMEA lines read from gpsd to `handler` until the quit command or a disconnect.
+///
+/// gpsd interleaves its own JSON reports with the relayed sentences; only lines starting
+/// with `$` are passed on.
+fn relay_nmea<R: BufRead>(
+    mut reader: R,
+    quit: &mpsc::Receiver<String>,
+    handler: &dyn Fn(&Frame),
+) -> StreamEnd {
+    // Kept across timeouts, which can interrupt a line halfway
+    let mut line = Vec::new();
+
+    loop {
+        if quit.try_recv().is_ok() {
+            info!("Received quit command. Exiting the program.");
+            return StreamEnd::Quit;
+        }
+
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => return StreamEnd::Disconnected,
+            Ok(_) => {
+                // A relayed line can hold several sentences of a receiver ending them with `\r`
+                for sentence in String::from_utf8_lossy(&line).split(['\r', '\n']) {
+                    let sentence = sentence.trim_end();
+                    if sentence.starts_with('$') {
+                        handler(&Frame::Nmea(sentence.to_string()));
+                    }
+                }
+                line.clear();
+            }
+            Err(ref e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) => {}
+            Err(e) => {
+                error!("gpsd read error: {}", e);
+                return StreamEnd::Disconnected;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_connect_gpsd_sends_watch_command() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        let _stream = connect_gpsd(&address).unwrap();
+        let (mut server, _) = listener.accept().unwrap();
+
+        let mut command = vec![0; GPSD_WATCH_COMMAND.len()];
+        server.read_exact(&mut command).unwrap();
+        assert_eq!(command, GPSD_WATCH_COMMAND.as_bytes());
+    }
+}
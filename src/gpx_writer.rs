@@ -0,0 +1,143 @@
+use crate::gps_data_parser::PositionFix;
+use log::{error, info};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+const GPX_HEADER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="gps-to-mqtt" xmlns="http://www.topografix.com/GPX/1/1">
+  <trk>
+    <name>gps-to-mqtt track</name>
+    <trkseg>
+"#;
+
+const GPX_FOOTER: &str = "    </trkseg>
+  </trk>
+</gpx>
+";
+
+lazy_static::lazy_static! {
+    /// The open track of every source, keyed by `device_id`.
+    static ref GPX_WRITERS: Mutex<HashMap<String, GpxWriter<BufWriter<File>>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Writes position fixes as a GPX 1.1 track.
+///
+/// The header and opening `<trkseg>` are written on creation and every point is flushed
+/// immediately, so a crash leaves a file that is only missing its closing tags.
+pub struct GpxWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> GpxWriter<W> {
+    /// Creates a GPX writer and writes the document header.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(GPX_HEADER.as_bytes())?;
+        writer.flush()?;
+        Ok(GpxWriter { writer })
+    }
+
+    /// Appends a `<trkpt>` for the fix.
+    pub fn write_point(&mut self, fix: &PositionFix) -> io::Result<()> {
+        writeln!(
+            self.writer,
+            "      <trkpt lat=\"{:.7}\" lon=\"{:.7}\"><ele>{:.1}</ele><time>{}</time></trkpt>",
+            fix.lat, fix.lon, fix.alt, fix.timestamp
+        )?;
+        self.writer.flush()
+    }
+
+    /// Writes the closing tags and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.write_all(GPX_FOOTER.as_bytes())?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Returns the track file of `device_id` when several sources are logged: `device_id` is added
+/// to the file name of `path`, so `track.gpx` becomes `track-position.gpx`.
+fn device_path(path: &str, device_id: &str) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let file_name = match path.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, device_id, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, device_id),
+    };
+    path.with_file_name(file_name)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Opens a GPX track file for every source, replacing any existing files.
+///
+/// A single source is logged to `path` itself. With several sources each gets its own file
+/// next to it, named after its `device_id`, so their tracks aren't mixed.
+///
+/// # Arguments
+///
+/// * `path` - Path of the `.gpx` file to write
+/// * `device_ids` - The `device_id` of every source
+pub fn init(path: &str, device_ids: &[String]) -> io::Result<()> {
+    let mut writers = HashMap::new();
+    for device_id in device_ids {
+        let path = match device_ids.len() {
+            1 => path.to_string(),
+            _ => device_path(path, device_id),
+        };
+        let writer = GpxWriter::new(BufWriter::new(File::create(&path)?))?;
+        writers.insert(device_id.clone(), writer);
+        info!("Logging GPX track of {} to {}", device_id, path);
+    }
+    *GPX_WRITERS.lock().unwrap() = writers;
+    Ok(())
+}
+
+/// Appends a fix to the GPX track of `device_id` if GPX logging is enabled.
+pub fn write_fix(fix: &PositionFix, device_id: &str) {
+    if let Some(writer) = GPX_WRITERS.lock().unwrap().get_mut(device_id) {
+        if let Err(e) = writer.write_point(fix) {
+            error!("Error writing GPX track point: {}", e);
+        }
+    }
+}
+
+/// Closes the GPX tracks cleanly if GPX logging is enabled.
+pub fn close() {
+    for (_, writer) in GPX_WRITERS.lock().unwrap().drain() {
+        if let Err(e) = writer.finish() {
+            error!("Error closing GPX track: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gps_data_parser::test_fix;
+
+    #[test]
+    fn test_gpx_document() {
+        let mut writer = GpxWriter::new(Vec::new()).unwrap();
+        writer.write_point(&test_fix()).unwrap();
+        let output = String::from_utf8(writer.finish().unwrap()).unwrap();
+
+        assert!(output.starts_with(GPX_HEADER));
+        assert!(output.contains(
+            "<trkpt lat=\"48.1173000\" lon=\"11.5166667\"><ele>545.4</ele><time>2024-03-23T12:35:19Z</time></trkpt>"
+        ));
+        assert!(output.ends_with(GPX_FOOTER));
+    }
+
+    #[test]
+    fn test_device_path() {
+        assert_eq!(
+            device_path("/var/log/track.gpx", "position"),
+            "/var/log/track-position.gpx"
+        );
+        assert_eq!(device_path("track", "timing"), "track-timing");
+    }
+}
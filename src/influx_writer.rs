@@ -0,0 +1,326 @@
+use crate::config::InfluxConfig;
+use crate::gps_data_parser::PositionFix;
+use log::{debug, error, info, warn};
+use std::sync::mpsc::{self, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Time allowed for one write request to InfluxDB.
+const INFLUX_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Fixes queued for the writer thread before new ones are dropped, e.g. while a request to an
+/// unreachable server is timing out.
+const INFLUX_QUEUE_SIZE: usize = 1000;
+
+/// Sender of the fixes and their `device_id` to the writer thread, and the thread, see `init`.
+type WriterThread = (SyncSender<(PositionFix, String)>, JoinHandle<()>);
+
+lazy_static::lazy_static! {
+    static ref INFLUX_WRITER: Mutex<Option<WriterThread>> = Mutex::new(None);
+}
+
+/// Batches position fixes as InfluxDB line protocol and writes them over HTTP.
+///
+/// A batch is written once it holds `batch_size` points or `flush_interval_secs` have passed
+/// since the last write. Failed batches are logged and dropped so an unreachable server can't
+/// grow the buffer without bound.
+pub struct InfluxWriter {
+    config: InfluxConfig,
+    write_url: String,
+    lines: Vec<String>,
+    last_flush: Instant,
+}
+
+impl InfluxWriter {
+    /// Creates a writer for the InfluxDB 2 write API at `config.url`.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The `[influxdb]` configuration section
+    pub fn new(config: InfluxConfig) -> Self {
+        let mut write_url = format!(
+            "{}/api/v2/write?bucket={}&precision=ns",
+            config.url.trim_end_matches('/'),
+            config.bucket
+        );
+        if let Some(org) = &config.org {
+            write_url.push_str(&format!("&org={}", org));
+        }
+
+        InfluxWriter {
+            config,
+            write_url,
+            lines: Vec::new(),
+            last_flush: Instant::now(),
+        }
+    }
+
+    /// Adds a fix to the batch, tagged with the `device_id` of its source, and writes the batch
+    /// when it is full or due.
+    ///
+    /// Fixes whose timestamp can't be parsed are skipped.
+    pub fn add_fix(&mut self, fix: &PositionFix, device_id: &str) {
+        match format_line(&self.config.measurement, device_id, fix) {
+            Some(line) => self.lines.push(line),
+            None => debug!("Skipping fix without a valid timestamp for InfluxDB"),
+        }
+
+        if self.lines.len() >= self.config.batch_size || self.flush_due_in().is_zero() {
+            self.flush();
+        }
+    }
+
+    /// Time left until the partial batch is due, zero when it is.
+    fn flush_due_in(&self) -> Duration {
+        Duration::from_secs(self.config.flush_interval_secs)
+            .saturating_sub(self.last_flush.elapsed())
+    }
+
+    /// Adds the fixes received from `fixes` until the sending side is dropped, and writes each
+    /// partial batch once it is due even when no more fixes arrive.
+    fn run(mut self, fixes: mpsc::Receiver<(PositionFix, String)>) {
+        loop {
+            let received = if self.lines.is_empty() {
+                fixes.recv().map_err(|_| RecvTimeoutError::Disconnected)
+            } else {
+                fixes.recv_timeout(self.flush_due_in())
+            };
+            match received {
+                Ok((fix, device_id)) => self.add_fix(&fix, &device_id),
+                Err(RecvTimeoutError::Timeout) => self.flush(),
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        self.flush();
+    }
+
+    /// Writes the buffered points, if any.
+    pub fn flush(&mut self) {
+        self.last_flush = Instant::now();
+        if self.lines.is_empty() {
+            return;
+        }
+
+        let body = self.lines.join("\n");
+        let mut request = ureq::post(&self.write_url)
+            .timeout(INFLUX_REQUEST_TIMEOUT)
+            .set("Content-Type", "text/plain; charset=utf-8");
+        if let Some(token) = &self.config.token {
+            request = request.set("Authorization", &format!("Token {}", token));
+        }
+
+        match request.send_string(&body) {
+            Ok(_) => debug!("Wrote {} points to InfluxDB", self.lines.len()),
+            Err(e) => error!(
+                "Error writing {} points to InfluxDB: {}",
+                self.lines.len(),
+                e
+            ),
+        }
+        self.lines.clear();
+    }
+}
+
+/// Formats a fix as one line of InfluxDB line protocol.
+///
+/// For example `gps,source=car lat=48.1173,lon=11.5166,alt=545.4,speed=22.4,course=84.4,fix_quality=1i,sats_used=8i 1711197319000000000`,
+/// with the speed in knots and the timestamp in nanoseconds.
+///
+/// # Returns
+///
+/// * `Option<String>` - The line, or `None` if the fix timestamp can't be parsed
+pub fn format_line(measurement: &str, source: &str, fix: &PositionFix) -> Option<String> {
+    let timestamp_ns = timestamp_ns(&fix.timestamp)?;
+
+    Some(format!(
+        "{},source={} lat={},lon={},alt={},speed={},course={},fix_quality={}i,sats_used={}i {}",
+        escape_key(measurement),
+        escape_key(source),
+        fix.lat,
+        fix.lon,
+        fix.alt,
+        fix.speed,
+        fix.course,
+        fix.fix_quality,
+        fix.sats_used,
+        timestamp_ns
+    ))
+}
+
+/// Escapes commas, spaces and equals signs in a line protocol measurement or tag.
+fn escape_key(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, ',' | ' ' | '=') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Converts a `YYYY-MM-DDTHH:MM:SS[.sss]Z` fix timestamp to nanoseconds since the unix epoch.
+fn timestamp_ns(timestamp: &str) -> Option<i64> {
+    let (date_time, fraction) = match timestamp.strip_suffix('Z')?.split_once('.') {
+        Some((date_time, fraction)) => (date_time, fraction),
+        None => (timestamp.strip_suffix('Z')?, ""),
+    };
+    let (date, time) = date_time.split_once('T')?;
+
+    let mut date = date.split('-').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+    let mut time = time.split(':').map(|part| part.parse::<i64>().ok());
+    let (hour, minute, second) = (time.next()??, time.next()??, time.next()??);
+
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // Up to nanosecond digits, right-padded so ".25" is 250ms
+    if fraction.len() > 9 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let nanos = format!("{:0<9}", fraction).parse::<i64>().ok()?;
+
+    let seconds = days_from_civil(year, month, day) * 86_400 + hour * 3_600 + minute * 60 + second;
+    Some(seconds * 1_000_000_000 + nanos)
+}
+
+/// Number of days from 1970-01-01 to the given proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Starts batching fixes to InfluxDB.
+///
+/// The requests are made by a writer thread, so a slow or unreachable server doesn't hold up
+/// reading the receiver.
+///
+/// # Arguments
+///
+/// * `config` - The `[influxdb]` configuration section
+pub fn init(config: &InfluxConfig) {
+    info!(
+        "Writing fixes to InfluxDB at {} (bucket {})",
+        config.url, config.bucket
+    );
+    let writer = InfluxWriter::new(config.clone());
+    let (sender, fixes) = mpsc::sync_channel(INFLUX_QUEUE_SIZE);
+    let handle = thread::spawn(move || writer.run(fixes));
+    *INFLUX_WRITER.lock().unwrap() = Some((sender, handle));
+}
+
+/// Queues a fix for the InfluxDB batch if the InfluxDB sink is enabled, with `device_id` as its
+/// `source` tag so the fixes of several sources stay apart.
+///
+/// The fix is dropped when the writer thread has fallen `INFLUX_QUEUE_SIZE` fixes behind.
+pub fn write_fix(fix: &PositionFix, device_id: &str) {
+    if let Some((sender, _)) = INFLUX_WRITER.lock().unwrap().as_ref() {
+        match sender.try_send((fix.clone(), device_id.to_string())) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => warn!("InfluxDB writer is behind, dropping a fix"),
+            Err(TrySendError::Disconnected(_)) => error!("InfluxDB writer thread has stopped"),
+        }
+    }
+}
+
+/// Writes the remaining buffered points and stops the writer thread if the InfluxDB sink is
+/// enabled.
+pub fn close() {
+    if let Some((sender, handle)) = INFLUX_WRITER.lock().unwrap().take() {
+        drop(sender);
+        if handle.join().is_err() {
+            error!("InfluxDB writer thread panicked");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gps_data_parser::test_fix;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_timestamp_ns() {
+        assert_eq!(timestamp_ns("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(
+            timestamp_ns("2024-03-23T12:35:19Z"),
+            Some(1_711_197_319_000_000_000)
+        );
+        // Leap day
+        assert_eq!(
+            timestamp_ns("2024-02-29T00:00:00Z"),
+            Some(1_709_164_800_000_000_000)
+        );
+        assert_eq!(
+            timestamp_ns("2024-03-23T12:35:19.250Z"),
+            Some(1_711_197_319_250_000_000)
+        );
+        assert_eq!(timestamp_ns("2024-03-23T12:35:19.2x0Z"), None);
+        assert_eq!(timestamp_ns("2024-13-01T00:00:00Z"), None);
+        assert_eq!(timestamp_ns(""), None);
+    }
+
+    #[test]
+    fn test_format_line() {
+        let fix = PositionFix {
+            lon: 11.5166,
+            ..test_fix()
+        };
+
+        assert_eq!(
+            format_line("gps", "my car", &fix).unwrap(),
+            "gps,source=my\\ car lat=48.1173,lon=11.5166,alt=545.4,speed=22.4,course=84.4,fix_quality=1i,sats_used=8i 1711197319000000000"
+        );
+    }
+
+    #[test]
+    fn test_partial_batch_written_on_timer() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let config = InfluxConfig {
+            url: format!("http://{}", listener.local_addr().unwrap()),
+            bucket: "telemetry".to_string(),
+            org: None,
+            token: None,
+            measurement: "gps".to_string(),
+            batch_size: 100,
+            flush_interval_secs: 1,
+        };
+        let fix = PositionFix {
+            lon: 11.5166,
+            ..test_fix()
+        };
+
+        let (sender, fixes) = mpsc::sync_channel(INFLUX_QUEUE_SIZE);
+        let writer = InfluxWriter::new(config);
+        let handle = thread::spawn(move || writer.run(fixes));
+        sender.send((fix, "car".to_string())).unwrap();
+
+        // Written without another fix arriving
+        let (mut server, _) = listener.accept().unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0; 1024];
+        while !String::from_utf8_lossy(&request).ends_with("1711197319000000000") {
+            let n = server.read(&mut buf).unwrap();
+            assert!(n > 0, "{}", String::from_utf8_lossy(&request));
+            request.extend_from_slice(&buf[..n]);
+        }
+        let request = String::from_utf8(request).unwrap();
+        assert!(request.starts_with("POST /api/v2/write?bucket=telemetry&precision=ns "));
+        assert!(request.contains("\r\n\r\ngps,source=car lat=48.1173,"));
+        server
+            .write_all(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n")
+            .unwrap();
+
+        drop(sender);
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,85 @@
+//! # GPS to MQTT
+//!
+//! Reads NMEA sentences from a serial GPS receiver, gpsd or UDP and publishes the parsed data to
+//! MQTT. The `gps-to-mqtt` binary is a thin layer over this library; embedding applications can
+//! receive the parsed sentences directly with `run_with_handler`.
+//!
+//! ## Modules
+//!
+//! - `config`: Module for configuration settings.
+//! - `csv_writer`: Module for logging position fixes to a CSV file.
+//! - `frame_reader`: Module for splitting the serial byte stream into NMEA and UBX frames.
+//! - `geodesy`: Module for distance calculations between coordinates.
+//! - `geofence`: Module for publishing geofence enter/exit events.
+//! - `gps_data_parser`: Module for parsing GPS data.
+//! - `gpsd_handler`: Module for reading NMEA sentences relayed by gpsd.
+//! - `gpx_writer`: Module for logging the track to a GPX file.
+//! - `influx_writer`: Module for writing position fixes to InfluxDB.
+//! - `mdns_advertiser`: Module for advertising the MQTT feed via mDNS (`mdns` feature).
+//! - `mqtt_handler`: Module for handling MQTT communication.
+//! - `reload_handler`: Module for reloading the configuration on SIGHUP.
+//! - `replay_handler`: Module for replaying recorded NMEA log files.
+//! - `selftest`: Module for feeding sample sentences through the pipeline with `--selftest`.
+//! - `serial_port_handler`: Module for handling serial communication with the GPS device.
+//! - `sqlite_writer`: Module for logging position fixes to a SQLite database (`sqlite` feature).
+//! - `stdout_writer`: Module for streaming sentences or fixes to stdout with `--stdout-format`.
+//! - `speed_conversions`: Module for converting speeds from knots to other units.
+//! - `ubx`: Module for building and validating u-blox UBX binary frames.
+//! - `udp_handler`: Module for receiving NMEA sentences broadcast over UDP.
+
+pub mod config;
+pub mod csv_writer;
+pub mod frame_reader;
+pub mod geodesy;
+pub mod geofence;
+pub mod gps_data_parser;
+pub mod gpsd_handler;
+pub mod gpx_writer;
+pub mod influx_writer;
+#[cfg(feature = "mdns")]
+pub mod mdns_advertiser;
+pub mod mqtt_handler;
+pub mod reload_handler;
+pub mod replay_handler;
+pub mod selftest;
+pub mod serial_port_handler;
+pub mod speed_conversions;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_writer;
+pub mod stdout_writer;
+pub mod ubx;
+pub mod udp_handler;
+
+pub use config::AppConfig;
+pub use gps_data_parser::ParsedData;
+
+use config::SourceType;
+use gps_data_parser::parsing_handler;
+use gpsd_handler::read_from_gpsd;
+use serial_port_handler::{read_from_port, set_quit_keyword, setup_serial_port};
+use udp_handler::read_from_udp;
+
+/// Reads from the configured source and calls `handler` with each parsed NMEA sentence.
+///
+/// Opens the serial port, connects to gpsd or listens for UDP datagrams according to the
+/// top-level `source_type`, like the binary does, but nothing is published to MQTT.
+/// `[[sources]]` are not read. Blocks until the `quit_keyword` is entered on stdin, stdin is
+/// closed, the process receives SIGINT/SIGTERM, or gpsd stays unreachable.
+///
+/// # Arguments
+///
+/// * `config` - Configuration settings, of which only the source settings are used.
+/// * `handler` - Called with every sentence that could be parsed.
+pub fn run_with_handler(config: &AppConfig, handler: impl Fn(ParsedData)) {
+    let handler = parsing_handler(handler);
+    set_quit_keyword(&config.quit_keyword);
+
+    match config.source_type {
+        SourceType::Serial => {
+            let mut port = setup_serial_port(config);
+            read_from_port(&mut port, config, &handler);
+        }
+        SourceType::Gpsd => read_from_gpsd(config, &handler),
+        SourceType::Udp => read_from_udp(config, &handler),
+    }
+}
@@ -1,12 +1,28 @@
-mod config;
-mod gps_data_parser;
-mod mqtt_handler;
-mod serial_port_handler;
-
-use config::load_configuration;
-use config::AppConfig;
+use gps_to_mqtt::config::write_config_template;
+use gps_to_mqtt::config::{load_configuration_with_path, log_config_files};
+use gps_to_mqtt::config::{AppConfig, SourceType};
+use gps_to_mqtt::gps_data_parser::{fix_timed_out, live_publishing_handler, publishing_handler};
+use gps_to_mqtt::gpsd_handler::read_from_gpsd;
+use gps_to_mqtt::mqtt_handler::{
+    disconnect_mqtt, setup_mqtt, start_heartbeat, start_trip_reset_listener, DryRunPublisher,
+    Heartbeat, Publisher,
+};
+use gps_to_mqtt::reload_handler::watch_reloads;
+use gps_to_mqtt::replay_handler::replay_file;
+use gps_to_mqtt::selftest::run_selftest;
+use gps_to_mqtt::serial_port_handler::{
+    broadcast_quit, list_serial_ports, quit_requested, read_from_port, set_quit_keyword,
+    setup_serial_port,
+};
+use gps_to_mqtt::stdout_writer::{self, StdoutFormat};
+use gps_to_mqtt::udp_handler::read_from_udp;
+use gps_to_mqtt::{csv_writer, gpx_writer, influx_writer};
 use gumdrop::Options;
-use serial_port_handler::{read_from_port, setup_serial_port};
+use log::{error, info, LevelFilter};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::thread;
 
 /// # GPS Data Processor
 ///
@@ -16,22 +32,19 @@ use serial_port_handler::{read_from_port, setup_serial_port};
 ///
 /// ## Usage
 ///
-/// Simply run the application, and it will establish communication with the GPS device. Press 'q' to quit the application.
+/// Simply run the application, and it will establish communication with the GPS device. Enter the `quit_keyword` ('q' by default) or close stdin to quit the application.
 ///
 /// ## Modules
 ///
-/// - `config`: Module for configuration settings.
-/// - `gps_data_parser`: Module for parsing GPS data.
-/// - `mqtt_handler`: Module for handling MQTT communication.
-/// - `serial_port_handler`: Module for handling serial communication with the GPS device.
+/// The modules live in the `gps_to_mqtt` library, see `lib.rs`.
 ///
 /// ## Functions
 ///
 /// - `main()`: The main function that loads configuration, sets up serial communication, and starts reading data from the port.
-/// - `display_welcome()`: Function to display a graphical welcome message.
+/// - `display_welcome(quit_keyword)`: Function to display a graphical welcome message.
 
-/// Displays a graphical welcome message.
-fn display_welcome() {
+/// Displays a graphical welcome message, naming the configured `quit_keyword`.
+fn display_welcome(quit_keyword: &str) {
     println!("\nWelcome to GPS Data Processor!\n");
     // satellite in red
     println!(
@@ -80,7 +93,10 @@ fn display_welcome() {
     println!("This application reads GPS data from a specified source and publishes it to an MQTT broker.");
     println!("Use the options below to interact with the application.\x1b[0m");
     println!("==========================================");
-    println!("Press 'q' + Enter to quit the application.");
+    println!(
+        "Press '{}' + Enter or Ctrl-C to quit the application.",
+        quit_keyword
+    );
     println!("==========================================\n");
 }
 
@@ -90,8 +106,65 @@ struct MyOptions {
     #[options(help = "print help message")]
     help: bool,
 
+    #[options(short = "V", help = "Print version information and exit")]
+    version: bool,
+
     #[options(help = "Sets a custom config file", meta = "FILE")]
     config: Option<String>,
+
+    #[options(count, help = "Increase log verbosity (repeat for more)")]
+    verbose: u32,
+
+    #[options(help = "Don't print the welcome banner")]
+    quiet: bool,
+
+    #[options(no_short, help = "Don't print the welcome banner")]
+    no_banner: bool,
+
+    #[options(no_short, help = "List available serial ports and exit")]
+    list_ports: bool,
+
+    #[options(
+        no_short,
+        help = "Replay NMEA sentences from a log file",
+        meta = "FILE"
+    )]
+    replay: Option<String>,
+
+    #[options(no_short, help = "Pace replay by the sentence timestamps")]
+    realtime: bool,
+
+    #[options(
+        no_short,
+        help = "Write a commented settings template and exit",
+        meta = "PATH"
+    )]
+    init_config: Option<String>,
+
+    #[options(no_short, help = "Overwrite an existing file with --init-config")]
+    force: bool,
+
+    #[options(
+        no_short,
+        help = "Print the messages to stdout instead of connecting to MQTT"
+    )]
+    dry_run: bool,
+
+    #[options(
+        no_short,
+        help = "Publish built-in sample sentences and exit, nonzero on failure"
+    )]
+    selftest: bool,
+
+    #[options(no_short, help = "Print the resolved configuration as JSON and exit")]
+    print_config: bool,
+
+    #[options(
+        no_short,
+        help = "Also write valid sentences (nmea) or one JSON object per fix (jsonl) to stdout",
+        meta = "FORMAT"
+    )]
+    stdout_format: Option<StdoutFormat>,
 }
 
 /// Prints the help message for the GPS Data Processor application.
@@ -104,7 +177,26 @@ fn print_help() {
     println!("Usage: gps-to-mqtt [options]");
     println!("Options:");
     println!("  -h, --help               Print this help message");
+    println!("  -V, --version            Print version information and exit");
     println!("  -c, --config FILE        Sets a custom config file path");
+    println!("  -v, --verbose            Increase log verbosity (repeat for more)");
+    println!("  -q, --quiet              Don't print the welcome banner");
+    println!("      --no-banner          Don't print the welcome banner");
+    println!("      --list-ports         List available serial ports and exit");
+    println!("      --replay FILE        Replay NMEA sentences from a log file");
+    println!("      --realtime           Pace replay by the sentence timestamps");
+    println!("      --init-config PATH   Write a commented settings template and exit");
+    println!("      --force              Overwrite an existing file with --init-config");
+    println!(
+        "      --dry-run            Print the messages to stdout instead of connecting to MQTT"
+    );
+    println!(
+        "      --selftest           Publish built-in sample sentences and exit, nonzero on failure"
+    );
+    println!("      --print-config       Print the resolved configuration as JSON and exit");
+    println!(
+        "      --stdout-format FORMAT  Also write valid sentences (nmea) or one JSON object per fix (jsonl) to stdout"
+    );
 }
 
 /// The main entry point of the application.
@@ -118,12 +210,231 @@ fn main() {
         print_help_and_exit();
     }
 
-    display_welcome();
+    if opts.version {
+        print_version_and_exit();
+    }
+
+    if opts.list_ports {
+        list_ports_and_exit();
+    }
+
+    if let Some(path) = &opts.init_config {
+        init_config_and_exit(path, opts.force);
+    }
+
+    if opts.print_config {
+        print_config_and_exit(opts.config.as_deref());
+    }
+
+    if opts.stdout_format.is_some() && opts.dry_run {
+        eprintln!("--stdout-format can't be combined with --dry-run, which prints to stdout too");
+        std::process::exit(1);
+    }
+
+    let (config, config_file) = load_config_or_exit(opts.config.as_deref());
+    set_quit_keyword(&config.quit_keyword);
+
+    // The banner would end up in logs and break tools reading stdout when it isn't a terminal
+    if !opts.quiet
+        && !opts.no_banner
+        && opts.stdout_format.is_none()
+        && std::io::stdout().is_terminal()
+    {
+        display_welcome(&config.quit_keyword);
+    }
+
+    init_logger(config.log_level, opts.verbose);
+    log_config_files(opts.config.as_deref(), config_file.as_deref());
+    log_startup(&config, &opts);
+
+    if opts.selftest {
+        selftest_and_exit(&config, opts.dry_run);
+    }
+
+    if let Some(format) = opts.stdout_format {
+        stdout_writer::init(format);
+    }
+
+    if let Some(path) = &config.gpx_output_path {
+        // A replay is published under the top-level device_id, even with [[sources]]
+        let device_ids: Vec<_> = match opts.replay {
+            Some(_) => vec![config.device_id.clone()],
+            None => config
+                .source_configs()
+                .into_iter()
+                .map(|source| source.device_id)
+                .collect(),
+        };
+        if let Err(e) = gpx_writer::init(path, &device_ids) {
+            eprintln!("Failed to open GPX output file {}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(path) = &config.csv_output_path {
+        if let Err(e) = csv_writer::init(path) {
+            eprintln!("Failed to open CSV output file {}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(influxdb) = &config.influxdb {
+        influx_writer::init(influxdb);
+    }
+
+    if let Some(path) = &config.sqlite_path {
+        #[cfg(feature = "sqlite")]
+        if let Err(e) = gps_to_mqtt::sqlite_writer::init(path) {
+            eprintln!("Failed to open SQLite database {}: {}", path, e);
+            std::process::exit(1);
+        }
+        #[cfg(not(feature = "sqlite"))]
+        log::warn!(
+            "sqlite_path {} is ignored, gps-to-mqtt was built without the sqlite feature",
+            path
+        );
+    }
+
+    let mut sources_failed = false;
+    if let Some(path) = &opts.replay {
+        run_with_publisher(&config, opts.dry_run, |mqtt, _| {
+            let handler = publishing_handler(&config, mqtt);
+            if let Err(e) = replay_file(path, opts.realtime, &handler) {
+                eprintln!("Failed to replay NMEA log {}: {}", path, e);
+            }
+        });
+    } else {
+        // Open the serial ports up front, so a missing device fails before connecting to MQTT
+        let sources: Vec<_> = config
+            .source_configs()
+            .into_iter()
+            .map(|source| {
+                let port =
+                    (source.source_type == SourceType::Serial).then(|| setup_serial_port(&source));
+                (source, port)
+            })
+            .collect();
+        // Written by `watch_reloads` on SIGHUP, read by the publishing handlers
+        let live_sources: Vec<_> = sources
+            .iter()
+            .map(|(source, _)| RwLock::new(source.clone()))
+            .collect();
+
+        run_with_publisher(&config, opts.dry_run, |mqtt, heartbeat| {
+            // Each source is read in its own thread; the quit command stops all of them
+            thread::scope(|scope| {
+                let readers: Vec<_> = sources
+                    .into_iter()
+                    .zip(&live_sources)
+                    .map(|((source, port), live_source)| {
+                        scope.spawn(move || {
+                            let handler = live_publishing_handler(live_source, mqtt);
+                            match port {
+                                Some(mut port) => read_from_port(&mut port, &source, &handler),
+                                None if source.source_type == SourceType::Udp => {
+                                    read_from_udp(&source, &handler)
+                                }
+                                None => read_from_gpsd(&source, &handler),
+                            }
+                        })
+                    })
+                    .collect();
+
+                scope.spawn(|| {
+                    watch_reloads(opts.config.as_deref(), &config, &live_sources, |reloaded| {
+                        apply_reload(reloaded, heartbeat, opts.verbose)
+                    })
+                });
+
+                // A panicking reader has already reported itself and counts as stopped
+                for reader in readers {
+                    let _ = reader.join();
+                }
+                // Without the quit command every source gave up, e.g. after
+                // `max_consecutive_failures`, so stop the reload watcher and exit with an error
+                if !quit_requested() {
+                    error!("All sources stopped, exiting");
+                    sources_failed = true;
+                    broadcast_quit();
+                }
+            });
+        });
+    }
+
+    gpx_writer::close();
+    csv_writer::close();
+    influx_writer::close();
+    #[cfg(feature = "sqlite")]
+    gps_to_mqtt::sqlite_writer::close();
+
+    if sources_failed || fix_timed_out() {
+        std::process::exit(1);
+    }
+}
+
+/// Logs a single machine-readable startup line with the version, source and broker.
+fn log_startup(config: &AppConfig, opts: &MyOptions) {
+    let source = match &opts.replay {
+        Some(path) => format!("replay:{}", path),
+        None => config
+            .source_configs()
+            .iter()
+            .map(|source| match source.source_type {
+                SourceType::Serial => format!("serial:{}@{}", source.port_name, source.baud_rate),
+                SourceType::Gpsd => format!("gpsd:{}:{}", source.gpsd_host, source.gpsd_port),
+                SourceType::Udp => format!("udp:{}", source.udp_port),
+            })
+            .collect::<Vec<_>>()
+            .join(","),
+    };
+    let broker = if opts.dry_run {
+        "dry-run".to_string()
+    } else {
+        format!("{}:{}", config.mqtt_host, config.mqtt_port)
+    };
+
+    info!(
+        "gps-to-mqtt version={} source={} broker={}",
+        env!("CARGO_PKG_VERSION"),
+        source,
+        broker
+    );
+}
+
+/// Connects to the MQTT broker, runs `run` with the client and the heartbeat, and disconnects
+/// cleanly afterwards.
+///
+/// The heartbeat and the mDNS advertisement run for as long as `run` does. With `dry_run`, no connection is made and `run`
+/// gets a `DryRunPublisher` printing each message instead, and no heartbeat.
+fn run_with_publisher(
+    config: &AppConfig,
+    dry_run: bool,
+    run: impl FnOnce(&dyn Publisher, Option<&Heartbeat>),
+) {
+    if dry_run {
+        run(&DryRunPublisher, None);
+        return;
+    }
+
+    let mqtt = setup_mqtt(config);
+    let heartbeat = start_heartbeat(mqtt.clone(), config);
+    start_trip_reset_listener(mqtt.clone(), &config.source_configs());
+    #[cfg(feature = "mdns")]
+    let advertisement = gps_to_mqtt::mdns_advertiser::advertise(config);
+    #[cfg(not(feature = "mdns"))]
+    if config.mdns_advertise {
+        log::warn!("mdns_advertise is ignored, gps-to-mqtt was built without the mdns feature");
+    }
+
+    run(&mqtt, Some(&heartbeat));
 
-    let config = load_config_or_exit(opts.config.as_deref());
+    #[cfg(feature = "mdns")]
+    if let Some(advertisement) = advertisement {
+        advertisement.stop();
+    }
 
-    let mut port = setup_serial_port(&config);
-    read_from_port(&mut port, &config);
+    heartbeat.stop();
+    disconnect_mqtt(&mqtt);
 }
 
 /// Parses the command-line arguments using the gumdrop crate.
@@ -147,10 +458,160 @@ fn print_help_and_exit() {
     std::process::exit(0);
 }
 
+/// Prints the crate version, followed by the git commit hash when built from a checkout, and
+/// exits the program with a status code of 0.
+fn print_version_and_exit() {
+    match option_env!("GIT_HASH") {
+        Some(hash) => println!("gps-to-mqtt {} ({})", env!("CARGO_PKG_VERSION"), hash),
+        None => println!("gps-to-mqtt {}", env!("CARGO_PKG_VERSION")),
+    }
+    std::process::exit(0);
+}
+
+/// Lists the available serial ports and exits the program.
+///
+/// No configuration is needed, so this works before a config file exists. Exits with a status
+/// code of 0, or 1 if the ports cannot be enumerated.
+fn list_ports_and_exit() {
+    if let Err(e) = list_serial_ports() {
+        eprintln!("Error listing serial ports: {}", e);
+        std::process::exit(1);
+    }
+    std::process::exit(0);
+}
+
+/// Writes the configuration template to `path` and exits the program.
+///
+/// Exits with a status code of 0 after printing the path, or 1 if the file already exists
+/// (without `force`) or cannot be written.
+fn init_config_and_exit(path: &str, force: bool) {
+    match write_config_template(Path::new(path), force) {
+        Ok(()) => {
+            println!("Wrote configuration template to {}", path);
+            std::process::exit(0);
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            eprintln!("{} already exists, pass --force to overwrite it", path);
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("Failed to write configuration template to {}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Prints the configuration a normal run would use and exits the program.
+///
+/// The configuration is loaded and validated with `load_config_or_exit`, then printed as pretty
+/// JSON together with the file it was read from (`null` when no default file was found).
+/// Secrets are redacted. Exits with a status code of 0, or 1 when the configuration is invalid.
+fn print_config_and_exit(config_path: Option<&str>) {
+    let (config, path) = load_config_or_exit(config_path);
+
+    /// Output of `--print-config`, keeping the fields in declaration order.
+    #[derive(serde::Serialize)]
+    struct ResolvedConfig {
+        config_file: Option<PathBuf>,
+        config: AppConfig,
+    }
+
+    let resolved = ResolvedConfig {
+        config_file: path,
+        config,
+    };
+    match serde_json::to_string_pretty(&resolved) {
+        Ok(json) => println!("{}", json),
+        Err(e) => {
+            eprintln!("Failed to serialize the configuration: {}", e);
+            std::process::exit(1);
+        }
+    }
+    std::process::exit(0);
+}
+
+/// Publishes the built-in sample sentences with `run_selftest` and exits the program.
+///
+/// A line is printed for each sentence type with the number of messages published. Exits with a
+/// status code of 0 when every sentence was published, or 1 otherwise. With `dry_run`, the
+/// messages are printed instead and no broker is needed.
+fn selftest_and_exit(config: &AppConfig, dry_run: bool) {
+    let mut results = Vec::new();
+    run_with_publisher(config, dry_run, |mqtt, _| {
+        results = run_selftest(config, mqtt);
+    });
+
+    for result in &results {
+        println!(
+            "{} {}: {} published, {} failed",
+            if result.passed() { "ok  " } else { "FAIL" },
+            result.sentence_type,
+            result.published,
+            result.failed
+        );
+    }
+
+    let passed = results.iter().filter(|result| result.passed()).count();
+    println!(
+        "Self-test: {}/{} sentences published",
+        passed,
+        results.len()
+    );
+    std::process::exit(if passed == results.len() { 0 } else { 1 });
+}
+
+/// Initializes the logger writing to stderr.
+///
+/// Each `-v` raises `level` by one step up to `trace`. Directives in `RUST_LOG` are applied
+/// on top and take precedence. Without them, the level is only enforced through
+/// `log::set_max_level`, so `apply_reload` can raise it as well as lower it.
+///
+/// # Arguments
+///
+/// * `level` - The configured `log_level`.
+/// * `verbose` - How many times `-v` was given.
+fn init_logger(level: LevelFilter, verbose: u32) {
+    let level = verbose_level(level, verbose);
+
+    if std::env::var_os("RUST_LOG").is_some() {
+        env_logger::Builder::new()
+            .filter_level(level)
+            .parse_default_env()
+            .init();
+    } else {
+        env_logger::Builder::new()
+            .filter_level(LevelFilter::Trace)
+            .init();
+        log::set_max_level(level);
+    }
+}
+
+/// Returns `level` raised by one step for each `-v`, up to `trace`.
+fn verbose_level(level: LevelFilter, verbose: u32) -> LevelFilter {
+    LevelFilter::iter()
+        .skip_while(|l| *l != level)
+        .nth(verbose as usize)
+        .unwrap_or(LevelFilter::Trace)
+}
+
+/// Applies the reloaded settings that aren't part of the source configurations: the log level,
+/// unless `RUST_LOG` is set, and the heartbeat.
+fn apply_reload(config: &AppConfig, heartbeat: Option<&Heartbeat>, verbose: u32) {
+    if std::env::var_os("RUST_LOG").is_some() {
+        info!("RUST_LOG is set, keeping its log levels");
+    } else {
+        log::set_max_level(verbose_level(config.log_level, verbose));
+    }
+
+    if let Some(heartbeat) = heartbeat {
+        heartbeat.update(config);
+    }
+}
+
 /// Loads the configuration from the specified path or exits the program on error.
 ///
 /// This function attempts to load the configuration from the given path. If the
-/// configuration cannot be loaded, it prints an error message and exits the program
+/// configuration cannot be loaded or fails `AppConfig::validate`, it prints an error message and exits the program
 /// with a status code of 1.
 ///
 /// # Arguments
@@ -159,13 +620,20 @@ fn print_help_and_exit() {
 ///
 /// # Returns
 ///
-/// * `AppConfig` - The loaded configuration.
-fn load_config_or_exit(config_path: Option<&str>) -> AppConfig {
-    match load_configuration(config_path) {
-        Ok(config) => config,
+/// * `(AppConfig, Option<PathBuf>)` - The loaded configuration and the file it was read from.
+fn load_config_or_exit(config_path: Option<&str>) -> (AppConfig, Option<PathBuf>) {
+    let (config, path) = match load_configuration_with_path(config_path) {
+        Ok(loaded) => loaded,
         Err(err) => {
             eprintln!("Error loading configuration: {}", err);
             std::process::exit(1);
         }
+    };
+
+    if let Err(err) = config.validate() {
+        eprintln!("Invalid configuration: {}", err);
+        std::process::exit(1);
     }
+
+    (config, path)
 }
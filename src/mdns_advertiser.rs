@@ -0,0 +1,118 @@
+use crate::config::{system_hostname, AppConfig};
+use log::{error, info};
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+
+/// mDNS service type the MQTT feed is advertised as.
+const SERVICE_TYPE: &str = "_gps-mqtt._tcp.local.";
+
+/// A registered mDNS service, withdrawn again with `stop`.
+pub struct Advertisement {
+    daemon: ServiceDaemon,
+    fullname: String,
+}
+
+impl Advertisement {
+    /// Withdraws the service, so browsers drop it right away instead of waiting for its TTL.
+    pub fn stop(self) {
+        if let Err(e) = self.daemon.unregister(&self.fullname) {
+            error!("Failed to withdraw mDNS advertisement: {}", e);
+        }
+        if let Err(e) = self.daemon.shutdown() {
+            error!("Failed to stop mDNS daemon: {}", e);
+        }
+    }
+}
+
+/// Advertises the MQTT feed as a `_gps-mqtt._tcp` mDNS service when `mdns_advertise` is set.
+///
+/// The service is named after `device_id` and points at the broker port. Its TXT records carry
+/// the broker host and port and the base topic, see `txt_properties`, so dashboards on the local
+/// network can subscribe without manual configuration.
+///
+/// # Arguments
+///
+/// * `config` - A reference to the `AppConfig` struct.
+///
+/// # Returns
+///
+/// Returns the running `Advertisement`, or `None` when it is disabled or registration failed.
+pub fn advertise(config: &AppConfig) -> Option<Advertisement> {
+    if !config.mdns_advertise {
+        return None;
+    }
+
+    let hostname = system_hostname();
+    let port = u16::try_from(config.mqtt_port).unwrap_or(1883);
+    let properties = txt_properties(config, &hostname);
+    let result = ServiceDaemon::new().and_then(|daemon| {
+        let service = ServiceInfo::new(
+            SERVICE_TYPE,
+            &config.device_id,
+            &format!("{}.local.", hostname),
+            "",
+            port,
+            properties.as_slice(),
+        )?
+        .enable_addr_auto();
+        let fullname = service.get_fullname().to_string();
+        daemon.register(service)?;
+        Ok(Advertisement { daemon, fullname })
+    });
+
+    match result {
+        Ok(advertisement) => {
+            info!("Advertising {} via mDNS", advertisement.fullname);
+            Some(advertisement)
+        }
+        Err(e) => {
+            error!("Failed to advertise via mDNS: {}", e);
+            None
+        }
+    }
+}
+
+/// Returns the TXT records of the advertisement.
+///
+/// A broker on the loopback interface is only reachable by others through this machine, so
+/// `localhost` is replaced by the `.local` name of `hostname`.
+fn txt_properties(config: &AppConfig, hostname: &str) -> Vec<(&'static str, String)> {
+    let broker_host = match config.mqtt_host.as_str() {
+        "localhost" | "127.0.0.1" | "::1" => format!("{}.local", hostname),
+        host => host.to_string(),
+    };
+
+    vec![
+        ("broker_host", broker_host),
+        ("broker_port", config.mqtt_port.to_string()),
+        ("base_topic", config.mqtt_base_topic.clone()),
+        ("device_id", config.device_id.clone()),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::template_config;
+
+    #[test]
+    fn test_txt_properties() {
+        let mut config = template_config();
+        config.device_id = "golf86-pi".to_string();
+
+        assert_eq!(
+            txt_properties(&config, "raspberrypi"),
+            [
+                ("broker_host", "raspberrypi.local".to_string()),
+                ("broker_port", "1883".to_string()),
+                ("base_topic", "/GOLF86/GPS/".to_string()),
+                ("device_id", "golf86-pi".to_string()),
+            ]
+        );
+
+        config.mqtt_host = "broker.lan".to_string();
+        assert_eq!(
+            txt_properties(&config, "raspberrypi")[0],
+            ("broker_host", "broker.lan".to_string())
+        );
+    }
+}
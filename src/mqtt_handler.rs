@@ -1,5 +1,9 @@
-use log::{debug, error};
+use log::{debug, error, info, warn};
 use paho_mqtt as mqtt;
+use std::collections::HashMap;
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use std::{process, time::Duration};
 use thiserror::Error;
 
@@ -11,14 +15,146 @@ pub enum PublishError {
     MqttError(#[from] mqtt::Error),
     #[error("Empty topic or payload")]
     EmptyInput,
+    #[error("MQTT connection lost, reconnect failed after {0} attempts")]
+    Reconnecting(u32),
+    #[error("MQTT connection lost, not reconnecting until the cooldown after a failed reconnect")]
+    CoolingDown,
 }
 
-use crate::config::AppConfig;
+use crate::config::{system_hostname, AppConfig, MqttTransport};
+use crate::gps_data_parser::{fix_age_ms, reset_trip};
+
+/// Default payload of the Last Will and Testament message.
+const LWT_OFFLINE_PAYLOAD: &str = "offline";
+/// Payload published on the LWT topic right after a successful connect.
+const LWT_ONLINE_PAYLOAD: &str = "online";
+
+/// Number of reconnect attempts made when a publish finds the client disconnected.
+const RECONNECT_ATTEMPTS: u32 = 3;
+/// Delay before the second reconnect attempt, doubled for each further attempt.
+const RECONNECT_BASE_DELAY: Duration = Duration::from_millis(250);
+/// Time after a failed reconnect cycle during which publishes fail fast instead of retrying.
+const RECONNECT_COOLDOWN: Duration = Duration::from_secs(5);
+/// Time allowed for in-flight messages to complete when disconnecting.
+const DISCONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+/// Number of topics `publish_if_changed` remembers until `setup_mqtt` applies `change_cache_size`.
+const DEFAULT_CHANGE_CACHE_SIZE: usize = 4096;
+
+/// Last payload per topic, bounded to `capacity` topics.
+///
+/// When full, the least recently updated topic is evicted. Its next value is then published
+/// even if unchanged, which costs one redundant message but keeps memory bounded on long runs
+/// with many per-satellite topics.
+struct LastValues {
+    capacity: usize,
+    /// Payload and the value of `clock` when the topic was last updated
+    values: HashMap<String, (String, u64)>,
+    clock: u64,
+}
+
+impl LastValues {
+    fn new(capacity: usize) -> Self {
+        LastValues {
+            capacity: capacity.max(1),
+            values: HashMap::new(),
+            clock: 0,
+        }
+    }
+
+    /// Records `payload` as the last value of `topic`.
+    ///
+    /// # Returns
+    ///
+    /// Returns `true` if the payload differs from the remembered one or the topic is new.
+    fn update(&mut self, topic: &str, payload: &str) -> bool {
+        self.clock += 1;
+
+        if let Some((last, updated)) = self.values.get_mut(topic) {
+            *updated = self.clock;
+            if last == payload {
+                return false;
+            }
+            *last = payload.to_string();
+            return true;
+        }
+
+        while self.values.len() >= self.capacity {
+            self.evict_oldest();
+        }
+        self.values
+            .insert(topic.to_string(), (payload.to_string(), self.clock));
+        true
+    }
+
+    /// Changes the capacity, evicting topics if it shrinks.
+    fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+        while self.values.len() > self.capacity {
+            self.evict_oldest();
+        }
+    }
+
+    /// Removes the least recently updated topic.
+    fn evict_oldest(&mut self) {
+        let oldest = self
+            .values
+            .iter()
+            .min_by_key(|(_, (_, updated))| *updated)
+            .map(|(topic, _)| topic.clone());
+        if let Some(topic) = oldest {
+            self.values.remove(&topic);
+        }
+    }
+}
+
+/// Time of the last publish per topic, for dropping updates that arrive too fast.
+#[derive(Default)]
+struct RateLimiter {
+    published: HashMap<String, Instant>,
+}
+
+impl RateLimiter {
+    /// Records a publish of `topic` at `now`, unless the last one was less than `min_interval`
+    /// before.
+    ///
+    /// # Returns
+    ///
+    /// Returns `true` if the update should be published.
+    fn allow(&mut self, topic: &str, now: Instant, min_interval: Duration) -> bool {
+        if let Some(last) = self.published.get_mut(topic) {
+            if now.duration_since(*last) < min_interval {
+                return false;
+            }
+            *last = now;
+            return true;
+        }
+
+        self.published.insert(topic.to_string(), now);
+        true
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref LAST_FAILED_RECONNECT: Mutex<Option<Instant>> = Mutex::new(None);
+    /// Last payload per topic published through `publish_if_changed`.
+    static ref LAST_VALUES: Mutex<LastValues> = Mutex::new(LastValues::new(DEFAULT_CHANGE_CACHE_SIZE));
+    /// Topic filters and QoS subscribed to, restored after a reconnect.
+    static ref SUBSCRIPTIONS: Mutex<Vec<(String, i32)>> = Mutex::new(Vec::new());
+    /// Last publish time per topic published through `publish_rate_limited`.
+    static ref RATE_LIMITER: Mutex<RateLimiter> = Mutex::new(RateLimiter::default());
+    /// MQTT 5 user properties attached to every message, set by `setup_mqtt`.
+    static ref USER_PROPERTIES: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+}
 
 /// Set up and return an MQTT client based on the provided configuration.
 ///
 /// This function takes an `AppConfig` reference, extracts MQTT-related information
 /// (host and port) from it, creates an MQTT client, sets a timeout, and attempts to connect to the broker.
+/// The client ID is taken from `mqtt_client_id`, or derived from the hostname and base topic so it
+/// stays the same across restarts and a restarted client takes over its stale connection. When
+/// `mqtt_lwt_topic` is configured, a retained Last Will and Testament message is registered with
+/// the broker and a matching retained `online` message is published once connected. With `mqtt_version` 5 the client speaks MQTT 5 and attaches
+/// `mqtt_user_properties` to every message.
 ///
 /// # Arguments
 ///
@@ -32,46 +168,456 @@ use crate::config::AppConfig;
 ///
 /// Returns an MQTT client upon successful setup and connection.
 pub fn setup_mqtt(config: &AppConfig) -> mqtt::Client {
-    // Format the MQTT broker host and port.
-    let host = format!("mqtt://{}:{}", config.mqtt_host, config.mqtt_port);
+    let host = server_uri(config);
+    debug!("Connecting to MQTT broker at {}", host);
+
+    let client_id = config
+        .mqtt_client_id
+        .clone()
+        .unwrap_or_else(|| default_client_id(&system_hostname(), &config.mqtt_base_topic));
+    debug!("Using MQTT client ID: {}", client_id);
+
+    let mqtt_version = if config.mqtt_version == 5 {
+        mqtt::MQTT_VERSION_5
+    } else {
+        mqtt::MQTT_VERSION_3_1_1
+    };
+    let create_opts = mqtt::CreateOptionsBuilder::new()
+        .server_uri(host)
+        .client_id(client_id)
+        .mqtt_version(mqtt_version)
+        .finalize();
 
     // Create an MQTT client.
-    let mut cli = mqtt::Client::new(host).unwrap_or_else(|e| {
+    let mut cli = mqtt::Client::new(create_opts).unwrap_or_else(|e| {
         // Print an error message and exit the program if client creation fails.
-        println!("Error creating the client: {:?}", e);
+        eprintln!("Error creating the client: {:?}", e);
         process::exit(1);
     });
 
+    LAST_VALUES
+        .lock()
+        .unwrap()
+        .set_capacity(config.change_cache_size);
+    *USER_PROPERTIES.lock().unwrap() = config.mqtt_user_properties.clone();
+
     // Set a timeout of 5 seconds for synchronous calls.
     cli.set_timeout(Duration::from_secs(5));
 
+    // Register the Last Will and Testament so subscribers notice when the connection drops.
+    let mut conn_opts = if config.mqtt_version == 5 {
+        mqtt::ConnectOptionsBuilder::new_v5()
+    } else {
+        mqtt::ConnectOptionsBuilder::new()
+    };
+    if config.mqtt_transport == MqttTransport::Wss {
+        // Paho refuses TLS URIs without SSL options; the defaults verify the server certificate
+        conn_opts.ssl_options(mqtt::SslOptionsBuilder::new().finalize());
+    }
+    if let Some(lwt_topic) = &config.mqtt_lwt_topic {
+        let lwt_payload = config
+            .mqtt_lwt_payload
+            .as_deref()
+            .unwrap_or(LWT_OFFLINE_PAYLOAD);
+        conn_opts.will_message(mqtt::Message::new_retained(
+            lwt_topic.as_str(),
+            lwt_payload,
+            1,
+        ));
+    }
+
     // Attempt to connect to the MQTT broker and exit the program if the connection fails.
-    if let Err(e) = cli.connect(None) {
-        println!("Unable to connect: {:?}", e);
+    if let Err(e) = cli.connect(conn_opts.finalize()) {
+        eprintln!("Unable to connect: {:?}", e);
         process::exit(1);
     }
 
+    // Replace a stale will message left on the broker by a previous connection.
+    if let Some(lwt_topic) = &config.mqtt_lwt_topic {
+        if let Err(e) = publish_message(&cli, lwt_topic, LWT_ONLINE_PAYLOAD, 1, true) {
+            error!("Error publishing online status to MQTT: {:?}", e);
+        }
+    }
+
     // Return the configured and connected MQTT client.
     cli
 }
 
-/// Publish an MQTT message to the specified topic with the given payload and QoS.
+/// Background thread publishing the liveness heartbeat, see `start_heartbeat`.
+pub struct Heartbeat {
+    updates: mpsc::Sender<HeartbeatSettings>,
+    handle: thread::JoinHandle<()>,
+}
+
+impl Heartbeat {
+    /// Applies the interval, topic and QoS of a reloaded configuration. The next heartbeat is
+    /// published one new interval from now.
+    pub fn update(&self, config: &AppConfig) {
+        // The thread only exits once `stop` drops the sender
+        let _ = self.updates.send(HeartbeatSettings::from_config(config));
+    }
+
+    /// Stops the heartbeat thread and waits for it to finish.
+    pub fn stop(self) {
+        drop(self.updates);
+        if self.handle.join().is_err() {
+            error!("Heartbeat thread panicked");
+        }
+    }
+}
+
+/// What the heartbeat thread publishes, and how often.
+struct HeartbeatSettings {
+    /// Zero while `heartbeat_secs` is 0, pausing the heartbeat
+    interval: Duration,
+    topic: String,
+    /// Device whose fix age is published to `fix_age_topic` with each heartbeat
+    device_id: String,
+    fix_age_topic: String,
+    qos: i32,
+    retain: bool,
+}
+
+impl HeartbeatSettings {
+    fn from_config(config: &AppConfig) -> Self {
+        HeartbeatSettings {
+            interval: Duration::from_secs(config.heartbeat_secs),
+            topic: config.topic("STATUS/HEARTBEAT"),
+            device_id: config.device_id.clone(),
+            fix_age_topic: config.topic("STATUS/FIX_AGE_MS"),
+            qos: config.mqtt_qos,
+            retain: config.mqtt_retain,
+        }
+    }
+}
+
+/// Start publishing the current unix timestamp to `{base}STATUS/HEARTBEAT` every `heartbeat_secs`.
+///
+/// The heartbeat is published regardless of whether GPS data is flowing, so subscribers can tell
+/// a stationary vehicle from a stopped application. While `heartbeat_secs` is 0 the thread only
+/// waits for `Heartbeat::update`, so a configuration reload can still enable it.
+///
+/// Each heartbeat also publishes `STATUS/FIX_AGE_MS` once there was a fix, so the age keeps growing
+/// when the receiver stops sending altogether.
+///
+/// # Arguments
+///
+/// * `cli` - The MQTT client used for publishing.
+/// * `config` - A reference to the `AppConfig` struct.
+///
+/// # Returns
+///
+/// Returns the running `Heartbeat`.
+pub fn start_heartbeat(cli: mqtt::Client, config: &AppConfig) -> Heartbeat {
+    let mut settings = HeartbeatSettings::from_config(config);
+    let (updates, received) = mpsc::channel::<HeartbeatSettings>();
+
+    let handle = thread::spawn(move || loop {
+        // Dropping the sender in `Heartbeat::stop` ends the wait early
+        let update = if settings.interval.is_zero() {
+            received
+                .recv()
+                .map_err(|_| mpsc::RecvTimeoutError::Disconnected)
+        } else {
+            received.recv_timeout(settings.interval)
+        };
+
+        match update {
+            Ok(update) => settings = update,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|elapsed| elapsed.as_secs())
+                    .unwrap_or(0);
+
+                if let Err(e) = publish_message(
+                    &cli,
+                    &settings.topic,
+                    &timestamp.to_string(),
+                    settings.qos,
+                    settings.retain,
+                ) {
+                    error!("Error publishing heartbeat to MQTT: {:?}", e);
+                }
+
+                if let Some(age) = fix_age_ms(&settings.device_id) {
+                    if let Err(e) = publish_message(
+                        &cli,
+                        &settings.fix_age_topic,
+                        &age.to_string(),
+                        settings.qos,
+                        settings.retain,
+                    ) {
+                        error!("Error publishing fix age to MQTT: {:?}", e);
+                    }
+                }
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+    });
+
+    Heartbeat { updates, handle }
+}
+
+/// Start listening for messages on the `TRIP/RESET` topic of each source, which reset the trip
+/// distance of that source.
+///
+/// Any non-retained message resets the accumulated distance and publishes 0 to
+/// `TRIP/DISTANCE_M`. Retained messages are ignored so a stale reset left on the broker doesn't
+/// clear the trip on every start. The listener thread runs until the process exits.
+///
+/// # Arguments
+///
+/// * `cli` - The connected MQTT client.
+/// * `sources` - The configuration of every source, see `AppConfig::source_configs`.
+pub fn start_trip_reset_listener(cli: mqtt::Client, sources: &[AppConfig]) {
+    // Reset topic -> (device ID, distance topic, QoS, retain)
+    let mut resets = HashMap::new();
+    for config in sources {
+        resets.insert(
+            config.topic("TRIP/RESET"),
+            (
+                config.device_id.clone(),
+                config.topic("TRIP/DISTANCE_M"),
+                config.mqtt_qos,
+                config.mqtt_retain,
+            ),
+        );
+    }
+
+    let messages = cli.start_consuming();
+    for reset_topic in resets.keys() {
+        if let Err(e) = subscribe(&cli, reset_topic, 1) {
+            error!("Error subscribing to {}: {}", reset_topic, e);
+            return;
+        }
+    }
+
+    thread::spawn(move || {
+        // `None` signals a lost connection, the subscription is restored on reconnect
+        for message in messages.iter().flatten() {
+            if message.retained() {
+                continue;
+            }
+            let Some((device_id, distance_topic, qos, retain)) = resets.get(message.topic()) else {
+                continue;
+            };
+
+            info!("Resetting trip distance of {}", device_id);
+            reset_trip(device_id);
+            if let Err(e) = publish_message(&cli, distance_topic, "0", *qos, *retain) {
+                error!("Error pushing trip distance to MQTT: {:?}", e);
+            }
+        }
+    });
+}
+
+/// Subscribes to `topic` and remembers it so `reconnect_client` can restore it.
+fn subscribe(cli: &mqtt::Client, topic: &str, qos: i32) -> Result<(), mqtt::Error> {
+    cli.subscribe(topic, qos)?;
+    SUBSCRIPTIONS.lock().unwrap().push((topic.to_string(), qos));
+    Ok(())
+}
+
+/// Disconnect the MQTT client cleanly.
+///
+/// Sending a proper DISCONNECT lets the broker end the session immediately instead of waiting
+/// for the keep-alive to time out, and prevents the Last Will message from being published on
+/// a deliberate shutdown.
 ///
 /// # Arguments
 ///
 /// * `cli` - A reference to the MQTT client.
+pub fn disconnect_mqtt(cli: &mqtt::Client) {
+    let opts = mqtt::DisconnectOptionsBuilder::new()
+        .timeout(DISCONNECT_TIMEOUT)
+        .finalize();
+
+    match cli.disconnect(opts) {
+        Ok(()) => info!("Disconnected from MQTT broker"),
+        Err(e) => error!("Error disconnecting from MQTT broker: {}", e),
+    }
+}
+
+/// Builds the MQTT 5 properties carrying `properties` as user properties.
+fn build_user_properties(properties: &[(String, String)]) -> mqtt::Properties {
+    let mut props = mqtt::Properties::new();
+    for (name, value) in properties {
+        if let Err(e) = props.push_string_pair(mqtt::PropertyCode::UserProperty, name, value) {
+            error!("Invalid MQTT user property {}: {}", name, e);
+        }
+    }
+    props
+}
+
+/// Builds the broker URI from the host, port, `mqtt_transport` and `mqtt_ws_path`, e.g.
+/// `mqtt://localhost:1883` or `wss://broker.example.com:443/mqtt`.
+fn server_uri(config: &AppConfig) -> String {
+    let scheme = match config.mqtt_transport {
+        MqttTransport::Tcp => "mqtt",
+        MqttTransport::Ws => "ws",
+        MqttTransport::Wss => "wss",
+    };
+    let path = match config.mqtt_transport {
+        MqttTransport::Tcp => "",
+        MqttTransport::Ws | MqttTransport::Wss => config.mqtt_ws_path.as_deref().unwrap_or(""),
+    };
+
+    format!(
+        "{}://{}:{}{}",
+        scheme, config.mqtt_host, config.mqtt_port, path
+    )
+}
+
+/// Derives a stable MQTT client ID from the hostname and base topic.
+///
+/// Characters outside `[A-Za-z0-9_-]` are replaced with `-`, so `host` and `/GOLF86/GPS/`
+/// produce `gps-to-mqtt-host-GOLF86-GPS`.
+fn default_client_id(hostname: &str, base_topic: &str) -> String {
+    let sanitize = |value: &str| {
+        value
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                    c
+                } else {
+                    '-'
+                }
+            })
+            .collect::<String>()
+            .trim_matches('-')
+            .to_string()
+    };
+
+    [
+        "gps-to-mqtt".to_string(),
+        sanitize(hostname),
+        sanitize(base_topic),
+    ]
+    .iter()
+    .filter(|part| !part.is_empty())
+    .cloned()
+    .collect::<Vec<_>>()
+    .join("-")
+}
+
+/// Destination of the parsed data, implemented by the MQTT client and the `--dry-run` sink.
+///
+/// Shared by the reader threads of all sources, so it must be `Sync`.
+pub trait Publisher: Sync {
+    /// Publishes `payload` to `topic`. Inputs are validated by `publish_message` beforehand.
+    fn publish(
+        &self,
+        topic: &str,
+        payload: &str,
+        qos: i32,
+        retain: bool,
+    ) -> Result<(), PublishError>;
+}
+
+impl Publisher for mqtt::Client {
+    /// If the publish fails because the client lost its connection, a bounded number of reconnect
+    /// attempts with exponential backoff is made and the message is published again. When
+    /// reconnecting fails, `PublishError::Reconnecting` is returned and further publishes fail
+    /// fast with `PublishError::CoolingDown` for a short cooldown so a dead broker doesn't stall
+    /// the serial reader.
+    fn publish(
+        &self,
+        topic: &str,
+        payload: &str,
+        qos: i32,
+        retain: bool,
+    ) -> Result<(), PublishError> {
+        let builder = mqtt::MessageBuilder::new()
+            .topic(topic)
+            .payload(payload)
+            .qos(qos)
+            .retained(retain);
+        let msg = match USER_PROPERTIES.lock().unwrap().as_slice() {
+            [] => builder,
+            properties => builder.properties(build_user_properties(properties)),
+        }
+        .finalize();
+
+        match mqtt::Client::publish(self, msg.clone()) {
+            Ok(()) => Ok(()),
+            Err(e) if self.is_connected() => Err(PublishError::MqttError(e)),
+            Err(e) => {
+                warn!("MQTT publish failed, client disconnected: {}", e);
+                reconnect_client(self)?;
+                mqtt::Client::publish(self, msg).map_err(PublishError::MqttError)
+            }
+        }
+    }
+}
+
+/// Publisher for `--dry-run` that prints each message to stdout instead of sending it.
+pub struct DryRunPublisher;
+
+impl Publisher for DryRunPublisher {
+    fn publish(
+        &self,
+        topic: &str,
+        payload: &str,
+        _qos: i32,
+        _retain: bool,
+    ) -> Result<(), PublishError> {
+        println!("{} {}", topic, payload);
+        Ok(())
+    }
+}
+
+/// Test double that records every published topic and payload in order.
+#[cfg(test)]
+#[derive(Default)]
+pub struct RecordingPublisher {
+    messages: Mutex<Vec<(String, String)>>,
+}
+
+#[cfg(test)]
+impl RecordingPublisher {
+    /// Returns the messages published so far as `(topic, payload)` pairs.
+    pub fn messages(&self) -> Vec<(String, String)> {
+        self.messages.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+impl Publisher for RecordingPublisher {
+    fn publish(
+        &self,
+        topic: &str,
+        payload: &str,
+        _qos: i32,
+        _retain: bool,
+    ) -> Result<(), PublishError> {
+        self.messages
+            .lock()
+            .unwrap()
+            .push((topic.to_string(), payload.to_string()));
+        Ok(())
+    }
+}
+
+/// Publish a message to the specified topic with the given payload, QoS and retained flag.
+///
+/// # Arguments
+///
+/// * `publisher` - The MQTT client or another `Publisher`.
 /// * `topic` - The MQTT topic to which the message will be published.
 /// * `payload` - The payload of the MQTT message.
 /// * `qos` - The Quality of Service level for the message.
+/// * `retain` - Whether the broker should retain the message for new subscribers.
 ///
 /// # Returns
 ///
-/// Returns `Result<(), mqtt::Error>` indicating success or failure.
+/// Returns `Result<(), PublishError>` indicating success or failure.
 pub fn publish_message(
-    cli: &mqtt::Client,
+    publisher: &dyn Publisher,
     topic: &str,
     payload: &str,
     qos: i32,
+    retain: bool,
 ) -> Result<(), PublishError> {
     // Validate inputs
     if topic.is_empty() || payload.is_empty() {
@@ -84,12 +630,280 @@ pub fn publish_message(
 
     debug!("Publishing message to topic: {}", topic);
 
-    let msg = mqtt::MessageBuilder::new()
-        .topic(topic)
-        .payload(payload)
-        .qos(qos)
-        .retained(true)
-        .finalize();
+    publisher.publish(topic, payload, qos, retain)
+}
+
+/// Removes the retained message of `topic` from the broker by publishing an empty retained
+/// payload, e.g. for a satellite that is no longer in view.
+///
+/// # Returns
+///
+/// Returns `Result<(), PublishError>` indicating success or failure.
+pub fn clear_retained(
+    publisher: &dyn Publisher,
+    topic: &str,
+    qos: i32,
+) -> Result<(), PublishError> {
+    if topic.is_empty() {
+        return Err(PublishError::EmptyInput);
+    }
+
+    if qos > 2 {
+        return Err(PublishError::InvalidQoS);
+    }
+
+    debug!("Clearing retained message of topic: {}", topic);
+
+    publisher.publish(topic, "", qos, true)
+}
+
+/// Publish a message only when the payload differs from the last one sent to the same topic.
+///
+/// Used for values that repeat across many sentences, such as the time and date, to avoid
+/// flooding subscribers with identical messages. The payload is remembered even when the
+/// publish fails, so a failed value isn't retried until it changes. At most `change_cache_size`
+/// topics are remembered; an evicted topic is republished once even if unchanged.
+///
+/// # Returns
+///
+/// Returns `Ok(true)` when published, `Ok(false)` when skipped as unchanged, or the
+/// `PublishError` of `publish_message`.
+pub fn publish_if_changed(
+    publisher: &dyn Publisher,
+    topic: &str,
+    payload: &str,
+    qos: i32,
+    retain: bool,
+) -> Result<bool, PublishError> {
+    if !LAST_VALUES.lock().unwrap().update(topic, payload) {
+        return Ok(false);
+    }
+
+    publish_message(publisher, topic, payload, qos, retain)?;
+    Ok(true)
+}
 
-    cli.publish(msg).map_err(PublishError::MqttError)
+/// Publish a message unless one was published to the same topic less than `min_interval` ago.
+///
+/// Used for values that change with every sentence, such as the position of a 10Hz receiver,
+/// where subscribers only need an update every so often and change detection doesn't help.
+/// Faster updates are dropped. As every sentence carries the current value, the first update
+/// after the interval is the most recent one. A zero `min_interval` publishes every message.
+///
+/// # Returns
+///
+/// Returns `Ok(true)` when published, `Ok(false)` when dropped, or the `PublishError` of
+/// `publish_message`.
+pub fn publish_rate_limited(
+    publisher: &dyn Publisher,
+    topic: &str,
+    payload: &str,
+    qos: i32,
+    retain: bool,
+    min_interval: Duration,
+) -> Result<bool, PublishError> {
+    if !min_interval.is_zero()
+        && !RATE_LIMITER
+            .lock()
+            .unwrap()
+            .allow(topic, Instant::now(), min_interval)
+    {
+        return Ok(false);
+    }
+
+    publish_message(publisher, topic, payload, qos, retain)?;
+    Ok(true)
+}
+
+/// Reconnects a disconnected MQTT client, unless a reconnect cycle failed within the cooldown.
+///
+/// # Returns
+///
+/// Returns `Ok(())` once reconnected, `PublishError::Reconnecting` if all attempts failed, or
+/// `PublishError::CoolingDown` without an attempt during the cooldown.
+fn reconnect_client(cli: &mqtt::Client) -> Result<(), PublishError> {
+    // Not held while retrying, so other publishers fail fast instead of waiting on the backoff
+    let last_failed = *LAST_FAILED_RECONNECT.lock().unwrap();
+    if let Some(failed_at) = last_failed {
+        if failed_at.elapsed() < RECONNECT_COOLDOWN {
+            return Err(PublishError::CoolingDown);
+        }
+    }
+
+    let reconnected = retry_with_backoff(RECONNECT_ATTEMPTS, RECONNECT_BASE_DELAY, || {
+        match cli.reconnect() {
+            Ok(_) => true,
+            Err(e) => {
+                warn!("MQTT reconnect attempt failed: {}", e);
+                false
+            }
+        }
+    });
+
+    if reconnected {
+        info!("Reconnected to MQTT broker");
+        *LAST_FAILED_RECONNECT.lock().unwrap() = None;
+        for (topic, qos) in SUBSCRIPTIONS.lock().unwrap().iter() {
+            if let Err(e) = cli.subscribe(topic, *qos) {
+                error!("Error restoring subscription to {}: {}", topic, e);
+            }
+        }
+        Ok(())
+    } else {
+        error!(
+            "Unable to reconnect to MQTT broker after {} attempts",
+            RECONNECT_ATTEMPTS
+        );
+        *LAST_FAILED_RECONNECT.lock().unwrap() = Some(Instant::now());
+        Err(PublishError::Reconnecting(RECONNECT_ATTEMPTS))
+    }
+}
+
+/// Calls `attempt` until it returns `true` or `attempts` calls have been made.
+///
+/// The delay between calls starts at `base_delay` and doubles after each failure.
+pub fn retry_with_backoff<F: FnMut() -> bool>(
+    attempts: u32,
+    base_delay: Duration,
+    mut attempt: F,
+) -> bool {
+    let mut delay = base_delay;
+    for n in 0..attempts {
+        if n > 0 {
+            thread::sleep(delay);
+            delay *= 2;
+        }
+        if attempt() {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::template_config;
+
+    #[test]
+    fn test_server_uri() {
+        let mut config = template_config();
+        assert_eq!(server_uri(&config), "mqtt://localhost:1883");
+
+        config.mqtt_host = "broker.example.com".to_string();
+        config.mqtt_port = 443;
+        config.mqtt_transport = MqttTransport::Wss;
+        assert_eq!(server_uri(&config), "wss://broker.example.com:443");
+        config.mqtt_ws_path = Some("/mqtt".to_string());
+        assert_eq!(server_uri(&config), "wss://broker.example.com:443/mqtt");
+        config.mqtt_transport = MqttTransport::Ws;
+        assert_eq!(server_uri(&config), "ws://broker.example.com:443/mqtt");
+    }
+
+    #[test]
+    fn test_default_client_id() {
+        assert_eq!(
+            default_client_id("raspberrypi", "/GOLF86/GPS/"),
+            "gps-to-mqtt-raspberrypi-GOLF86-GPS"
+        );
+        assert_eq!(
+            default_client_id("car.local", "telemetry/gps"),
+            "gps-to-mqtt-car-local-telemetry-gps"
+        );
+        assert_eq!(default_client_id("", "/"), "gps-to-mqtt");
+    }
+
+    #[test]
+    fn test_publish_if_changed_skips_repeated_payloads() {
+        let cli = mqtt::Client::new("tcp://localhost:1883").unwrap();
+        let topic = "/TEST/PUBLISH_IF_CHANGED/TME";
+
+        // Not connected, so a changed value is attempted and fails
+        assert!(publish_if_changed(&cli, topic, "12:35:19", 0, true).is_err());
+        assert!(matches!(
+            publish_if_changed(&cli, topic, "12:35:19", 0, true),
+            Ok(false)
+        ));
+        assert!(publish_if_changed(&cli, topic, "12:35:20", 0, true).is_err());
+        assert!(matches!(
+            publish_if_changed(&cli, topic, "12:35:20", 0, true),
+            Ok(false)
+        ));
+    }
+
+    #[test]
+    fn test_last_values_evicts_least_recently_updated() {
+        let mut values = LastValues::new(2);
+        assert!(values.update("A", "1"));
+        assert!(values.update("B", "1"));
+        // Touching A makes B the least recently updated
+        assert!(!values.update("A", "1"));
+
+        assert!(values.update("C", "1"));
+        assert_eq!(values.values.len(), 2);
+        assert!(!values.update("A", "1"));
+        assert!(!values.update("C", "1"));
+        // B was evicted, so its unchanged value counts as new
+        assert!(values.update("B", "1"));
+
+        values.set_capacity(1);
+        assert_eq!(values.values.len(), 1);
+        assert!(!values.update("B", "1"));
+    }
+
+    #[test]
+    fn test_rate_limiter_drops_rapid_updates() {
+        let mut limiter = RateLimiter::default();
+        let start = Instant::now();
+        let interval = Duration::from_millis(300);
+
+        // A 10Hz stream limited to one update per 300ms
+        let allowed: Vec<u64> = (0..=10)
+            .map(|n| n * 100)
+            .filter(|&ms| limiter.allow("LAT", start + Duration::from_millis(ms), interval))
+            .collect();
+        assert_eq!(allowed, [0, 300, 600, 900]);
+
+        // Topics are limited independently
+        assert!(limiter.allow("LNG", start + Duration::from_millis(1000), interval));
+        assert!(!limiter.allow("LAT", start + Duration::from_millis(1000), interval));
+        assert!(limiter.allow("LAT", start + Duration::from_millis(1200), interval));
+    }
+
+    #[test]
+    fn test_retry_with_backoff() {
+        // Succeeds on the third attempt
+        let mut calls = 0;
+        assert!(retry_with_backoff(3, Duration::from_millis(1), || {
+            calls += 1;
+            calls == 3
+        }));
+        assert_eq!(calls, 3);
+
+        // Gives up after the bounded number of attempts
+        let mut calls = 0;
+        assert!(!retry_with_backoff(3, Duration::from_millis(1), || {
+            calls += 1;
+            false
+        }));
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn test_publish_on_disconnected_client() {
+        // A client that never connected behaves like one whose broker went away
+        let cli = mqtt::Client::new("tcp://localhost:1").unwrap();
+        let result = publish_message(&cli, "/TEST/TOPIC", "payload", 0, true);
+        // Other tests publishing on a disconnected client may have started the cooldown already
+        assert!(matches!(
+            result,
+            Err(PublishError::Reconnecting(_) | PublishError::CoolingDown)
+        ));
+
+        // Immediately retrying fails fast during the cooldown
+        let started = Instant::now();
+        let result = publish_message(&cli, "/TEST/TOPIC", "payload", 0, true);
+        assert!(matches!(result, Err(PublishError::CoolingDown)));
+        assert!(started.elapsed() < RECONNECT_BASE_DELAY);
+    }
 }
@@ -0,0 +1,232 @@
+use crate::config::{load_configuration_with_path, log_config_files, AppConfig};
+use crate::serial_port_handler::{quit_channel, quit_requested};
+use log::{error, info, warn};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Once, RwLock};
+use std::time::Duration;
+
+/// Set by the SIGHUP handler and cleared by `watch_reloads` when it starts the reload.
+static RELOAD_REQUESTED: AtomicBool = AtomicBool::new(false);
+/// Installs the SIGHUP handler once, however often `watch_reloads` is called.
+static SIGHUP_HANDLER: Once = Once::new();
+/// How often `watch_reloads` checks for a pending reload and the quit command.
+const RELOAD_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Reloads the configuration on SIGHUP until the quit command is received.
+///
+/// The file is read again from `config_path`, or the default locations when it is `None`, and
+/// must pass `AppConfig::validate`; otherwise the error is logged and the running configuration
+/// is kept. The settings that can change live are written to every source configuration, see
+/// `apply_live_settings`. Other changed settings are logged as needing a restart. Only Unix
+/// systems have SIGHUP, elsewhere this just waits for the quit command.
+///
+/// # Arguments
+///
+/// * `config_path` - The configuration file given on the command line.
+/// * `config` - The configuration the application was started with.
+/// * `sources` - The configuration of every source, read by `live_publishing_handler`.
+/// * `on_reload` - Called with the new configuration after a successful reload, to apply the
+///   settings that live outside the source configurations, like the log level.
+pub fn watch_reloads(
+    config_path: Option<&str>,
+    config: &AppConfig,
+    sources: &[RwLock<AppConfig>],
+    on_reload: impl Fn(&AppConfig),
+) {
+    SIGHUP_HANDLER.call_once(install_sighup_handler);
+    let quit = quit_channel();
+    let mut current = config.clone();
+
+    loop {
+        // The quit command may have been sent before the channel was opened
+        match quit.recv_timeout(RELOAD_POLL_INTERVAL) {
+            Err(mpsc::RecvTimeoutError::Timeout) if !quit_requested() => {}
+            _ => return,
+        }
+        if !RELOAD_REQUESTED.swap(false, Ordering::SeqCst) {
+            continue;
+        }
+
+        info!("Received SIGHUP, reloading the configuration");
+        let reloaded = match load_configuration_with_path(config_path)
+            .and_then(|(reloaded, path)| reloaded.validate().map(|()| (reloaded, path)))
+        {
+            Ok((reloaded, path)) => {
+                log_config_files(config_path, path.as_deref());
+                reloaded
+            }
+            Err(e) => {
+                error!(
+                    "Failed to reload the configuration, keeping the current one: {}",
+                    e
+                );
+                continue;
+            }
+        };
+
+        for field in restart_required(&current, &reloaded) {
+            warn!("{} changed, restart to apply it", field);
+        }
+        let applied = apply_live_settings(&mut current, &reloaded);
+        for source in sources {
+            apply_live_settings(&mut source.write().unwrap(), &reloaded);
+        }
+        if applied.is_empty() {
+            info!("Configuration reloaded, no live settings changed");
+        } else {
+            info!("Configuration reloaded, applied {}", applied.join(", "));
+        }
+
+        on_reload(&current);
+    }
+}
+
+/// Copies the settings that can change while running from `reloaded` to `config`: the base
+/// topic, QoS, heartbeat interval and log level.
+///
+/// # Returns
+///
+/// The names of the settings that changed.
+pub fn apply_live_settings(config: &mut AppConfig, reloaded: &AppConfig) -> Vec<&'static str> {
+    let mut changed = Vec::new();
+
+    if config.mqtt_base_topic != reloaded.mqtt_base_topic {
+        config.mqtt_base_topic = reloaded.mqtt_base_topic.clone();
+        changed.push("mqtt_base_topic");
+    }
+    if config.mqtt_qos != reloaded.mqtt_qos {
+        config.mqtt_qos = reloaded.mqtt_qos;
+        changed.push("mqtt_qos");
+    }
+    if config.heartbeat_secs != reloaded.heartbeat_secs {
+        config.heartbeat_secs = reloaded.heartbeat_secs;
+        changed.push("heartbeat_secs");
+    }
+    if config.log_level != reloaded.log_level {
+        config.log_level = reloaded.log_level;
+        changed.push("log_level");
+    }
+
+    changed
+}
+
+/// Returns the names of the settings changed in `reloaded` that only take effect after a
+/// restart.
+///
+/// The source and broker connection settings are named, any other difference is reported as
+/// `other settings`.
+fn restart_required(config: &AppConfig, reloaded: &AppConfig) -> Vec<&'static str> {
+    let connection_changes = [
+        ("source_type", config.source_type != reloaded.source_type),
+        ("port_name", config.port_name != reloaded.port_name),
+        ("baud_rate", config.baud_rate != reloaded.baud_rate),
+        ("gpsd_host", config.gpsd_host != reloaded.gpsd_host),
+        ("gpsd_port", config.gpsd_port != reloaded.gpsd_port),
+        ("udp_port", config.udp_port != reloaded.udp_port),
+        ("mqtt_host", config.mqtt_host != reloaded.mqtt_host),
+        ("mqtt_port", config.mqtt_port != reloaded.mqtt_port),
+        ("sources", config.sources != reloaded.sources),
+    ];
+    let mut changed: Vec<_> = connection_changes
+        .into_iter()
+        .filter(|(_, changed)| *changed)
+        .map(|(field, _)| field)
+        .collect();
+
+    // Whatever still differs once the live and named settings are taken over
+    let mut others = AppConfig {
+        source_type: reloaded.source_type,
+        port_name: reloaded.port_name.clone(),
+        baud_rate: reloaded.baud_rate,
+        gpsd_host: reloaded.gpsd_host.clone(),
+        gpsd_port: reloaded.gpsd_port,
+        udp_port: reloaded.udp_port,
+        mqtt_host: reloaded.mqtt_host.clone(),
+        mqtt_port: reloaded.mqtt_port,
+        sources: reloaded.sources.clone(),
+        ..config.clone()
+    };
+    apply_live_settings(&mut others, reloaded);
+    if others != *reloaded {
+        changed.push("other settings");
+    }
+
+    changed
+}
+
+/// Installs a SIGHUP handler requesting a reload from `watch_reloads`.
+#[cfg(unix)]
+fn install_sighup_handler() {
+    extern "C" fn on_sighup(_: libc::c_int) {
+        RELOAD_REQUESTED.store(true, Ordering::SeqCst);
+    }
+
+    // SAFETY: the handler only stores to an atomic, which is async-signal-safe
+    let previous = unsafe {
+        libc::signal(
+            libc::SIGHUP,
+            on_sighup as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        )
+    };
+    if previous == libc::SIG_ERR {
+        error!(
+            "Failed to install SIGHUP handler: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+/// Other platforms have no SIGHUP, so the configuration is never reloaded.
+#[cfg(not(unix))]
+fn install_sighup_handler() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::template_config;
+    use log::LevelFilter;
+
+    #[test]
+    fn test_apply_live_settings() {
+        let mut config = template_config();
+        let mut reloaded = config.clone();
+        assert!(apply_live_settings(&mut config, &reloaded).is_empty());
+        assert!(restart_required(&config, &reloaded).is_empty());
+
+        reloaded.mqtt_base_topic = "/CAR/NAV/".to_string();
+        reloaded.mqtt_qos = 1;
+        reloaded.log_level = LevelFilter::Debug;
+        assert!(restart_required(&config, &reloaded).is_empty());
+        assert_eq!(
+            apply_live_settings(&mut config, &reloaded),
+            ["mqtt_base_topic", "mqtt_qos", "log_level"]
+        );
+        assert_eq!(config.topic("ALT"), "/CAR/NAV/ALT");
+        assert_eq!(config.mqtt_qos, 1);
+
+        reloaded.port_name = "/dev/ttyUSB0".to_string();
+        reloaded.baud_rate = 115200;
+        assert_eq!(
+            restart_required(&config, &reloaded),
+            ["port_name", "baud_rate"]
+        );
+        assert!(apply_live_settings(&mut config, &reloaded).is_empty());
+        assert_eq!(config.port_name, "/dev/ttyACM0");
+
+        reloaded.mqtt_retain = false;
+        assert_eq!(
+            restart_required(&config, &reloaded),
+            ["port_name", "baud_rate", "other settings"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_sighup_requests_reload() {
+        SIGHUP_HANDLER.call_once(install_sighup_handler);
+        RELOAD_REQUESTED.store(false, Ordering::SeqCst);
+        // SAFETY: raising a signal whose handler is installed above
+        unsafe { libc::raise(libc::SIGHUP) };
+        assert!(RELOAD_REQUESTED.swap(false, Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,143 @@
+use crate::frame_reader::Frame;
+use crate::serial_port_handler::quit_requested;
+use log::info;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::thread;
+use std::time::Duration;
+
+/// Seconds in one day, used to handle the UTC midnight rollover while pacing playback.
+const SECONDS_PER_DAY: f64 = 86_400.0;
+/// Longest pause inserted between two sentences during realtime playback.
+const MAX_REPLAY_GAP: Duration = Duration::from_secs(10);
+
+/// Replay NMEA sentences from a log file, passing each line to `handler`.
+///
+/// Each line of the file is handled like a sentence read from the serial port. With `realtime`
+/// set, playback is paced by the UTC time of RMC and ZDA sentences, so a 10Hz log replays at
+/// 10Hz. Gaps longer than `MAX_REPLAY_GAP` are shortened. Returns once the end of the file is
+/// reached, or early once the quit command was sent, e.g. by a fix timeout.
+///
+/// # Arguments
+///
+/// * `path` - Path to the NMEA log file.
+/// * `realtime` - Whether to pace playback by the sentence timestamps.
+/// * `handler` - Called with every line, e.g. the `publishing_handler`.
+pub fn replay_file(path: &str, realtime: bool, handler: &dyn Fn(&Frame)) -> io::Result<()> {
+    let reader = BufReader::new(File::open(path)?);
+
+    info!("Replaying NMEA log: {}", path);
+
+    let mut last_time: Option<f64> = None;
+    let mut sentences = 0;
+
+    for chunk in reader.split(b'\n') {
+        let chunk = chunk?;
+        // Logs of receivers ending sentences with `\r` only hold several sentences per chunk
+        for line in String::from_utf8_lossy(&chunk).split('\r') {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if quit_requested() {
+                info!("Stopping the replay after {} sentences", sentences);
+                return Ok(());
+            }
+
+            if realtime {
+                if let Some(time) = sentence_time_of_day(line) {
+                    if let Some(previous) = last_time {
+                        thread::sleep(replay_delay(previous, time));
+                    }
+                    last_time = Some(time);
+                }
+            }
+
+            handler(&Frame::Nmea(line.to_string()));
+            sentences += 1;
+        }
+    }
+
+    info!("Replay finished after {} sentences", sentences);
+    info!("Reached end of NMEA log. Exiting the program.");
+    Ok(())
+}
+
+/// Extracts the UTC time of day in seconds from an RMC or ZDA sentence.
+///
+/// Both sentence types carry the `hhmmss.ss` time in their first field. Fields that aren't six
+/// digits with an optional fraction, as found in corrupt logs, give `None`.
+fn sentence_time_of_day(line: &str) -> Option<f64> {
+    let sentence = line.strip_prefix('$')?.split('*').next()?;
+    let mut fields = sentence.split(',');
+    let sentence_type = fields.next()?;
+    if !sentence_type.ends_with("RMC") && !sentence_type.ends_with("ZDA") {
+        return None;
+    }
+
+    let time = fields.next()?;
+    let (whole, fraction) = time.split_once('.').unwrap_or((time, ""));
+    if whole.len() != 6
+        || !whole.bytes().all(|b| b.is_ascii_digit())
+        || !fraction.bytes().all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let hours = time[0..2].parse::<f64>().ok()?;
+    let minutes = time[2..4].parse::<f64>().ok()?;
+    let seconds = time[4..].parse::<f64>().ok()?;
+    Some(hours * 3600.0 + minutes * 60.0 + seconds)
+}
+
+/// Computes the pause between two consecutive sentence times, handling midnight rollover.
+fn replay_delay(previous: f64, current: f64) -> Duration {
+    let mut delta = current - previous;
+    if delta < 0.0 {
+        delta += SECONDS_PER_DAY;
+    }
+    Duration::try_from_secs_f64(delta)
+        .unwrap_or(MAX_REPLAY_GAP)
+        .min(MAX_REPLAY_GAP)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sentence_time_of_day() {
+        assert_eq!(
+            sentence_time_of_day(
+                "$GNRMC,123519.50,A,4807.038,N,01131.000,E,022.4,084.4,230394,,*6A"
+            ),
+            Some(12.0 * 3600.0 + 35.0 * 60.0 + 19.5)
+        );
+        assert_eq!(
+            sentence_time_of_day("$GNZDA,000001.00,04,07,2002,00,00*70"),
+            Some(1.0)
+        );
+        assert_eq!(
+            sentence_time_of_day(
+                "$GNGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47"
+            ),
+            None
+        );
+        assert_eq!(sentence_time_of_day("$GNRMC,,V,,,,,,,,,,N*4D"), None);
+        assert_eq!(sentence_time_of_day("garbage"), None);
+        assert_eq!(sentence_time_of_day("$GNRMC,1°3519,A"), None);
+        assert_eq!(sentence_time_of_day("$GNRMC,1235,A"), None);
+        assert_eq!(sentence_time_of_day("$GNRMC,1200NaN,A"), None);
+        assert_eq!(sentence_time_of_day("$GNRMC,1200inf,A"), None);
+        assert_eq!(sentence_time_of_day("$GNRMC,12-9e5,A"), None);
+        assert_eq!(sentence_time_of_day("$GNRMC,123519.-5,A"), None);
+    }
+
+    #[test]
+    fn test_replay_delay() {
+        assert_eq!(replay_delay(10.0, 10.1), Duration::from_secs_f64(0.1));
+        assert_eq!(replay_delay(86_399.5, 0.5), Duration::from_secs(1));
+        assert_eq!(replay_delay(0.0, 3600.0), MAX_REPLAY_GAP);
+        assert_eq!(replay_delay(0.0, f64::NAN), MAX_REPLAY_GAP);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::config::AppConfig;
+use crate::gps_data_parser::process_gps_data;
+use crate::mqtt_handler::{PublishError, Publisher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Representative sentences fed through the pipeline by `--selftest`, one of each common type.
+pub const SELFTEST_SENTENCES: [&str; 6] = [
+    "$GNGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*59",
+    "$GNRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*74",
+    "$GNVTG,084.4,T,081.3,M,022.4,N,041.5,K*56",
+    "$GPGSV,1,1,04,04,77,048,42,05,35,292,39,09,23,203,36,12,11,045,31*7F",
+    "$GNGSA,A,3,04,05,,09,12,,,,,,,,1.8,1.0,1.5*2B",
+    "$GNGLL,4807.038,N,01131.000,E,123519,A*3B",
+];
+
+/// Outcome of one `SELFTEST_SENTENCES` entry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SentenceResult {
+    /// Sentence type, e.g. `GGA`
+    pub sentence_type: String,
+    /// Messages the publisher accepted
+    pub published: usize,
+    /// Messages the publisher rejected
+    pub failed: usize,
+}
+
+impl SentenceResult {
+    /// Whether the sentence published at least one message and none failed.
+    pub fn passed(&self) -> bool {
+        self.published > 0 && self.failed == 0
+    }
+}
+
+/// Feeds `SELFTEST_SENTENCES` through `process_gps_data` and counts what gets published.
+///
+/// This exercises the parse and publish path end to end without a receiver. Against a connected
+/// broker every message is really published, so the self-test doubles as a connectivity check.
+/// Messages are sent without the retained flag, so the sample fix doesn't replace the retained
+/// values subscribers see until the next real fix.
+///
+/// # Arguments
+///
+/// * `config` - Configuration settings for the application.
+/// * `mqtt` - The MQTT client, or the `DryRunPublisher`.
+///
+/// # Returns
+///
+/// The result of every sentence, in the order of `SELFTEST_SENTENCES`.
+pub fn run_selftest(config: &AppConfig, mqtt: &dyn Publisher) -> Vec<SentenceResult> {
+    SELFTEST_SENTENCES
+        .iter()
+        .map(|sentence| {
+            let counter = CountingPublisher {
+                inner: mqtt,
+                published: AtomicUsize::new(0),
+                failed: AtomicUsize::new(0),
+            };
+            // `process_gps_data` logs publish errors itself, the counter records them
+            let processed = process_gps_data(sentence.as_bytes(), config, &counter);
+            SentenceResult {
+                sentence_type: sentence.get(3..6).unwrap_or(sentence).to_string(),
+                published: counter.published.into_inner(),
+                failed: counter.failed.into_inner() + usize::from(processed.is_err()),
+            }
+        })
+        .collect()
+}
+
+/// Publisher passing every message on to `inner`, never retained, while counting the outcomes.
+struct CountingPublisher<'a> {
+    inner: &'a dyn Publisher,
+    published: AtomicUsize,
+    failed: AtomicUsize,
+}
+
+impl Publisher for CountingPublisher<'_> {
+    fn publish(
+        &self,
+        topic: &str,
+        payload: &str,
+        qos: i32,
+        _retain: bool,
+    ) -> Result<(), PublishError> {
+        let result = self.inner.publish(topic, payload, qos, false);
+        let counter = if result.is_ok() {
+            &self.published
+        } else {
+            &self.failed
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::template_config;
+    use crate::gps_data_parser::has_valid_checksum;
+    use crate::mqtt_handler::RecordingPublisher;
+    use std::sync::Mutex;
+
+    /// Publisher rejecting every message, like a client whose broker went away.
+    struct FailingPublisher;
+
+    impl Publisher for FailingPublisher {
+        fn publish(&self, _: &str, _: &str, _: i32, _: bool) -> Result<(), PublishError> {
+            Err(PublishError::Reconnecting(3))
+        }
+    }
+
+    /// Publisher recording the retained flag of every message.
+    #[derive(Default)]
+    struct RetainRecorder(Mutex<Vec<bool>>);
+
+    impl Publisher for RetainRecorder {
+        fn publish(&self, _: &str, _: &str, _: i32, retain: bool) -> Result<(), PublishError> {
+            self.0.lock().unwrap().push(retain);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_selftest_sentences_have_valid_checksums() {
+        for sentence in SELFTEST_SENTENCES {
+            assert!(has_valid_checksum(sentence), "{}", sentence);
+        }
+    }
+
+    #[test]
+    fn test_run_selftest() {
+        let mut config = template_config();
+        config.device_id = "selftest".to_string();
+
+        let mqtt = RecordingPublisher::default();
+        let results = run_selftest(&config, &mqtt);
+        let types: Vec<&str> = results.iter().map(|r| r.sentence_type.as_str()).collect();
+        assert_eq!(types, ["GGA", "RMC", "VTG", "GSV", "GSA", "GLL"]);
+        assert!(results.iter().all(SentenceResult::passed), "{:?}", results);
+        let published: usize = results.iter().map(|r| r.published).sum();
+        assert_eq!(published, mqtt.messages().len());
+
+        let results = run_selftest(&config, &FailingPublisher);
+        assert!(results.iter().all(|r| !r.passed()), "{:?}", results);
+
+        // The sample fix must not replace the retained values of the real ones
+        config.device_id = "selftest-retain".to_string();
+        assert!(config.mqtt_retain);
+        let mqtt = RetainRecorder::default();
+        run_selftest(&config, &mqtt);
+        let retained = mqtt.0.into_inner().unwrap();
+        assert!(!retained.is_empty());
+        assert!(!retained.contains(&true));
+    }
+}
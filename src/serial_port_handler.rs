@@ -1,24 +1,41 @@
 use crate::config::AppConfig;
-use crate::gps_data_parser::process_gps_data;
-use crate::mqtt_handler::setup_mqtt;
-use log::{error, info};
-use serialport::SerialPort;
-use std::io::{self, BufRead};
-use std::sync::mpsc;
+use crate::frame_reader::{Frame, FrameReader};
+use crate::gps_data_parser::has_valid_checksum;
+use crate::ubx::{build_ubx_frame, decode_ubx_frame, ubx_ack_for, UbxAck};
+use log::{error, info, warn};
+use serialport::{SerialPort, SerialPortBuilder, SerialPortType};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, BufRead, IsTerminal};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex, Once};
 use std::thread;
+use std::time::{Duration, Instant};
 
-/// UBX-CFG-RATE command bytes for 10Hz sampling
-const UBX_CFG_RATE_10HZ: [u8; 14] = [
-    0xB5, 0x62, // Header
-    0x06, 0x08, // Class/ID
-    0x06, 0x00, // Length
-    0x64, 0x00, // Measurement rate (100ms)
-    0x01, 0x00, // Navigation rate
-    0x01, 0x00, // Time reference
-    0x7A, 0x12, // Checksum
-];
+/// Message sent on the quit channels, whatever keyword or signal triggered it.
 const QUIT_COMMAND: &str = "q";
 
+lazy_static::lazy_static! {
+    /// Senders of every channel returned by `quit_channel`, each polled by one source loop.
+    static ref QUIT_SENDERS: Mutex<Vec<mpsc::Sender<String>>> = Mutex::new(Vec::new());
+    /// Line `check_quit` waits for on stdin, see `set_quit_keyword`.
+    static ref QUIT_KEYWORD: Mutex<String> = Mutex::new(QUIT_COMMAND.to_string());
+}
+
+/// Starts the stdin watcher and the signal handler once, however many sources are read.
+static QUIT_LISTENERS: Once = Once::new();
+/// Set once the quit command was sent, see `quit_requested`.
+static QUIT_REQUESTED: AtomicBool = AtomicBool::new(false);
+/// Read timeout of the serial port, so the read loop can poll the quit channel.
+const READ_TIMEOUT: Duration = Duration::from_millis(1000);
+/// Baud rates tried in order when `auto_baud` is set.
+const AUTO_BAUD_RATES: [u32; 4] = [4800, 9600, 38400, 115200];
+/// How long each `AUTO_BAUD_RATES` entry is listened to for a valid sentence.
+const AUTO_BAUD_PROBE_TIME: Duration = Duration::from_secs(1);
+/// Read timeout while waiting for a UBX acknowledgement, bounding the overshoot past
+/// `ubx_ack_timeout_ms`.
+const UBX_ACK_READ_TIMEOUT: Duration = Duration::from_millis(100);
+
 /// Set up and open a serial port based on the provided configuration.
 ///
 /// This function takes an `AppConfig` reference, lists available serial ports, opens and configures
@@ -36,126 +53,585 @@ const QUIT_COMMAND: &str = "q";
 ///
 /// Returns a boxed trait object representing the opened serial port.
 pub fn setup_serial_port(config: &AppConfig) -> Box<dyn serialport::SerialPort> {
-    println!("Opening port: {}", config.port_name);
+    info!("Opening port: {}", config.port_name);
 
-    let mut port = serialport::new(&config.port_name, config.baud_rate as u32)
-        .timeout(std::time::Duration::from_millis(1000))
-        .open()
-        .unwrap_or_else(|err| {
+    let mut port = if config.auto_baud {
+        detect_baud_rate(config).unwrap_or_else(|| {
+            eprintln!(
+                "No valid NMEA sentences received on {} at any of {:?} baud",
+                config.port_name, AUTO_BAUD_RATES
+            );
+            std::process::exit(1);
+        })
+    } else {
+        open_configured_port(config).unwrap_or_else(|err| {
             eprintln!("Failed to open port: {}", err);
             std::process::exit(1);
-        });
+        })
+    };
+
+    // The 10Hz toggle predates `gps_rate_hz` and keeps precedence for backward compatibility
+    let rate_hz = if config.set_gps_to_10hz {
+        Some(10)
+    } else {
+        config.gps_rate_hz
+    };
 
-    if config.set_gps_to_10hz {
-        println!("Setting GPS sample rate to 10Hz");
-        if let Err(e) = gps_resolution_to_10hz(&mut port) {
-            eprintln!("Failed to set GPS sample rate: {:?}", e);
+    let ack_timeout = Duration::from_millis(config.ubx_ack_timeout_ms);
+    if let Some(rate_hz) = rate_hz {
+        info!("Setting GPS sample rate to {}Hz", rate_hz);
+        if let Err(e) = set_gps_rate(&mut port, rate_hz, ack_timeout) {
+            error!("Failed to set GPS sample rate: {}", e);
         }
     }
 
+    send_ubx_init_commands(&mut port, &config.ubx_init_commands, ack_timeout);
+
     port
 }
 
+/// Opens the serial port with the configured path, baud rate and framing.
+///
+/// # Arguments
+///
+/// * `config` - A reference to the `AppConfig` struct containing serial port configuration information.
+///
+/// # Returns
+///
+/// * `io::Result<Box<dyn SerialPort>>` - The opened port or the error from opening it
+pub fn open_configured_port(config: &AppConfig) -> io::Result<Box<dyn SerialPort>> {
+    Ok(port_builder(config).open()?)
+}
+
+/// Builds the `serialport` builder for `open_configured_port` from the configuration.
+fn port_builder(config: &AppConfig) -> SerialPortBuilder {
+    serialport::new(&config.port_name, config.baud_rate as u32)
+        .data_bits(config.data_bits)
+        .parity(config.parity)
+        .stop_bits(config.stop_bits)
+        .flow_control(config.flow_control)
+        .timeout(READ_TIMEOUT)
+}
+
+/// Opens the port at each of `AUTO_BAUD_RATES` until one yields a valid NMEA sentence.
+///
+/// Each rate is listened to for `AUTO_BAUD_PROBE_TIME`. A rate only counts once a complete
+/// `$...*hh` sentence with a matching checksum is received, since a wrong rate still produces
+/// bytes, just garbled ones.
+///
+/// # Arguments
+///
+/// * `config` - A reference to the `AppConfig` struct containing serial port configuration information.
+///
+/// # Returns
+///
+/// * `Option<Box<dyn SerialPort>>` - The port opened at the detected rate, or `None` if no rate worked
+fn detect_baud_rate(config: &AppConfig) -> Option<Box<dyn SerialPort>> {
+    for rate in AUTO_BAUD_RATES {
+        info!("Trying baud rate {}", rate);
+
+        let mut port = match port_builder(config)
+            .baud_rate(rate)
+            .timeout(Duration::from_millis(100))
+            .open()
+        {
+            Ok(port) => port,
+            Err(e) => {
+                error!("Failed to open port at {} baud: {}", rate, e);
+                continue;
+            }
+        };
+
+        if receives_valid_sentence(&mut port) {
+            info!("Detected baud rate {}", rate);
+            if let Err(e) = port.set_timeout(READ_TIMEOUT) {
+                error!("Failed to set serial port timeout: {}", e);
+            }
+            return Some(port);
+        }
+    }
+
+    None
+}
+
+/// Reads from the port for `AUTO_BAUD_PROBE_TIME` and reports whether a valid sentence arrived.
+fn receives_valid_sentence(port: &mut Box<dyn SerialPort>) -> bool {
+    let deadline = Instant::now() + AUTO_BAUD_PROBE_TIME;
+    let mut received = Vec::new();
+    let mut buf = [0; 256];
+
+    while Instant::now() < deadline {
+        match port.read(&mut buf) {
+            Ok(t) => {
+                received.extend_from_slice(&buf[..t]);
+                if contains_valid_sentence(&received) {
+                    return true;
+                }
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::TimedOut => (),
+            Err(e) => {
+                error!("Serial port read error: {:?}", e);
+                return false;
+            }
+        }
+    }
+
+    false
+}
+
+/// Returns whether the bytes contain a complete NMEA sentence with a valid checksum.
+///
+/// Bytes before the `$` of a line are skipped, as the first line usually starts mid-sentence.
+fn contains_valid_sentence(data: &[u8]) -> bool {
+    String::from_utf8_lossy(data)
+        .lines()
+        .filter_map(|line| line.find('$').map(|start| line[start..].trim()))
+        .any(has_valid_checksum)
+}
+
+/// Writes the `ubx_init_commands` frames to the device in order.
+///
+/// Each frame's length and checksum is validated first; invalid frames are logged and skipped
+/// so a typo can't send garbage to the receiver. Commands the device rejects are logged.
+///
+/// # Arguments
+///
+/// * `port` - Mutable reference to the opened serial port
+/// * `commands` - Hex-encoded UBX frames
+/// * `ack_timeout` - How long to wait for each acknowledgement, see `send_ubx_command`
+fn send_ubx_init_commands(
+    port: &mut Box<dyn SerialPort>,
+    commands: &[String],
+    ack_timeout: Duration,
+) {
+    for command in commands {
+        let frame = match decode_ubx_frame(command) {
+            Ok(frame) => frame,
+            Err(e) => {
+                error!("Skipping invalid UBX init command: {}", e);
+                continue;
+            }
+        };
+
+        info!(
+            "Sending UBX init command class 0x{:02X} ID 0x{:02X}",
+            frame[2], frame[3]
+        );
+        if let Err(e) = send_ubx_command(port, &frame, ack_timeout) {
+            error!("Failed to send UBX init command: {}", e);
+        }
+    }
+}
+
+/// Writes a UBX command frame and waits up to `ack_timeout` for the device to acknowledge it.
+///
+/// Success is logged on UBX-ACK-ACK. When no acknowledgement arrives in time, e.g. from a
+/// receiver that doesn't acknowledge the message, a warning is logged and the command is assumed
+/// to have worked. NMEA sentences read while waiting are dropped. A zero `ack_timeout` only
+/// writes the frame.
+///
+/// # Returns
+///
+/// * `io::Result<()>` - Success, the write error, or an error if the device answered UBX-ACK-NAK
+fn send_ubx_command(
+    port: &mut Box<dyn SerialPort>,
+    frame: &[u8],
+    ack_timeout: Duration,
+) -> io::Result<()> {
+    let (class, id) = (frame[2], frame[3]);
+    port.write_all(frame)?;
+    if ack_timeout.is_zero() {
+        return Ok(());
+    }
+
+    match wait_for_ubx_ack(port, class, id, ack_timeout)? {
+        Some(UbxAck::Ack) => {
+            info!(
+                "UBX command class 0x{:02X} ID 0x{:02X} acknowledged",
+                class, id
+            );
+            Ok(())
+        }
+        Some(UbxAck::Nak) => Err(io::Error::other(format!(
+            "UBX command class 0x{:02X} ID 0x{:02X} rejected by the device (NAK)",
+            class, id
+        ))),
+        None => {
+            warn!(
+                "No acknowledgement of UBX command class 0x{:02X} ID 0x{:02X} within {}ms",
+                class,
+                id,
+                ack_timeout.as_millis()
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Reads from the port for up to `timeout` until the acknowledgement of the command with `class`
+/// and `id` arrives.
+///
+/// The port's read timeout is shortened while waiting and restored afterwards.
+///
+/// # Returns
+///
+/// * `io::Result<Option<UbxAck>>` - The acknowledgement, `None` on timeout, or a read error
+fn wait_for_ubx_ack(
+    port: &mut Box<dyn SerialPort>,
+    class: u8,
+    id: u8,
+    timeout: Duration,
+) -> io::Result<Option<UbxAck>> {
+    let read_timeout = port.timeout();
+    port.set_timeout(UBX_ACK_READ_TIMEOUT)?;
+
+    let deadline = Instant::now() + timeout;
+    let mut reader = FrameReader::default();
+    let mut buf = [0; 256];
+    let mut ack = Ok(None);
+    while Instant::now() < deadline {
+        match port.read(&mut buf) {
+            Ok(t) => {
+                if let Some(found) = find_ubx_ack(&mut reader, &buf[..t], class, id) {
+                    ack = Ok(Some(found));
+                    break;
+                }
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::TimedOut => (),
+            Err(e) => {
+                ack = Err(e);
+                break;
+            }
+        }
+    }
+
+    port.set_timeout(read_timeout)?;
+    ack
+}
+
+/// Adds received bytes to `reader` and returns the acknowledgement of the command with `class`
+/// and `id` among the completed frames, if any.
+fn find_ubx_ack(reader: &mut FrameReader, data: &[u8], class: u8, id: u8) -> Option<UbxAck> {
+    reader.push(data).iter().find_map(|frame| match frame {
+        Frame::Ubx(message) => ubx_ack_for(message, class, id),
+        Frame::Nmea(_) => None,
+    })
+}
+
+/// Print the serial ports available on this system.
+///
+/// Each port is listed with its type; USB ports also show their VID/PID and product string
+/// where available, which helps choosing the right `port_name`.
+///
+/// # Returns
+///
+/// * `serialport::Result<()>` - Success or the error from enumerating ports
+pub fn list_serial_ports() -> serialport::Result<()> {
+    let ports = serialport::available_ports()?;
+
+    if ports.is_empty() {
+        println!("No serial ports found.");
+        return Ok(());
+    }
+
+    println!("Available serial ports:");
+    for port in ports {
+        println!(
+            "  {}  {}",
+            port.port_name,
+            describe_port_type(&port.port_type)
+        );
+    }
+
+    Ok(())
+}
+
+/// Formats a serial port type for `list_serial_ports`.
+fn describe_port_type(port_type: &SerialPortType) -> String {
+    match port_type {
+        SerialPortType::UsbPort(info) => {
+            let mut description = format!("USB {:04x}:{:04x}", info.vid, info.pid);
+            if let Some(manufacturer) = &info.manufacturer {
+                description.push_str(&format!(" {}", manufacturer));
+            }
+            if let Some(product) = &info.product {
+                description.push_str(&format!(" {}", product));
+            }
+            description
+        }
+        SerialPortType::PciPort => "PCI".to_string(),
+        SerialPortType::BluetoothPort => "Bluetooth".to_string(),
+        SerialPortType::Unknown => "Unknown".to_string(),
+    }
+}
+
 /// Read data from the provided serial port and process it.
 ///
 /// This function takes a mutable reference to a boxed trait object representing a serial port,
-/// continuously reads data from the port, and processes the received data using the `process_data` function.
+/// continuously reads data from the port, splits it into NMEA sentences and UBX messages with a
+/// `FrameReader` and passes each of them to `handler`.
+/// After a read error, e.g. when the receiver is unplugged, the port is reopened with a
+/// `Backoff` delay until a read succeeds again or `max_consecutive_failures` is reached.
+/// The loop exits when 'q' is entered on stdin or the process receives SIGINT/SIGTERM, leaving
+/// the MQTT client connected so the caller can disconnect it cleanly.
 ///
 /// # Arguments
 ///
 /// * `port` - A mutable reference to a boxed trait object representing a serial port.
-pub fn read_from_port(port: &mut Box<dyn SerialPort>, config: &AppConfig) {
+/// * `config` - A reference to the `AppConfig` struct, used to reopen the port.
+/// * `handler` - Called with every frame, e.g. the `publishing_handler`.
+pub fn read_from_port(
+    port: &mut Box<dyn SerialPort>,
+    config: &AppConfig,
+    handler: &dyn Fn(&Frame),
+) {
     let mut serial_buf = vec![0; 1024];
-    let mqtt = setup_mqtt(&config);
+    let mut frames = FrameReader::default();
+    // Reopen at the rate the port was opened with, which `auto_baud` may have detected
+    let baud_rate = port.baud_rate().unwrap_or(config.baud_rate as u32);
+    let mut backoff = Backoff::new(
+        Duration::from_millis(config.reconnect_base_ms),
+        Duration::from_millis(config.reconnect_max_ms),
+    );
 
-    let (sender, receiver) = mpsc::channel();
-
-    thread::spawn({
-        let sender = sender.clone();
-        move || check_quit(sender)
-    });
+    let receiver = quit_channel();
 
     loop {
         if let Ok(message) = receiver.try_recv() {
-            if message == "q" {
-                println!("Received quit command. Exiting the program.");
+            if message == QUIT_COMMAND {
+                info!("Received quit command. Exiting the program.");
                 break;
             }
         }
 
         match port.read(serial_buf.as_mut_slice()) {
             Ok(t) if t > 0 => {
-                let data = &serial_buf[..t];
-                if let Err(e) = process_gps_data(data, config, mqtt.clone()) {
-                    eprintln!("Error processing GPS data: {:?}", e);
+                backoff.reset();
+                for frame in frames.push(&serial_buf[..t]) {
+                    handler(&frame);
                 }
             }
             Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => (),
-            Err(e) => eprintln!("Serial port read error: {:?}", e),
+            Err(e) => {
+                error!("Serial port read error: {:?}", e);
+                let delay = backoff.next_delay(random_fraction());
+                if config.max_consecutive_failures > 0
+                    && backoff.failures() >= config.max_consecutive_failures
+                {
+                    error!(
+                        "Giving up on {} after {} consecutive failures",
+                        config.port_name,
+                        backoff.failures()
+                    );
+                    break;
+                }
+
+                warn!("Reopening {} in {:?}", config.port_name, delay);
+                // Waiting on the quit channel keeps shutdown responsive during long delays
+                if receiver.recv_timeout(delay).as_deref() == Ok(QUIT_COMMAND) {
+                    info!("Received quit command. Exiting the program.");
+                    break;
+                }
+                match port_builder(config).baud_rate(baud_rate).open() {
+                    Ok(reopened) => {
+                        info!("Reopened {}", config.port_name);
+                        *port = reopened;
+                        frames = FrameReader::default();
+                    }
+                    Err(e) => warn!("Failed to reopen {}: {}", config.port_name, e),
+                }
+            }
             _ => (),
         }
     }
 }
 
-/// Configures GPS device to output at 10Hz sampling rate
+/// Exponential backoff between attempts to reopen the serial port.
 ///
-/// Sends UBX-CFG-RATE command to a ublox GPS device to set measurement
-/// rate to 100ms (10Hz). Uses UBX protocol format:
-/// - Header: 0xB5 0x62
-/// - Class/ID: 0x06 0x08 (CFG-RATE)
-/// - Payload: rate(U2), navRate(U2), timeRef(U2)
+/// The delay starts at the base delay and doubles with each consecutive failure up to the
+/// maximum. "Equal jitter" is applied: a random half of the delay is skipped, so devices that
+/// failed together spread out their reconnects instead of retrying in lockstep.
+struct Backoff {
+    base: Duration,
+    max: Duration,
+    failures: u32,
+}
+
+impl Backoff {
+    fn new(base: Duration, max: Duration) -> Self {
+        Backoff {
+            base,
+            max,
+            failures: 0,
+        }
+    }
+
+    /// Number of failures since the last `reset`.
+    fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Records a failure and returns the delay before the next attempt.
+    ///
+    /// `jitter` is a fraction in [0, 1) selecting where in the upper half of the delay to land.
+    fn next_delay(&mut self, jitter: f64) -> Duration {
+        let delay = self
+            .base
+            .saturating_mul(2u32.saturating_pow(self.failures))
+            .min(self.max);
+        self.failures = self.failures.saturating_add(1);
+        delay / 2 + (delay / 2).mul_f64(jitter)
+    }
+
+    /// Returns to the base delay after a successful read.
+    fn reset(&mut self) {
+        self.failures = 0;
+    }
+}
+
+/// Returns a pseudo-random fraction in [0, 1) from the randomly keyed std hasher.
+fn random_fraction() -> f64 {
+    let bits = RandomState::new().build_hasher().finish() >> 11;
+    bits as f64 / (1u64 << 53) as f64
+}
+
+/// Returns a channel receiving the quit command on 'q' + Enter, stdin EOF or SIGINT/SIGTERM.
+///
+/// Source loops poll it between reads to shut down cleanly. Every channel receives the
+/// command, so all sources stop when several are read at the same time.
+pub fn quit_channel() -> mpsc::Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    QUIT_SENDERS.lock().unwrap().push(sender);
+
+    QUIT_LISTENERS.call_once(|| {
+        thread::spawn(check_quit);
+        install_signal_handler();
+    });
+
+    receiver
+}
+
+/// Whether the quit command was sent with `broadcast_quit`.
+///
+/// When the source loops return without it, they stopped by themselves, e.g. after giving up on
+/// the serial port.
+pub fn quit_requested() -> bool {
+    QUIT_REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Sets the line that quits the application when entered on stdin, `quit_keyword` in the
+/// configuration. Leading and trailing whitespace is ignored.
+pub fn set_quit_keyword(keyword: &str) {
+    *QUIT_KEYWORD.lock().unwrap() = keyword.trim().to_string();
+}
+
+/// Sends the quit command to every channel returned by `quit_channel`.
+///
+/// Besides the quit keyword and the signal handler, this stops the remaining loops, e.g. the
+/// reload watcher once every source has stopped.
+pub fn broadcast_quit() {
+    QUIT_REQUESTED.store(true, Ordering::SeqCst);
+    for sender in QUIT_SENDERS.lock().unwrap().iter() {
+        // The loop of a source that already stopped no longer listens
+        let _ = sender.send(QUIT_COMMAND.to_string());
+    }
+}
+
+/// Installs a SIGINT/SIGTERM handler that sends the quit command to the read loops.
+///
+/// This lets the application shut down cleanly when run as a service without stdin,
+/// instead of being killed in the middle of a publish.
+fn install_signal_handler() {
+    let result = ctrlc::set_handler(|| {
+        info!("Received termination signal");
+        broadcast_quit();
+    });
+
+    if let Err(e) = result {
+        error!("Failed to install signal handler: {}", e);
+    }
+}
+
+/// Builds a UBX-CFG-RATE command frame for the given measurement rate.
+///
+/// The measurement period is `1000 / rate_hz` milliseconds, with one measurement per navigation
+/// solution and UTC as time reference. The payload is rate(U2), navRate(U2), timeRef(U2).
+///
+/// # Arguments
+///
+/// * `rate_hz` - Desired measurement rate in Hz. A rate of 0 is treated as 1Hz.
+///
+/// # Returns
+///
+/// * `Vec<u8>` - The complete UBX frame ready to be written to the device
+pub fn build_ubx_cfg_rate(rate_hz: u16) -> Vec<u8> {
+    let [rate_lo, rate_hi] = (1000 / rate_hz.max(1)).to_le_bytes();
+
+    build_ubx_frame(
+        0x06, // CFG
+        0x08, // RATE
+        &[
+            rate_lo, rate_hi, // Measurement rate (ms)
+            0x01, 0x00, // Navigation rate
+            0x01, 0x00, // Time reference
+        ],
+    )
+}
+
+/// Configures GPS device to output at the given sampling rate
+///
+/// Sends a UBX-CFG-RATE command built by `build_ubx_cfg_rate` to a ublox GPS device and waits
+/// for its acknowledgement with `send_ubx_command`.
 ///
 /// # Arguments
 ///
 /// * `port` - Mutable reference to serial port implementing SerialPort trait
+/// * `rate_hz` - Desired measurement rate in Hz
+/// * `ack_timeout` - How long to wait for the acknowledgement, zero to not wait
 ///
 /// # Returns
 ///
-/// * `io::Result<()>` - Success or IO error
+/// * `io::Result<()>` - Success, or the IO error or NAK, in which case the rate wasn't set
 ///
-pub fn gps_resolution_to_10hz(port: &mut Box<dyn SerialPort>) -> io::Result<()> {
-    port.write_all(&UBX_CFG_RATE_10HZ).map_err(|e| {
-        error!("Failed to set GPS sample rate: {}", e);
-        e
-    })?;
+pub fn set_gps_rate(
+    port: &mut Box<dyn SerialPort>,
+    rate_hz: u16,
+    ack_timeout: Duration,
+) -> io::Result<()> {
+    send_ubx_command(port, &build_ubx_cfg_rate(rate_hz), ack_timeout)?;
 
-    info!("GPS sample rate configured to 10Hz");
+    info!("GPS sample rate configured to {}Hz", rate_hz);
     Ok(())
 }
 
-/// Monitors standard input for quit command ('q' + Enter)
+/// Monitors standard input for the quit keyword ('q' + Enter by default)
 ///
 /// This function runs in a separate thread and monitors stdin for user input.
-/// When the quit command is detected, it sends a message through the provided channel.
-///
-/// # Arguments
-///
-/// * `sender` - Channel sender used to communicate quit command to main thread
+/// When the quit keyword is entered or stdin reaches EOF, e.g. because the pipe feeding it
+/// closed or Ctrl-D was pressed, the quit command is sent to every source loop with
+/// `broadcast_quit`.
 ///
 /// # Example
 ///
-/// ```
-/// use std::sync::mpsc;
-/// let (tx, rx) = mpsc::channel();
-/// std::thread::spawn(move || check_quit(tx));
+/// ```ignore
+/// std::thread::spawn(check_quit);
 /// ```
 ///
 /// # Notes
 ///
 /// - Blocks until user enters input
-/// - Exits when 'q' is entered or on stdin error
-fn check_quit(sender: mpsc::Sender<String>) {
+/// - Exits when the quit keyword is entered, on EOF or on stdin error
+/// - EOF of a null device stdin, as services get, only stops watching stdin
+fn check_quit() {
     let stdin = io::stdin();
+    let null_stdin = is_null_device(&stdin);
     let mut lines = stdin.lock().lines();
 
     loop {
         match lines.next() {
             Some(Ok(line)) => {
-                if line.trim() == QUIT_COMMAND {
-                    if let Err(e) = sender.send(QUIT_COMMAND.to_string()) {
-                        error!("Failed to send quit command: {}", e);
-                        break;
-                    }
+                if line.trim() == *QUIT_KEYWORD.lock().unwrap() {
+                    broadcast_quit();
                     break;
                 }
             }
@@ -163,7 +639,169 @@ fn check_quit(sender: mpsc::Sender<String>) {
                 error!("Error reading from stdin: {}", e);
                 break;
             }
-            None => break,
+            None if null_stdin => break,
+            None => {
+                info!("Reached the end of stdin");
+                broadcast_quit();
+                break;
+            }
+        }
+    }
+}
+
+/// Whether stdin is a character device other than a terminal, i.e. `/dev/null`.
+///
+/// Services started by systemd read from `/dev/null` and see EOF immediately, which must not
+/// stop them.
+#[cfg(unix)]
+fn is_null_device(stdin: &io::Stdin) -> bool {
+    use std::os::fd::AsFd;
+    use std::os::unix::fs::FileTypeExt;
+
+    if stdin.is_terminal() {
+        return false;
+    }
+    stdin
+        .as_fd()
+        .try_clone_to_owned()
+        .map(std::fs::File::from)
+        .and_then(|file| file.metadata())
+        .is_ok_and(|metadata| metadata.file_type().is_char_device())
+}
+
+/// The device type of stdin isn't checked on this platform, so EOF always quits.
+#[cfg(not(unix))]
+fn is_null_device(_stdin: &io::Stdin) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::template_config;
+    use crate::ubx::ubx_checksum;
+
+    /// UBX-CFG-RATE command bytes for 10Hz sampling, as previously hardcoded
+    const UBX_CFG_RATE_10HZ: [u8; 14] = [
+        0xB5, 0x62, // Header
+        0x06, 0x08, // Class/ID
+        0x06, 0x00, // Length
+        0x64, 0x00, // Measurement rate (100ms)
+        0x01, 0x00, // Navigation rate
+        0x01, 0x00, // Time reference
+        0x7A, 0x12, // Checksum
+    ];
+
+    #[test]
+    fn test_backoff_sequence() {
+        let mut backoff = Backoff::new(Duration::from_millis(1000), Duration::from_millis(10000));
+        let delays: Vec<u128> = (0..6)
+            .map(|_| backoff.next_delay(0.0).as_millis())
+            .collect();
+        assert_eq!(delays, [500, 1000, 2000, 4000, 5000, 5000]);
+        assert_eq!(backoff.failures(), 6);
+
+        // The jitter spreads the delay over its upper half
+        assert_eq!(backoff.next_delay(0.5), Duration::from_millis(7500));
+
+        backoff.reset();
+        assert_eq!(backoff.failures(), 0);
+        assert_eq!(backoff.next_delay(0.0), Duration::from_millis(500));
+        assert_eq!(backoff.next_delay(0.999), Duration::from_millis(1999));
+    }
+
+    #[test]
+    fn test_random_fraction_in_range() {
+        for _ in 0..100 {
+            assert!((0.0..1.0).contains(&random_fraction()));
         }
     }
+
+    #[test]
+    fn test_describe_port_type() {
+        let usb = SerialPortType::UsbPort(serialport::UsbPortInfo {
+            vid: 0x1546,
+            pid: 0x01a8,
+            serial_number: None,
+            manufacturer: Some("u-blox AG".to_string()),
+            product: Some("u-blox GNSS receiver".to_string()),
+        });
+        assert_eq!(
+            describe_port_type(&usb),
+            "USB 1546:01a8 u-blox AG u-blox GNSS receiver"
+        );
+        assert_eq!(describe_port_type(&SerialPortType::PciPort), "PCI");
+        assert_eq!(describe_port_type(&SerialPortType::Unknown), "Unknown");
+    }
+
+    #[test]
+    fn test_port_builder_applies_config() {
+        let mut config = template_config();
+        config.baud_rate = 115200;
+        config.parity = serialport::Parity::Even;
+        config.data_bits = serialport::DataBits::Seven;
+
+        assert_eq!(
+            port_builder(&config),
+            serialport::new("/dev/ttyACM0", 115200)
+                .data_bits(serialport::DataBits::Seven)
+                .parity(serialport::Parity::Even)
+                .stop_bits(serialport::StopBits::One)
+                .flow_control(serialport::FlowControl::None)
+                .timeout(READ_TIMEOUT)
+        );
+    }
+
+    #[test]
+    fn test_contains_valid_sentence() {
+        assert!(contains_valid_sentence(
+            b"\xb5\x62\x01\x07$GNVTG,054.7,T,034.4,M,005.5,N,010.2,K*56\r\n$GNGGA,12"
+        ));
+        // Garbage from a wrong baud rate and a truncated sentence don't count
+        assert!(!contains_valid_sentence(b"\xf0\x0e\x80\xfe$\x98\x1e*~\r\n"));
+        assert!(!contains_valid_sentence(b"$GNVTG,054.7,T,034.4,M,0"));
+        assert!(!contains_valid_sentence(b""));
+    }
+
+    #[test]
+    fn test_build_ubx_cfg_rate_matches_10hz_constant() {
+        assert_eq!(build_ubx_cfg_rate(10), UBX_CFG_RATE_10HZ);
+    }
+
+    #[test]
+    fn test_build_ubx_cfg_rate_other_rates() {
+        // 1Hz -> 1000ms period
+        let frame = build_ubx_cfg_rate(1);
+        assert_eq!(&frame[6..8], &[0xE8, 0x03]);
+        assert_eq!(ubx_checksum(&frame[2..12]), (frame[12], frame[13]));
+
+        // 5Hz -> 200ms period
+        let frame = build_ubx_cfg_rate(5);
+        assert_eq!(&frame[6..8], &[0xC8, 0x00]);
+        assert_eq!(ubx_checksum(&frame[2..12]), (frame[12], frame[13]));
+    }
+
+    #[test]
+    fn test_find_ubx_ack() {
+        let ack_cfg_msg = build_ubx_frame(0x05, 0x01, &[0x06, 0x01]);
+        let ack_cfg_rate = build_ubx_frame(0x05, 0x01, &[0x06, 0x08]);
+        let nak_cfg_rate = build_ubx_frame(0x05, 0x00, &[0x06, 0x08]);
+
+        // An ACK split over two reads, after NMEA output and the ACK of another command
+        let mut reader = FrameReader::default();
+        let mut data = b"$GPGGA,123519,4807.038,N*47\r\n".to_vec();
+        data.extend_from_slice(&ack_cfg_msg);
+        data.extend_from_slice(&ack_cfg_rate[..5]);
+        assert_eq!(find_ubx_ack(&mut reader, &data, 0x06, 0x08), None);
+        assert_eq!(
+            find_ubx_ack(&mut reader, &ack_cfg_rate[5..], 0x06, 0x08),
+            Some(UbxAck::Ack)
+        );
+
+        let mut reader = FrameReader::default();
+        assert_eq!(
+            find_ubx_ack(&mut reader, &nak_cfg_rate, 0x06, 0x08),
+            Some(UbxAck::Nak)
+        );
+    }
 }
@@ -0,0 +1,44 @@
+/// Kilometers per hour in one knot.
+pub const KPH_PER_KNOT: f64 = 1.852;
+
+/// Meters per second in one knot.
+pub const MS_PER_KNOT: f64 = 0.514444;
+
+/// Miles per hour in one knot.
+pub const MPH_PER_KNOT: f64 = 1.15078;
+
+/// Converts a speed in knots to kilometers per hour.
+pub fn knots_to_kph(knots: f64) -> f64 {
+    knots * KPH_PER_KNOT
+}
+
+/// Converts a speed in knots to meters per second.
+pub fn knots_to_ms(knots: f64) -> f64 {
+    knots * MS_PER_KNOT
+}
+
+/// Converts a speed in knots to miles per hour.
+pub fn knots_to_mph(knots: f64) -> f64 {
+    knots * MPH_PER_KNOT
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_knots_conversions() {
+        assert!((knots_to_kph(10.0) - 18.52).abs() < 1e-9);
+        assert!((knots_to_ms(10.0) - 5.14444).abs() < 1e-9);
+        assert!((knots_to_mph(10.0) - 11.5078).abs() < 1e-9);
+        assert_eq!(knots_to_ms(0.0), 0.0);
+    }
+
+    #[test]
+    fn test_units_are_consistent() {
+        // 1 knot is 1852 m per hour
+        assert!((KPH_PER_KNOT / 3.6 - MS_PER_KNOT).abs() < 1e-6);
+        // 1 mile is 1609.344 m
+        assert!((KPH_PER_KNOT / 1.609344 - MPH_PER_KNOT).abs() < 1e-5);
+    }
+}
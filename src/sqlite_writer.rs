@@ -0,0 +1,237 @@
+use crate::gps_data_parser::PositionFix;
+use log::{debug, error, info};
+use rusqlite::{params, Connection};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Interval at which the open transaction is committed.
+const COMMIT_INTERVAL: Duration = Duration::from_secs(5);
+
+const CREATE_TABLE: &str = "CREATE TABLE IF NOT EXISTS fixes (
+    id INTEGER PRIMARY KEY,
+    timestamp TEXT NOT NULL,
+    lat REAL NOT NULL,
+    lon REAL NOT NULL,
+    alt REAL NOT NULL,
+    speed REAL NOT NULL,
+    course REAL NOT NULL,
+    fix_quality INTEGER NOT NULL,
+    sats INTEGER NOT NULL,
+    device_id TEXT NOT NULL DEFAULT ''
+)";
+
+/// Adds the `device_id` column to `fixes` tables created before it existed.
+const ADD_DEVICE_ID: &str = "ALTER TABLE fixes ADD COLUMN device_id TEXT NOT NULL DEFAULT ''";
+
+const INSERT_FIX: &str =
+    "INSERT INTO fixes (timestamp, lat, lon, alt, speed, course, fix_quality, sats, device_id)
+    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)";
+
+lazy_static::lazy_static! {
+    static ref SQLITE_WRITER: Mutex<Option<SqliteWriter>> = Mutex::new(None);
+}
+
+/// Inserts position fixes into the `fixes` table of a SQLite database.
+///
+/// Rows are inserted with a cached prepared statement inside a transaction, which is committed
+/// every `COMMIT_INTERVAL` and on close, so a crash loses at most the last few seconds.
+pub struct SqliteWriter {
+    connection: Connection,
+    last_commit: Instant,
+}
+
+impl SqliteWriter {
+    /// Creates a writer on an open database, creating the `fixes` table if it doesn't exist and
+    /// adding the `device_id` column if it's missing.
+    pub fn new(connection: Connection) -> rusqlite::Result<Self> {
+        connection.execute_batch(CREATE_TABLE)?;
+        let has_device_id: bool = connection.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('fixes') WHERE name = 'device_id'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_device_id {
+            connection.execute_batch(ADD_DEVICE_ID)?;
+        }
+        Ok(SqliteWriter {
+            connection,
+            last_commit: Instant::now(),
+        })
+    }
+
+    /// Inserts one row for the fix of the source `device_id`, and commits when the transaction
+    /// is due.
+    ///
+    /// Fixes without quality and dead-reckoning estimates are skipped, see
+    /// `PositionFix::is_valid`. The speed is stored in knots.
+    pub fn add_fix(&mut self, fix: &PositionFix, device_id: &str) -> rusqlite::Result<()> {
+        if !fix.is_valid() {
+            debug!("Skipping invalid fix for SQLite");
+            return Ok(());
+        }
+
+        if self.connection.is_autocommit() {
+            self.connection.execute_batch("BEGIN")?;
+        }
+        self.connection
+            .prepare_cached(INSERT_FIX)?
+            .execute(params![
+                fix.timestamp,
+                fix.lat,
+                fix.lon,
+                fix.alt,
+                fix.speed,
+                fix.course,
+                fix.fix_quality,
+                fix.sats_used,
+                device_id
+            ])?;
+
+        if self.last_commit.elapsed() >= COMMIT_INTERVAL {
+            self.commit()?;
+        }
+        Ok(())
+    }
+
+    /// Commits the open transaction, if any.
+    pub fn commit(&mut self) -> rusqlite::Result<()> {
+        self.last_commit = Instant::now();
+        if !self.connection.is_autocommit() {
+            self.connection.execute_batch("COMMIT")?;
+        }
+        Ok(())
+    }
+}
+
+/// Opens the SQLite database at `path`, creating it and the `fixes` table on first run.
+///
+/// # Arguments
+///
+/// * `path` - Path of the database file
+pub fn init(path: &str) -> rusqlite::Result<()> {
+    let writer = SqliteWriter::new(Connection::open(path)?)?;
+    *SQLITE_WRITER.lock().unwrap() = Some(writer);
+    info!("Logging fixes to SQLite database {}", path);
+    Ok(())
+}
+
+/// Inserts a fix of the source `device_id` into the database if SQLite logging is enabled.
+pub fn write_fix(fix: &PositionFix, device_id: &str) {
+    if let Some(writer) = SQLITE_WRITER.lock().unwrap().as_mut() {
+        if let Err(e) = writer.add_fix(fix, device_id) {
+            error!("Error writing fix to SQLite: {}", e);
+        }
+    }
+}
+
+/// Commits the remaining rows and closes the database if SQLite logging is enabled.
+pub fn close() {
+    if let Some(mut writer) = SQLITE_WRITER.lock().unwrap().take() {
+        if let Err(e) = writer.commit() {
+            error!("Error committing fixes to SQLite: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gps_data_parser::test_fix;
+
+    #[test]
+    fn test_sqlite_rows() {
+        let fix = test_fix();
+        let invalid = PositionFix {
+            fix_quality: 0,
+            ..fix.clone()
+        };
+        let estimated = PositionFix {
+            fix_quality: 6,
+            ..fix.clone()
+        };
+
+        let mut writer = SqliteWriter::new(Connection::open_in_memory().unwrap()).unwrap();
+        writer.add_fix(&fix, "golf86-pi").unwrap();
+        writer.add_fix(&invalid, "golf86-pi").unwrap();
+        writer.add_fix(&estimated, "golf86-pi").unwrap();
+        writer.add_fix(&fix, "golf86-pi").unwrap();
+        assert!(!writer.connection.is_autocommit());
+
+        writer.commit().unwrap();
+        assert!(writer.connection.is_autocommit());
+
+        let count: i64 = writer
+            .connection
+            .query_row("SELECT COUNT(*) FROM fixes", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 2);
+
+        let row: (String, f64, f64, f64, f64, f64, i64, i64, String) = writer
+            .connection
+            .query_row(
+                "SELECT timestamp, lat, lon, alt, speed, course, fix_quality, sats, device_id
+                FROM fixes",
+                [],
+                |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                        row.get(5)?,
+                        row.get(6)?,
+                        row.get(7)?,
+                        row.get(8)?,
+                    ))
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            row,
+            (
+                "2024-03-23T12:35:19Z".to_string(),
+                48.1173,
+                11.5166667,
+                545.4,
+                22.4,
+                84.4,
+                1,
+                8,
+                "golf86-pi".to_string()
+            )
+        );
+
+        // Reopening an existing database keeps its rows
+        let writer = SqliteWriter::new(writer.connection).unwrap();
+        let count: i64 = writer
+            .connection
+            .query_row("SELECT COUNT(*) FROM fixes", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn test_sqlite_adds_device_id() {
+        let connection = Connection::open_in_memory().unwrap();
+        // The table as created before the `device_id` column
+        connection
+            .execute_batch(
+                "CREATE TABLE fixes (id INTEGER PRIMARY KEY, timestamp TEXT NOT NULL,
+                lat REAL NOT NULL, lon REAL NOT NULL, alt REAL NOT NULL, speed REAL NOT NULL,
+                course REAL NOT NULL, fix_quality INTEGER NOT NULL, sats INTEGER NOT NULL)",
+            )
+            .unwrap();
+
+        let writer = SqliteWriter::new(connection).unwrap();
+        let columns: i64 = writer
+            .connection
+            .query_row(
+                "SELECT COUNT(*) FROM pragma_table_info('fixes') WHERE name = 'device_id'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(columns, 1);
+    }
+}
@@ -0,0 +1,118 @@
+use crate::gps_data_parser::{has_valid_checksum, position_json, PositionFix};
+use log::{error, warn};
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+lazy_static::lazy_static! {
+    static ref STDOUT_FORMAT: Mutex<Option<StdoutFormat>> = Mutex::new(None);
+}
+
+/// Machine-readable stream written to stdout with `--stdout-format`, next to MQTT publishing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StdoutFormat {
+    /// Every received sentence with a valid checksum, as received
+    Nmea,
+    /// One JSON object per complete fix, the `POSITION` document of JSON mode
+    Jsonl,
+}
+
+impl FromStr for StdoutFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "nmea" => Ok(StdoutFormat::Nmea),
+            "jsonl" => Ok(StdoutFormat::Jsonl),
+            _ => Err(format!(
+                "invalid stdout format: {} (expected nmea or jsonl)",
+                format
+            )),
+        }
+    }
+}
+
+/// Returns the line written for a received sentence in `format`, if any.
+fn sentence_line(format: StdoutFormat, sentence: &str) -> Option<&str> {
+    (format == StdoutFormat::Nmea && has_valid_checksum(sentence)).then_some(sentence)
+}
+
+/// Returns the line written for a fix in `format`, if any.
+fn fix_line(format: StdoutFormat, fix: &PositionFix, device_id: &str) -> Option<String> {
+    if format != StdoutFormat::Jsonl {
+        return None;
+    }
+
+    match position_json(fix, device_id) {
+        Ok(line) => Some(line),
+        Err(e) => {
+            error!("Error serializing position to JSON: {:?}", e);
+            None
+        }
+    }
+}
+
+/// Starts writing the `format` stream to stdout.
+pub fn init(format: StdoutFormat) {
+    *STDOUT_FORMAT.lock().unwrap() = Some(format);
+}
+
+/// Writes a received sentence to stdout if the `nmea` stream is enabled.
+pub fn write_sentence(sentence: &str) {
+    write_with(|format| sentence_line(format, sentence).map(str::to_string));
+}
+
+/// Writes a fix to stdout if the `jsonl` stream is enabled.
+pub fn write_fix(fix: &PositionFix, device_id: &str) {
+    write_with(|format| fix_line(format, fix, device_id));
+}
+
+/// Writes the line `line_for` returns for the enabled format, and flushes it so a reader at the
+/// other end of a pipe sees it right away.
+///
+/// The stream is turned off after a write error, e.g. when the reading process exited.
+fn write_with(line_for: impl FnOnce(StdoutFormat) -> Option<String>) {
+    let mut format = STDOUT_FORMAT.lock().unwrap();
+    let Some(line) = (*format).and_then(line_for) else {
+        return;
+    };
+
+    let mut stdout = io::stdout().lock();
+    if let Err(e) = writeln!(stdout, "{}", line).and_then(|_| stdout.flush()) {
+        warn!("Stopping the stdout stream after a write error: {}", e);
+        *format = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gps_data_parser::test_fix;
+
+    #[test]
+    fn test_stdout_lines() {
+        assert_eq!("nmea".parse(), Ok(StdoutFormat::Nmea));
+        assert_eq!("jsonl".parse(), Ok(StdoutFormat::Jsonl));
+        assert!("json".parse::<StdoutFormat>().is_err());
+
+        let sentence = "$GNVTG,054.7,T,034.4,M,005.5,N,010.2,K*56";
+        assert_eq!(sentence_line(StdoutFormat::Nmea, sentence), Some(sentence));
+        assert_eq!(sentence_line(StdoutFormat::Jsonl, sentence), None);
+        assert_eq!(
+            sentence_line(
+                StdoutFormat::Nmea,
+                "$GNVTG,054.7,T,034.4,M,005.5,N,010.2,K*57"
+            ),
+            None
+        );
+
+        let fix = test_fix();
+        let line = fix_line(StdoutFormat::Jsonl, &fix, "car").unwrap();
+        assert!(!line.contains('\n'));
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(json["device_id"], "car");
+        assert_eq!(json["lat"], 48.1173);
+        assert_eq!(json["sats_used"], 8);
+        assert_eq!(fix_line(StdoutFormat::Nmea, &fix, "car"), None);
+    }
+}
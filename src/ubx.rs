@@ -0,0 +1,287 @@
+/// Sync characters starting every UBX frame.
+pub const UBX_SYNC: [u8; 2] = [0xB5, 0x62];
+
+/// Bytes of a UBX frame around the payload: sync, class, ID, length and checksum.
+pub const UBX_OVERHEAD: usize = 8;
+
+/// UBX-MON-HW message class and ID.
+pub const UBX_MON_HW: (u8, u8) = (0x0A, 0x09);
+
+/// UBX-ACK-ACK and UBX-ACK-NAK message class and IDs.
+pub const UBX_ACK_ACK: (u8, u8) = (0x05, 0x01);
+pub const UBX_ACK_NAK: (u8, u8) = (0x05, 0x00);
+
+/// The device's response to a UBX-CFG command.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UbxAck {
+    /// UBX-ACK-ACK, the command was applied
+    Ack,
+    /// UBX-ACK-NAK, the command was rejected
+    Nak,
+}
+
+/// A UBX message received from the device.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UbxMessage {
+    pub class: u8,
+    pub id: u8,
+    pub payload: Vec<u8>,
+}
+
+/// Antenna and jamming state from a UBX-MON-HW message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonHw {
+    /// Antenna supervisor state: `INIT`, `DONTKNOW`, `OK`, `SHORT` or `OPEN`
+    pub antenna_status: &'static str,
+    /// Jamming state from the flags: `unknown`, `ok`, `warning` or `critical`
+    pub jamming_state: &'static str,
+    /// CW jamming indicator, 0 (no jamming) to 255 (strong jamming)
+    pub jamming_indicator: u8,
+}
+
+/// Builds a complete UBX frame for the given message class, ID and payload.
+///
+/// Uses UBX protocol format:
+/// - Header: 0xB5 0x62
+/// - Class/ID
+/// - Length: payload length as U2 (little endian)
+/// - Payload
+/// - Checksum: 8-bit Fletcher over class, ID, length and payload
+///
+/// # Arguments
+///
+/// * `class` - The message class, e.g. 0x06 for CFG
+/// * `id` - The message ID within the class
+/// * `payload` - The message payload, at most 65535 bytes
+///
+/// # Returns
+///
+/// * `Vec<u8>` - The frame ready to be written to the device
+pub fn build_ubx_frame(class: u8, id: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(payload.len() + UBX_OVERHEAD);
+    frame.extend_from_slice(&UBX_SYNC);
+    frame.extend_from_slice(&[class, id]);
+    frame.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+    frame.extend_from_slice(payload);
+
+    let (ck_a, ck_b) = ubx_checksum(&frame[2..]);
+    frame.extend_from_slice(&[ck_a, ck_b]);
+    frame
+}
+
+/// Computes the 8-bit Fletcher checksum used by the UBX protocol.
+///
+/// # Arguments
+///
+/// * `data` - The frame bytes between the sync header and the checksum (class, ID, length, payload)
+pub fn ubx_checksum(data: &[u8]) -> (u8, u8) {
+    data.iter().fold((0u8, 0u8), |(ck_a, ck_b), byte| {
+        let ck_a = ck_a.wrapping_add(*byte);
+        (ck_a, ck_b.wrapping_add(ck_a))
+    })
+}
+
+/// Decodes a hex-encoded UBX frame from `ubx_init_commands` and checks it is complete.
+///
+/// Whitespace between bytes is allowed, so both `B562060800...` and `B5 62 06 08 00 ...` work.
+///
+/// # Returns
+///
+/// * `Result<Vec<u8>, String>` - The frame bytes, or why the frame is invalid
+pub fn decode_ubx_frame(hex: &str) -> Result<Vec<u8>, String> {
+    let digits: Vec<char> = hex.chars().filter(|c| !c.is_whitespace()).collect();
+
+    let frame = digits
+        .chunks(2)
+        .map(|pair| {
+            if pair.len() < 2 {
+                return Err(format!("odd number of hex digits in {:?}", hex));
+            }
+            let byte: String = pair.iter().collect();
+            u8::from_str_radix(&byte, 16).map_err(|_| format!("invalid hex byte {:?}", byte))
+        })
+        .collect::<Result<Vec<u8>, String>>()?;
+
+    if frame.len() < UBX_OVERHEAD || frame[..2] != UBX_SYNC {
+        return Err(format!("{:?} is not a UBX frame", hex));
+    }
+
+    let payload_len = u16::from_le_bytes([frame[4], frame[5]]) as usize;
+    if frame.len() != payload_len + UBX_OVERHEAD {
+        return Err(format!(
+            "length field {} doesn't match the {} payload bytes of {:?}",
+            payload_len,
+            frame.len() - UBX_OVERHEAD,
+            hex
+        ));
+    }
+
+    let (ck_a, ck_b) = ubx_checksum(&frame[2..frame.len() - 2]);
+    if frame[frame.len() - 2..] != [ck_a, ck_b] {
+        return Err(format!(
+            "checksum mismatch in {:?} (expected {:02X} {:02X})",
+            hex, ck_a, ck_b
+        ));
+    }
+
+    Ok(frame)
+}
+
+/// Returns the acknowledgement of the command with `class` and `id` carried by `message`.
+///
+/// Returns `None` for any other message, including acknowledgements of other commands.
+pub fn ubx_ack_for(message: &UbxMessage, class: u8, id: u8) -> Option<UbxAck> {
+    if message.payload.len() < 2 || message.payload[..2] != [class, id] {
+        return None;
+    }
+
+    match (message.class, message.id) {
+        UBX_ACK_ACK => Some(UbxAck::Ack),
+        UBX_ACK_NAK => Some(UbxAck::Nak),
+        _ => None,
+    }
+}
+
+/// Parses the payload of a UBX-MON-HW message.
+///
+/// Returns `None` if the payload is shorter than the 60 bytes of the message.
+pub fn parse_mon_hw(payload: &[u8]) -> Option<MonHw> {
+    if payload.len() < 60 {
+        return None;
+    }
+
+    let antenna_status = match payload[20] {
+        0 => "INIT",
+        1 => "DONTKNOW",
+        2 => "OK",
+        3 => "SHORT",
+        4 => "OPEN",
+        _ => "DONTKNOW",
+    };
+
+    let jamming_state = match (payload[22] >> 2) & 0x03 {
+        1 => "ok",
+        2 => "warning",
+        3 => "critical",
+        _ => "unknown",
+    };
+
+    Some(MonHw {
+        antenna_status,
+        jamming_state,
+        jamming_indicator: payload[45],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// UBX-CFG-RATE command bytes for 10Hz sampling
+    const UBX_CFG_RATE_10HZ: [u8; 14] = [
+        0xB5, 0x62, 0x06, 0x08, 0x06, 0x00, 0x64, 0x00, 0x01, 0x00, 0x01, 0x00, 0x7A, 0x12,
+    ];
+
+    #[test]
+    fn test_build_ubx_frame_matches_cfg_rate() {
+        let frame = build_ubx_frame(0x06, 0x08, &[0x64, 0x00, 0x01, 0x00, 0x01, 0x00]);
+        assert_eq!(frame, UBX_CFG_RATE_10HZ);
+
+        // Polling a message has an empty payload
+        assert_eq!(
+            build_ubx_frame(0x06, 0x08, &[]),
+            [0xB5, 0x62, 0x06, 0x08, 0x00, 0x00, 0x0E, 0x30]
+        );
+    }
+
+    #[test]
+    fn test_decode_ubx_frame() {
+        assert_eq!(
+            decode_ubx_frame("B5 62 06 08 06 00 64 00 01 00 01 00 7A 12"),
+            Ok(UBX_CFG_RATE_10HZ.to_vec())
+        );
+        assert_eq!(
+            decode_ubx_frame("b562060806006400010001007a12"),
+            Ok(UBX_CFG_RATE_10HZ.to_vec())
+        );
+
+        assert!(
+            decode_ubx_frame("B5 62 06 08 06 00 64 00 01 00 01 00 7A 13")
+                .unwrap_err()
+                .starts_with("checksum mismatch")
+        );
+        assert!(
+            decode_ubx_frame("B5 62 06 08 07 00 64 00 01 00 01 00 7A 12")
+                .unwrap_err()
+                .starts_with("length field 7")
+        );
+        assert!(decode_ubx_frame("24 47 50")
+            .unwrap_err()
+            .ends_with("is not a UBX frame"));
+        assert!(decode_ubx_frame("B5 6").is_err());
+        assert!(decode_ubx_frame("B5 ZZ").is_err());
+    }
+
+    #[test]
+    fn test_ubx_ack_for() {
+        let message = |(class, id): (u8, u8), payload: &[u8]| UbxMessage {
+            class,
+            id,
+            payload: payload.to_vec(),
+        };
+
+        // Acknowledgements of CFG-RATE
+        assert_eq!(
+            ubx_ack_for(&message(UBX_ACK_ACK, &[0x06, 0x08]), 0x06, 0x08),
+            Some(UbxAck::Ack)
+        );
+        assert_eq!(
+            ubx_ack_for(&message(UBX_ACK_NAK, &[0x06, 0x08]), 0x06, 0x08),
+            Some(UbxAck::Nak)
+        );
+
+        // Of another command, another message and a truncated payload
+        assert_eq!(
+            ubx_ack_for(&message(UBX_ACK_ACK, &[0x06, 0x01]), 0x06, 0x08),
+            None
+        );
+        assert_eq!(
+            ubx_ack_for(&message(UBX_MON_HW, &[0x06, 0x08]), 0x06, 0x08),
+            None
+        );
+        assert_eq!(
+            ubx_ack_for(&message(UBX_ACK_ACK, &[0x06]), 0x06, 0x08),
+            None
+        );
+    }
+
+    /// Builds a MON-HW payload with the given antenna status, flags and jamming indicator.
+    fn mon_hw_payload(antenna_status: u8, flags: u8, jamming_indicator: u8) -> Vec<u8> {
+        let mut payload = vec![0; 60];
+        payload[20] = antenna_status;
+        payload[22] = flags;
+        payload[45] = jamming_indicator;
+        payload
+    }
+
+    #[test]
+    fn test_parse_mon_hw() {
+        assert_eq!(
+            parse_mon_hw(&mon_hw_payload(2, 0x04, 12)),
+            Some(MonHw {
+                antenna_status: "OK",
+                jamming_state: "ok",
+                jamming_indicator: 12,
+            })
+        );
+        assert_eq!(
+            parse_mon_hw(&mon_hw_payload(4, 0x0C, 200)),
+            Some(MonHw {
+                antenna_status: "OPEN",
+                jamming_state: "critical",
+                jamming_indicator: 200,
+            })
+        );
+        assert_eq!(parse_mon_hw(&[0; 59]), None);
+    }
+}
@@ -0,0 +1,119 @@
+use crate::config::AppConfig;
+use crate::frame_reader::{Frame, FrameReader};
+use crate::serial_port_handler::quit_channel;
+use log::{error, info};
+use std::io;
+use std::net::UdpSocket;
+use std::time::Duration;
+
+/// Largest datagram accepted. NMEA gateways send a few sentences per packet, far below this.
+const MAX_DATAGRAM_LEN: usize = 65_535;
+/// Receive timeout, bounding how long a quit command waits while no datagrams arrive.
+const UDP_READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Receive NMEA datagrams on `udp_port` and process them.
+///
+/// A datagram may carry one or several sentences, or only part of one: bytes are buffered in a
+/// `FrameReader` across datagrams, so a sentence split over two packets is still delivered
+/// whole. The loop exits when 'q' is entered on stdin or the process receives SIGINT/SIGTERM.
+///
+/// # Arguments
+///
+/// * `config` - A reference to the `AppConfig` struct.
+/// * `handler` - Called with every sentence, e.g. the `publishing_handler`.
+pub fn read_from_udp(config: &AppConfig, handler: &dyn Fn(&Frame)) {
+    let socket = match bind_udp(config.udp_port) {
+        Ok(socket) => socket,
+        Err(e) => {
+            error!("Unable to bind UDP port {}: {}", config.udp_port, e);
+            return;
+        }
+    };
+    info!(
+        "Listening for NMEA datagrams on UDP port {}",
+        config.udp_port
+    );
+
+    let quit = quit_channel();
+    let mut buffer = vec![0; MAX_DATAGRAM_LEN];
+    let mut frames = FrameReader::default();
+
+    loop {
+        if quit.try_recv().is_ok() {
+            info!("Received quit command. Exiting the program.");
+            return;
+        }
+
+        match receive_datagram(&socket, &mut buffer, &mut frames, handler) {
+            Ok(()) => (),
+            Err(ref e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) => {}
+            Err(e) => error!("UDP receive error: {}", e),
+        }
+    }
+}
+
+/// Binds a UDP socket on all interfaces, so broadcast datagrams are received too.
+fn bind_udp(port: u16) -> io::Result<UdpSocket> {
+    let socket = UdpSocket::bind(("0.0.0.0", port))?;
+    socket.set_read_timeout(Some(UDP_READ_TIMEOUT))?;
+    Ok(socket)
+}
+
+/// Waits for one datagram and passes the frames it completes to `handler`.
+fn receive_datagram(
+    socket: &UdpSocket,
+    buffer: &mut [u8],
+    frames: &mut FrameReader,
+    handler: &dyn Fn(&Frame),
+) -> io::Result<()> {
+    let len = socket.recv(buffer)?;
+    for frame in frames.push(&buffer[..len]) {
+        handler(&frame);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_sentences_reassembled_across_datagrams() {
+        let socket = bind_udp(0).unwrap();
+        let port = socket.local_addr().unwrap().port();
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+        let datagrams: [&[u8]; 3] = [
+            b"$GNVTG,054.7,T,034.4,M,005.5,N,010.2,K*56\r\n$GNGGA,123519,48",
+            b"07.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47\r\n$GNZDA,2015",
+            b"30.00,04,07,2002,00,00*7A\r\n",
+        ];
+        for datagram in datagrams {
+            sender.send_to(datagram, ("127.0.0.1", port)).unwrap();
+        }
+
+        let received = Mutex::new(Vec::new());
+        let handler = |frame: &Frame| received.lock().unwrap().push(frame.clone());
+        let mut buffer = vec![0; MAX_DATAGRAM_LEN];
+        let mut frames = FrameReader::default();
+        for _ in datagrams {
+            receive_datagram(&socket, &mut buffer, &mut frames, &handler).unwrap();
+        }
+
+        assert_eq!(
+            received.into_inner().unwrap(),
+            [
+                Frame::Nmea("$GNVTG,054.7,T,034.4,M,005.5,N,010.2,K*56".to_string()),
+                Frame::Nmea(
+                    "$GNGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47".to_string()
+                ),
+                Frame::Nmea("$GNZDA,201530.00,04,07,2002,00,00*7A".to_string()),
+            ]
+        );
+    }
+}